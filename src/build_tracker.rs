@@ -8,11 +8,16 @@ use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::fs;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
 const TRACKER_DIR: &str = ".reen";
 const TRACKER_FILE: &str = "build_tracker.json";
 
+/// Size of the read buffer used when streaming a file through the hasher, so hashing memory
+/// usage stays constant regardless of file size.
+const HASH_BUFFER_SIZE: usize = 64 * 1024;
+
 /// Represents the stage in the build pipeline
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Stage {
@@ -33,8 +38,16 @@ pub struct FileTrack {
     pub input_hash: String,
     /// Hash of the output file(s)
     pub output_hash: String,
+    /// Path of the output file, used to re-verify its hash later
+    pub output_path: String,
     /// Timestamp of last update
     pub timestamp: String,
+    /// Combined hash of this file's resolved dependencies at the time it was last built, so
+    /// a dependency that changed in an earlier run (not just earlier in this same run) is
+    /// detected even though this file's own input bytes are unchanged. Empty for tracks
+    /// recorded before this field existed or for files with no dependencies.
+    #[serde(default)]
+    pub dependency_hash: String,
 }
 
 /// Main build tracker
@@ -44,6 +57,37 @@ pub struct BuildTracker {
     tracks: HashMap<String, HashMap<String, FileTrack>>,
 }
 
+/// Declares the pipeline's stage topology and path conventions declaratively, instead of
+/// hardcoding them into `upstream_changed`'s match arms. Each entry names a stage's single
+/// upstream stage and how to resolve the path that upstream's `input_hash` was recorded
+/// against for a given file name, so a transitive walk can invalidate correctly across
+/// multi-hop chains and new stages can be registered without touching the traversal logic.
+struct DependencyGraph {
+    upstream: HashMap<Stage, Stage>,
+    input_path: HashMap<Stage, fn(&str) -> PathBuf>,
+}
+
+impl DependencyGraph {
+    /// The stage topology used by `reen`'s draft -> spec -> implementation/tests -> compile
+    /// pipeline.
+    fn pipeline() -> Self {
+        let mut upstream = HashMap::new();
+        upstream.insert(Stage::Implementation, Stage::Specification);
+        upstream.insert(Stage::Tests, Stage::Specification);
+        upstream.insert(Stage::Compile, Stage::Implementation);
+
+        let mut input_path: HashMap<Stage, fn(&str) -> PathBuf> = HashMap::new();
+        input_path.insert(Stage::Specification, |name| {
+            PathBuf::from("drafts").join(format!("{}.md", name))
+        });
+        input_path.insert(Stage::Implementation, |name| {
+            PathBuf::from("contexts").join(format!("{}.md", name))
+        });
+
+        Self { upstream, input_path }
+    }
+}
+
 impl BuildTracker {
     /// Load the build tracker from disk, or create a new one
     pub fn load() -> Result<Self> {
@@ -81,6 +125,17 @@ impl BuildTracker {
         PathBuf::from(TRACKER_DIR).join(TRACKER_FILE)
     }
 
+    /// True if `name` has a recorded track for `stage`, i.e. its output path is an artifact this
+    /// tracker already knows it generated, rather than a file that merely happens to exist
+    /// (hand-written, left over from a different tool, or from a run this tracker never saw).
+    pub fn is_tracked(&self, stage: Stage, name: &str) -> bool {
+        let stage_key = format!("{:?}", stage);
+        self.tracks
+            .get(&stage_key)
+            .map(|stage_tracks| stage_tracks.contains_key(name))
+            .unwrap_or(false)
+    }
+
     /// Check if a file needs to be regenerated for a given stage
     ///
     /// Returns true if:
@@ -108,61 +163,178 @@ impl BuildTracker {
         // Compute current input hash
         let current_input_hash = Self::hash_file(input_path)?;
 
-        // If input hasn't changed, no need to regenerate
-        if current_input_hash == track.input_hash {
-            return Ok(false);
+        // If the input changed, we definitely need to regenerate
+        if current_input_hash != track.input_hash {
+            return Ok(true);
+        }
+
+        // An empty recorded output hash means the output wasn't written when we last
+        // recorded this stage (see `record`), so always treat it as needing regeneration.
+        if track.output_hash.is_empty() {
+            return Ok(true);
         }
 
-        Ok(true)
+        // The input is unchanged, but the output may have been hand-edited since it was
+        // generated. Recompute its hash so we don't silently overwrite or skip a manual edit.
+        let current_output_hash = Self::hash_file(output_path)?;
+        Ok(current_output_hash != track.output_hash)
     }
 
-    /// Check if any upstream stage has changed that would affect this stage
-    ///
-    /// For example:
-    /// - Implementation depends on Specification
-    /// - Compile/Run/Test depend on Implementation
-    pub fn upstream_changed(&self, stage: Stage, name: &str) -> Result<bool> {
-        match stage {
-            Stage::Specification => {
-                // First stage, no upstream
-                Ok(false)
-            }
-            Stage::Implementation | Stage::Tests => {
-                // Depends on Specification
-                // Check if specification was regenerated recently
-                let spec_stage_key = format!("{:?}", Stage::Specification);
-                if let Some(spec_tracks) = self.tracks.get(&spec_stage_key) {
-                    if let Some(spec_track) = spec_tracks.get(name) {
-                        // Get corresponding input path (draft)
-                        let draft_path = PathBuf::from("drafts").join(format!("{}.md", name));
-                        if draft_path.exists() {
-                            let current_hash = Self::hash_file(&draft_path)?;
-                            if current_hash != spec_track.input_hash {
-                                return Ok(true); // Draft changed, spec needs update
-                            }
-                        }
+    /// Like `needs_update`, but also invalidates a file whose `dependency_hash` (the combined
+    /// content hash of its resolved dependencies) no longer matches what was recorded at its
+    /// last build, so a dependency regenerated in an earlier, separate invocation is still
+    /// caught. Returns the reason for the decision alongside it, for `--explain-cache`.
+    pub fn needs_update_with_dependency_hash(
+        &self,
+        stage: Stage,
+        name: &str,
+        input_path: &Path,
+        output_path: &Path,
+        dependency_hash: &str,
+    ) -> Result<(bool, String)> {
+        if !output_path.exists() {
+            return Ok((true, "output file does not exist".to_string()));
+        }
+
+        let stage_key = format!("{:?}", stage);
+        let Some(stage_tracks) = self.tracks.get(&stage_key) else {
+            return Ok((true, "no previous build record for this stage".to_string()));
+        };
+        let Some(track) = stage_tracks.get(name) else {
+            return Ok((true, "no previous build record for this file".to_string()));
+        };
+
+        let current_input_hash = Self::hash_file(input_path)?;
+        if current_input_hash != track.input_hash {
+            return Ok((true, "input file content changed".to_string()));
+        }
+
+        if dependency_hash != track.dependency_hash {
+            return Ok((
+                true,
+                "a resolved dependency's content changed since the last build".to_string(),
+            ));
+        }
+
+        if track.output_hash.is_empty() {
+            return Ok((
+                true,
+                "output was not recorded on the previous build".to_string(),
+            ));
+        }
+
+        let current_output_hash = Self::hash_file(output_path)?;
+        if current_output_hash != track.output_hash {
+            return Ok((
+                true,
+                "output file was modified outside the pipeline".to_string(),
+            ));
+        }
+
+        Ok((
+            false,
+            "input, dependencies, and output are all unchanged".to_string(),
+        ))
+    }
+
+    /// Like `record`, but also persists `dependency_hash` so a later run can detect a
+    /// dependency that changed since this build via `needs_update_with_dependency_hash`.
+    pub fn record_with_dependency_hash(
+        &mut self,
+        stage: Stage,
+        name: &str,
+        input_path: &Path,
+        output_path: &Path,
+        dependency_hash: &str,
+    ) -> Result<()> {
+        self.record(stage, name, input_path, output_path)?;
+        let stage_key = format!("{:?}", stage);
+        if let Some(track) = self
+            .tracks
+            .get_mut(&stage_key)
+            .and_then(|tracks| tracks.get_mut(name))
+        {
+            track.dependency_hash = dependency_hash.to_string();
+        }
+        Ok(())
+    }
+
+    /// Report every tracked file whose on-disk output no longer matches its recorded hash,
+    /// i.e. it was modified outside the pipeline since it was last generated.
+    pub fn verify_outputs(&self) -> Vec<(Stage, String)> {
+        let mut mismatches = Vec::new();
+
+        for (stage_key, stage_tracks) in &self.tracks {
+            let Some(stage) = Self::stage_from_key(stage_key) else {
+                continue;
+            };
+
+            for (name, track) in stage_tracks {
+                if track.output_hash.is_empty() {
+                    continue;
+                }
+
+                let output_path = PathBuf::from(&track.output_path);
+                let current_hash = match Self::hash_file(&output_path) {
+                    Ok(hash) => hash,
+                    Err(_) => {
+                        // Output is missing or unreadable; it no longer matches the recorded hash.
+                        mismatches.push((stage, name.clone()));
+                        continue;
                     }
+                };
+
+                if current_hash != track.output_hash {
+                    mismatches.push((stage, name.clone()));
                 }
-                Ok(false)
             }
-            Stage::Compile => {
-                // Depends on Implementation
-                // Check if any implementation files changed
-                let impl_stage_key = format!("{:?}", Stage::Implementation);
-                if let Some(impl_tracks) = self.tracks.get(&impl_stage_key) {
-                    for (impl_name, impl_track) in impl_tracks {
-                        let spec_path = PathBuf::from("contexts").join(format!("{}.md", impl_name));
-                        if spec_path.exists() {
-                            let current_hash = Self::hash_file(&spec_path)?;
-                            if current_hash != impl_track.input_hash {
-                                return Ok(true); // Spec changed, impl needs update
+        }
+
+        mismatches
+    }
+
+    /// Maps a stage's `{:?}`-derived tracking key back to its `Stage` value.
+    fn stage_from_key(key: &str) -> Option<Stage> {
+        match key {
+            "Specification" => Some(Stage::Specification),
+            "Implementation" => Some(Stage::Implementation),
+            "Tests" => Some(Stage::Tests),
+            "Compile" => Some(Stage::Compile),
+            _ => None,
+        }
+    }
+
+    /// Check if any upstream stage has changed that would affect this stage.
+    ///
+    /// Walks `stage`'s transitive upstream stages (via `DependencyGraph::pipeline`) in
+    /// topological order, so a multi-hop chain like Compile -> Implementation -> Specification
+    /// correctly invalidates Compile when the original draft changes, not just when
+    /// Implementation itself is regenerated.
+    pub fn upstream_changed(&self, stage: Stage, name: &str) -> Result<bool> {
+        let graph = DependencyGraph::pipeline();
+
+        let mut current = stage;
+        while let Some(&upstream_stage) = graph.upstream.get(&current) {
+            let stage_key = format!("{:?}", upstream_stage);
+
+            if let Some(stage_tracks) = self.tracks.get(&stage_key) {
+                if let Some(track) = stage_tracks.get(name) {
+                    if let Some(resolve) = graph.input_path.get(&upstream_stage) {
+                        let path = resolve(name);
+                        if path.exists() {
+                            let current_hash = Self::hash_file(&path)?;
+                            if current_hash != track.input_hash {
+                                return Ok(true);
                             }
                         }
                     }
                 }
-                Ok(false)
             }
+
+            current = upstream_stage;
         }
+
+        Ok(false)
     }
 
     /// Record a successful file transformation
@@ -184,22 +356,62 @@ impl BuildTracker {
         stage_tracks.insert(name.to_string(), FileTrack {
             input_hash,
             output_hash,
+            output_path: output_path.to_string_lossy().into_owned(),
             timestamp,
+            dependency_hash: String::new(),
         });
 
         Ok(())
     }
 
-    /// Compute SHA256 hash of a file
+    /// Compute SHA256 hash of a file, streaming it through a reused fixed-size buffer so memory
+    /// usage stays constant regardless of file size.
     fn hash_file(path: &Path) -> Result<String> {
-        let content = fs::read(path)
+        let file = fs::File::open(path)
             .with_context(|| format!("Failed to read file: {}", path.display()))?;
 
+        Self::hash_reader(file)
+            .with_context(|| format!("Failed to hash file: {}", path.display()))
+    }
+
+    /// Streams `reader` through the hasher in fixed-size chunks until EOF.
+    fn hash_reader<R: Read>(mut reader: R) -> Result<String> {
         let mut hasher = Sha256::new();
-        hasher.update(&content);
-        let result = hasher.finalize();
+        let mut buffer = [0u8; HASH_BUFFER_SIZE];
+
+        loop {
+            let read = reader.read(&mut buffer).context("Failed to read while hashing")?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
 
-        Ok(hex::encode(result))
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    /// Copies `reader` into `writer` while hashing the bytes as they pass through, returning the
+    /// digest of what was written. Lets a caller capture an output's hash in-flight while it is
+    /// being generated, rather than writing the file and then re-reading it from disk to hash it.
+    pub(crate) fn hash_while_copying<R: Read, W: std::io::Write>(
+        mut reader: R,
+        mut writer: W,
+    ) -> Result<String> {
+        let mut hasher = Sha256::new();
+        let mut buffer = [0u8; HASH_BUFFER_SIZE];
+
+        loop {
+            let read = reader.read(&mut buffer).context("Failed to read while copying")?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            writer
+                .write_all(&buffer[..read])
+                .context("Failed to write while copying")?;
+        }
+
+        Ok(hex::encode(hasher.finalize()))
     }
 
     /// Get a summary of tracked files
@@ -254,6 +466,23 @@ mod tests {
         fs::remove_file(&test_file).ok();
     }
 
+    #[test]
+    fn test_hash_while_copying_matches_hash_file() {
+        let temp_dir = std::env::temp_dir();
+        let dest_file = temp_dir.join("test_hash_while_copying.txt");
+
+        let content = b"streamed content".to_vec();
+        let mut dest = fs::File::create(&dest_file).unwrap();
+        let tee_hash = BuildTracker::hash_while_copying(content.as_slice(), &mut dest).unwrap();
+        drop(dest);
+
+        let file_hash = BuildTracker::hash_file(&dest_file).unwrap();
+        assert_eq!(tee_hash, file_hash);
+        assert_eq!(fs::read(&dest_file).unwrap(), content);
+
+        fs::remove_file(&dest_file).ok();
+    }
+
     #[test]
     fn test_tracker_record_and_load() {
         let mut tracker = BuildTracker::default();
@@ -275,4 +504,25 @@ mod tests {
         fs::remove_file(&input_file).ok();
         fs::remove_file(&output_file).ok();
     }
+
+    #[test]
+    fn test_is_tracked() {
+        let mut tracker = BuildTracker::default();
+        assert!(!tracker.is_tracked(Stage::Specification, "test"));
+
+        let temp_dir = std::env::temp_dir();
+        let input_file = temp_dir.join("is_tracked_input.txt");
+        let output_file = temp_dir.join("is_tracked_output.txt");
+
+        fs::write(&input_file, "input content").unwrap();
+        fs::write(&output_file, "output content").unwrap();
+
+        tracker.record(Stage::Specification, "test", &input_file, &output_file).unwrap();
+        assert!(tracker.is_tracked(Stage::Specification, "test"));
+        assert!(!tracker.is_tracked(Stage::Implementation, "test"));
+        assert!(!tracker.is_tracked(Stage::Specification, "other"));
+
+        fs::remove_file(&input_file).ok();
+        fs::remove_file(&output_file).ok();
+    }
 }
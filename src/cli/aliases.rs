@@ -0,0 +1,157 @@
+//! Config-driven aliases for `clear cache`/`clear artifact` targets, modeled on cargo's own
+//! `[alias]` resolution: a short token read from `.reen/config.toml` expands into either
+//! another token or a `(stage, names)` pair before the caller's own `match target` arms run.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+const CONFIG_DIR: &str = ".reen";
+const CONFIG_FILE: &str = "config.toml";
+const MAX_ALIAS_DEPTH: usize = 8;
+
+/// One `[alias]` entry: either a plain rename (`spec = "specification"`) or a compound
+/// expansion that also pins a default set of names
+/// (`nightly = { stage = "tests", names = ["regression"] }`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum AliasEntry {
+    Simple(String),
+    Compound {
+        stage: String,
+        #[serde(default)]
+        names: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct RawConfig {
+    #[serde(default)]
+    alias: HashMap<String, AliasEntry>,
+}
+
+/// The parsed `[alias]` table, ready for repeated resolution calls within a single command.
+#[derive(Debug, Clone, Default)]
+pub struct AliasConfig {
+    aliases: HashMap<String, AliasEntry>,
+}
+
+/// Loads `.reen/config.toml` relative to the current directory. A missing file is not an
+/// error; it just means no aliases are defined.
+pub fn load_alias_config() -> Result<AliasConfig> {
+    let path = Path::new(CONFIG_DIR).join(CONFIG_FILE);
+    if !path.exists() {
+        return Ok(AliasConfig::default());
+    }
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let raw: RawConfig =
+        toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))?;
+    Ok(AliasConfig { aliases: raw.alias })
+}
+
+/// Expands `target`/`names` through the `[alias]` table until a base token (recognized by
+/// [`is_base_target`]) is reached. Guards against cyclic or overly-deep alias chains, and
+/// rejects unknown tokens with a clear error instead of silently falling through.
+pub fn resolve_alias(
+    config: &AliasConfig,
+    target: &str,
+    names: Vec<String>,
+) -> Result<(String, Vec<String>)> {
+    let mut current = target.to_string();
+    let mut current_names = names;
+    let mut seen = HashSet::new();
+
+    for _ in 0..MAX_ALIAS_DEPTH {
+        if is_base_target(&current) {
+            return Ok((current, current_names));
+        }
+        if !seen.insert(current.clone()) {
+            anyhow::bail!("Alias cycle detected while resolving '{}'", target);
+        }
+        match config.aliases.get(&current) {
+            Some(AliasEntry::Simple(next)) => current = next.clone(),
+            Some(AliasEntry::Compound { stage, names: alias_names }) => {
+                if current_names.is_empty() {
+                    current_names = alias_names.clone();
+                }
+                current = stage.clone();
+            }
+            None => anyhow::bail!(
+                "Unknown target or alias '{}'. Expected specification(s), implementation(s), \
+                 test(s), or a defined [alias] entry in .reen/config.toml.",
+                current
+            ),
+        }
+    }
+
+    anyhow::bail!(
+        "Alias '{}' did not resolve to a target within {} expansions; check .reen/config.toml for a cycle",
+        target,
+        MAX_ALIAS_DEPTH
+    )
+}
+
+fn is_base_target(target: &str) -> bool {
+    matches!(
+        target,
+        "specification" | "specifications" | "implementation" | "implementations" | "test" | "tests"
+    )
+}
+
+/// Every top-level subcommand name `reen` itself recognizes, so a `.reen/config.toml` alias
+/// can never shadow a built-in (it simply won't be looked up for one of these tokens).
+const BUILTIN_COMMANDS: &[&str] = &[
+    "create", "check", "review", "fix", "checkpoint", "commit", "rollback", "compile", "run",
+    "test", "watch", "clear", "cache", "help",
+];
+
+/// Splices a user-defined `[alias]` expansion (e.g. `gen = "create implementation"`) into
+/// `args` in place of its first positional token (`args[0]` being the program name), mirroring
+/// cargo's `aliased_command`: the alias value is split on whitespace into a command vector and
+/// spliced in, recursing to resolve chained aliases while guarding against cycles. A token that
+/// already names a built-in subcommand, or that matches no `[alias]` entry, is left untouched so
+/// clap can parse (or reject) it itself.
+pub fn expand_argv_alias(config: &AliasConfig, args: Vec<String>) -> Result<Vec<String>> {
+    expand_argv_alias_with_depth(config, args, &mut HashSet::new())
+}
+
+fn expand_argv_alias_with_depth(
+    config: &AliasConfig,
+    args: Vec<String>,
+    seen: &mut HashSet<String>,
+) -> Result<Vec<String>> {
+    if config.aliases.is_empty() {
+        return Ok(args);
+    }
+
+    let Some(first) = args.get(1) else {
+        return Ok(args);
+    };
+    if BUILTIN_COMMANDS.contains(&first.as_str()) {
+        return Ok(args);
+    }
+
+    let Some(AliasEntry::Simple(expansion)) = config.aliases.get(first) else {
+        return Ok(args);
+    };
+
+    if !seen.insert(first.clone()) {
+        anyhow::bail!("Alias cycle detected while resolving '{}'", first);
+    }
+    if seen.len() > MAX_ALIAS_DEPTH {
+        anyhow::bail!(
+            "Alias '{}' did not resolve to a built-in command within {} expansions; check .reen/config.toml for a cycle",
+            first,
+            MAX_ALIAS_DEPTH
+        );
+    }
+
+    let mut spliced = vec![args[0].clone()];
+    spliced.extend(expansion.split_whitespace().map(str::to_string));
+    spliced.extend(args.into_iter().skip(2));
+
+    expand_argv_alias_with_depth(config, spliced, seen)
+}
@@ -0,0 +1,299 @@
+use anyhow::{bail, Result};
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+/// A parsed `# cfg(...)` / `#[cfg(...)]` predicate tree: `all(...)`/`any(...)`/`not(...)`
+/// combinators over `key = "value"` leaves (`feature = "account"` being the common case).
+/// Mirrors the subset of `rustc`'s own `cfg` grammar that specs and generated code actually
+/// use, evaluated against an active configuration set supplied on the CLI rather than one
+/// rustc derives from the target triple.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CfgExpr {
+    Predicate { key: String, value: String },
+    All(Vec<CfgExpr>),
+    Any(Vec<CfgExpr>),
+    Not(Box<CfgExpr>),
+}
+
+impl CfgExpr {
+    /// Parses a single cfg predicate expression, e.g. `feature = "account"`,
+    /// `all(feature = "account", target = "wasm")`, or `not(feature = "legacy")`.
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(input)?,
+            pos: 0,
+        };
+        let expr = parser.parse_expr()?;
+        if parser.pos != parser.tokens.len() {
+            bail!("Unexpected trailing input in cfg expression: {}", input);
+        }
+        Ok(expr)
+    }
+
+    /// Evaluates this expression against the active `(key, value)` configuration set.
+    pub fn evaluate(&self, active: &HashSet<(String, String)>) -> bool {
+        match self {
+            CfgExpr::Predicate { key, value } => active.contains(&(key.clone(), value.clone())),
+            CfgExpr::All(exprs) => exprs.iter().all(|e| e.evaluate(active)),
+            CfgExpr::Any(exprs) => exprs.iter().any(|e| e.evaluate(active)),
+            CfgExpr::Not(inner) => !inner.evaluate(active),
+        }
+    }
+
+    /// Collects every `feature = "name"` referenced anywhere in this expression tree, so
+    /// callers can cross-check referenced features against a crate's declared `[features]`.
+    pub fn referenced_features(&self, out: &mut Vec<String>) {
+        match self {
+            CfgExpr::Predicate { key, value } if key == "feature" => out.push(value.clone()),
+            CfgExpr::Predicate { .. } => {}
+            CfgExpr::All(exprs) | CfgExpr::Any(exprs) => {
+                for expr in exprs {
+                    expr.referenced_features(out);
+                }
+            }
+            CfgExpr::Not(inner) => inner.referenced_features(out),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Eq,
+    Comma,
+    LParen,
+    RParen,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = input.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '=' => {
+                tokens.push(Token::Eq);
+                i += 1;
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            }
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            '"' => {
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        Some('"') => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&ch) => {
+                            value.push(ch);
+                            i += 1;
+                        }
+                        None => bail!("Unterminated string literal in cfg expression: {}", input),
+                    }
+                }
+                tokens.push(Token::Str(value));
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            other => bail!("Unexpected character '{}' in cfg expression: {}", other, input),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn bump(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn parse_expr(&mut self) -> Result<CfgExpr> {
+        match self.bump() {
+            Some(Token::Ident(name)) if name == "all" => {
+                Ok(CfgExpr::All(self.parse_expr_list()?))
+            }
+            Some(Token::Ident(name)) if name == "any" => {
+                Ok(CfgExpr::Any(self.parse_expr_list()?))
+            }
+            Some(Token::Ident(name)) if name == "not" => {
+                self.expect(Token::LParen)?;
+                let inner = self.parse_expr()?;
+                self.expect(Token::RParen)?;
+                Ok(CfgExpr::Not(Box::new(inner)))
+            }
+            Some(Token::Ident(key)) => {
+                self.expect(Token::Eq)?;
+                match self.bump() {
+                    Some(Token::Str(value)) => Ok(CfgExpr::Predicate { key, value }),
+                    other => bail!("Expected a quoted value after `{} =`, found {:?}", key, other),
+                }
+            }
+            other => bail!("Expected a cfg predicate or combinator, found {:?}", other),
+        }
+    }
+
+    fn parse_expr_list(&mut self) -> Result<Vec<CfgExpr>> {
+        self.expect(Token::LParen)?;
+        let mut exprs = Vec::new();
+        loop {
+            exprs.push(self.parse_expr()?);
+            match self.peek() {
+                Some(Token::Comma) => {
+                    self.bump();
+                }
+                Some(Token::RParen) => {
+                    self.bump();
+                    break;
+                }
+                other => bail!("Expected `,` or `)`, found {:?}", other),
+            }
+        }
+        Ok(exprs)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.bump() {
+            Some(token) if token == expected => Ok(()),
+            other => bail!("Expected {:?}, found {:?}", expected, other),
+        }
+    }
+}
+
+/// Parses repeatable `--cfg key="value"` CLI arguments into the active configuration set
+/// `CfgExpr::evaluate` checks predicates against.
+pub fn parse_active_cfgs(raw: &[String]) -> Result<HashSet<(String, String)>> {
+    let mut active = HashSet::new();
+    for entry in raw {
+        let Some((key, quoted_value)) = entry.split_once('=') else {
+            bail!("Invalid --cfg value (expected key=\"value\"): {}", entry);
+        };
+        let value = quoted_value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value);
+        active.insert((key.trim().to_string(), value.to_string()));
+    }
+    Ok(active)
+}
+
+/// Leading `# cfg(...)` front-matter line of a draft/specification file, if present. Only
+/// the first line is considered a cfg gate, matching how `extract_compile_error_message`
+/// already treats `#![cfg(...)]` as a header rather than scanning the whole body.
+pub fn extract_leading_cfg(content: &str) -> Option<CfgExpr> {
+    let first_line = content.lines().next()?.trim();
+    let inner = first_line
+        .strip_prefix("# cfg(")
+        .and_then(|rest| rest.strip_suffix(')'))?;
+    CfgExpr::parse(inner).ok()
+}
+
+/// The process-wide active cfg set, supplied via repeatable `--cfg key="value"` CLI flags
+/// and set once at startup. Read internally by `resolve_input_files` rather than threaded
+/// through its many call sites, the same caching approach `detect_active_cfgs` uses for the
+/// (conceptually distinct) rustc-autodetected cfg set.
+static ACTIVE_CFGS: OnceLock<HashSet<(String, String)>> = OnceLock::new();
+
+/// Sets the process-wide active cfg set from parsed CLI `--cfg` flags. Must be called at
+/// most once, before any code consults `active_cfgs`.
+pub fn set_active_cfgs(active: HashSet<(String, String)>) {
+    let _ = ACTIVE_CFGS.set(active);
+}
+
+/// Returns the process-wide active cfg set, empty if `set_active_cfgs` was never called
+/// (e.g. in tests that exercise cfg-aware code paths directly).
+pub fn active_cfgs() -> &'static HashSet<(String, String)> {
+    ACTIVE_CFGS.get_or_init(HashSet::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_simple_predicate() {
+        let expr = CfgExpr::parse(r#"feature = "account""#).unwrap();
+        assert_eq!(
+            expr,
+            CfgExpr::Predicate {
+                key: "feature".to_string(),
+                value: "account".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn evaluates_all_any_not() {
+        let mut active = HashSet::new();
+        active.insert(("feature".to_string(), "account".to_string()));
+
+        let expr = CfgExpr::parse(r#"all(feature = "account", not(feature = "legacy"))"#).unwrap();
+        assert!(expr.evaluate(&active));
+
+        let expr = CfgExpr::parse(r#"any(feature = "legacy", feature = "account")"#).unwrap();
+        assert!(expr.evaluate(&active));
+
+        let expr = CfgExpr::parse(r#"not(feature = "account")"#).unwrap();
+        assert!(!expr.evaluate(&active));
+    }
+
+    #[test]
+    fn collects_referenced_features() {
+        let expr =
+            CfgExpr::parse(r#"all(feature = "account", any(feature = "legacy", target = "wasm"))"#)
+                .unwrap();
+        let mut features = Vec::new();
+        expr.referenced_features(&mut features);
+        features.sort();
+        assert_eq!(features, vec!["account".to_string(), "legacy".to_string()]);
+    }
+
+    #[test]
+    fn extracts_leading_cfg_from_front_matter() {
+        let content = "# cfg(feature = \"account\")\n\n# Account\n\nSome spec body.";
+        let expr = extract_leading_cfg(content).expect("leading cfg");
+        assert_eq!(
+            expr,
+            CfgExpr::Predicate {
+                key: "feature".to_string(),
+                value: "account".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn parses_active_cfgs_from_cli_args() {
+        let active = parse_active_cfgs(&["feature=\"account\"".to_string()]).unwrap();
+        assert!(active.contains(&("feature".to_string(), "account".to_string())));
+    }
+}
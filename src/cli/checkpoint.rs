@@ -0,0 +1,248 @@
+//! Transactional checkpoints over the `.reen` cache and generated artifacts, modeled on
+//! openethereum's nested-checkpoint state design: a stack of frames, each recording the
+//! prior content (or absence) of every tracked file touched since it opened, so a
+//! multi-stage run can be `commit`ted (folded into its parent, keeping the changes) or
+//! `rollback`ed (every recorded file restored to what it was when the frame opened).
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const CHECKPOINT_DIR: &str = ".reen/checkpoints";
+const STACK_FILE: &str = ".reen/checkpoint_stack.json";
+
+/// A single file's state at the moment a checkpoint frame first touched it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TouchedFile {
+    path: String,
+    existed: bool,
+    /// Where the prior content was copied to, present only when `existed` is true.
+    snapshot: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+struct CheckpointFrame {
+    touched: Vec<TouchedFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+struct CheckpointStack {
+    frames: Vec<CheckpointFrame>,
+}
+
+impl CheckpointStack {
+    fn load() -> Result<Self> {
+        let path = Path::new(STACK_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content =
+            fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = Path::new(STACK_FILE).parent() {
+            fs::create_dir_all(parent).context("Failed to create .reen directory")?;
+        }
+        let content =
+            serde_json::to_string_pretty(self).context("Failed to serialize checkpoint stack")?;
+        fs::write(STACK_FILE, content).context("Failed to write checkpoint stack")
+    }
+}
+
+fn snapshot_path(frame_index: usize, path_str: &str) -> PathBuf {
+    let sanitized = path_str.replace(['/', '\\'], "__");
+    PathBuf::from(CHECKPOINT_DIR)
+        .join(frame_index.to_string())
+        .join(sanitized)
+}
+
+/// Opens a new checkpoint frame and returns its depth (1 for the outermost). Subsequent
+/// calls to [`record_before`] capture prior file state against the innermost open frame.
+pub fn open() -> Result<usize> {
+    let mut stack = CheckpointStack::load()?;
+    stack.frames.push(CheckpointFrame::default());
+    let depth = stack.frames.len();
+    stack.save()?;
+    Ok(depth)
+}
+
+/// True if at least one checkpoint frame is currently open.
+pub fn is_open() -> Result<bool> {
+    Ok(!CheckpointStack::load()?.frames.is_empty())
+}
+
+/// Records `path`'s current content (or absence) against the innermost open checkpoint
+/// frame, if it hasn't already been recorded this frame. A no-op when no frame is open.
+/// Call this immediately before overwriting or removing a tracked file.
+pub fn record_before(path: &Path) -> Result<()> {
+    let mut stack = CheckpointStack::load()?;
+    let Some(frame_index) = stack.frames.len().checked_sub(1) else {
+        return Ok(());
+    };
+
+    let path_str = path.to_string_lossy().into_owned();
+    let frame = &mut stack.frames[frame_index];
+    if frame.touched.iter().any(|t| t.path == path_str) {
+        return Ok(());
+    }
+
+    let existed = path.exists();
+    let snapshot = if existed {
+        let dest = snapshot_path(frame_index, &path_str);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create checkpoint snapshot directory")?;
+        }
+        fs::copy(path, &dest).with_context(|| format!("Failed to snapshot {}", path.display()))?;
+        Some(dest.to_string_lossy().into_owned())
+    } else {
+        None
+    };
+
+    frame.touched.push(TouchedFile {
+        path: path_str,
+        existed,
+        snapshot,
+    });
+    stack.save()
+}
+
+/// Commits the innermost open checkpoint frame: changes made since it opened are kept. If
+/// a parent frame is still open, the frame's recorded history folds into it (first-touch
+/// wins) so a rollback of the parent still restores to before this nested frame opened;
+/// otherwise the frame's on-disk snapshots are discarded as no longer needed.
+pub fn commit() -> Result<()> {
+    let mut stack = CheckpointStack::load()?;
+    let frame_index = stack
+        .frames
+        .len()
+        .checked_sub(1)
+        .context("No open checkpoint to commit")?;
+    let frame = stack.frames.pop().expect("checked above");
+
+    if let Some(parent) = stack.frames.last_mut() {
+        for touched in frame.touched {
+            if !parent.touched.iter().any(|t| t.path == touched.path) {
+                parent.touched.push(touched);
+            }
+        }
+    } else {
+        let _ = fs::remove_dir_all(PathBuf::from(CHECKPOINT_DIR).join(frame_index.to_string()));
+    }
+
+    stack.save()
+}
+
+/// Rolls back the innermost open checkpoint frame: every file it recorded is restored to
+/// its prior content, or removed if it didn't exist when the frame opened.
+pub fn rollback() -> Result<()> {
+    let mut stack = CheckpointStack::load()?;
+    let frame_index = stack
+        .frames
+        .len()
+        .checked_sub(1)
+        .context("No open checkpoint to roll back")?;
+    let frame = stack.frames.pop().expect("checked above");
+
+    for touched in frame.touched.iter().rev() {
+        let path = PathBuf::from(&touched.path);
+        if touched.existed {
+            let snapshot = touched
+                .snapshot
+                .as_ref()
+                .context("Checkpoint record is missing its snapshot path")?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+            fs::copy(snapshot, &path)
+                .with_context(|| format!("Failed to restore {}", path.display()))?;
+        } else if path.exists() {
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+    }
+
+    let _ = fs::remove_dir_all(PathBuf::from(CHECKPOINT_DIR).join(frame_index.to_string()));
+    stack.save()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Checkpoint state lives under the process's current directory, so tests that
+    // exercise it must not run concurrently with each other.
+    static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+    fn in_temp_dir<F: FnOnce()>(f: F) {
+        let _guard = TEST_LOCK.lock().unwrap();
+        let dir = std::env::temp_dir().join(format!("reen-checkpoint-test-{:?}", std::thread::current().id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let original = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+        f();
+        std::env::set_current_dir(original).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rollback_restores_modified_file_and_removes_new_one() {
+        in_temp_dir(|| {
+            fs::write("existing.txt", "before").unwrap();
+            open().unwrap();
+
+            record_before(Path::new("existing.txt")).unwrap();
+            fs::write("existing.txt", "after").unwrap();
+
+            record_before(Path::new("new.txt")).unwrap();
+            fs::write("new.txt", "generated").unwrap();
+
+            rollback().unwrap();
+
+            assert_eq!(fs::read_to_string("existing.txt").unwrap(), "before");
+            assert!(!Path::new("new.txt").exists());
+            assert!(!is_open().unwrap());
+        });
+    }
+
+    #[test]
+    fn commit_keeps_changes_and_closes_frame() {
+        in_temp_dir(|| {
+            open().unwrap();
+            record_before(Path::new("new.txt")).unwrap();
+            fs::write("new.txt", "generated").unwrap();
+
+            commit().unwrap();
+
+            assert_eq!(fs::read_to_string("new.txt").unwrap(), "generated");
+            assert!(!is_open().unwrap());
+        });
+    }
+
+    #[test]
+    fn nested_commit_folds_into_parent_for_later_rollback() {
+        in_temp_dir(|| {
+            open().unwrap();
+            record_before(Path::new("outer.txt")).unwrap();
+            fs::write("outer.txt", "outer-change").unwrap();
+
+            open().unwrap();
+            record_before(Path::new("inner.txt")).unwrap();
+            fs::write("inner.txt", "inner-change").unwrap();
+            commit().unwrap();
+
+            rollback().unwrap();
+
+            assert!(!Path::new("outer.txt").exists());
+            assert!(!Path::new("inner.txt").exists());
+            assert!(!is_open().unwrap());
+        });
+    }
+}
@@ -88,10 +88,26 @@ impl ExecutionNode {
         Ok(resolved)
     }
 
+    /// Resolves the full transitive dependency closure (unbounded depth).
     pub fn resolve_dependency_closure(
         &self,
         primary_root: &str,
         fallback_root: Option<&str>,
+    ) -> Result<Vec<DependencyArtifact>> {
+        self.resolve_dependency_closure_with_depth(primary_root, fallback_root, None)
+    }
+
+    /// Resolves the dependency closure, optionally bounded to `max_depth` hops from
+    /// this node. `max_depth == Some(1)` returns only direct dependencies; `None` walks
+    /// the full transitive closure. The walk is breadth-first, so nearer dependencies are
+    /// resolved before farther ones and a dependency reachable at several depths is
+    /// recorded (and expanded) at its nearest one. The returned list is sorted by path,
+    /// not by depth.
+    pub fn resolve_dependency_closure_with_depth(
+        &self,
+        primary_root: &str,
+        fallback_root: Option<&str>,
+        max_depth: Option<usize>,
     ) -> Result<Vec<DependencyArtifact>> {
         let primary_index = build_index(primary_root)?;
         let fallback_index = match fallback_root {
@@ -101,11 +117,16 @@ impl ExecutionNode {
         let primary_by_canonical = index_by_canonical(&primary_index);
         let fallback_by_canonical = index_by_canonical(&fallback_index);
 
-        let mut queue = self.direct_dependencies.clone();
+        let mut queue: std::collections::VecDeque<(DependencyLocator, usize)> = self
+            .direct_dependencies
+            .iter()
+            .cloned()
+            .map(|dep| (dep, 1))
+            .collect();
         let mut seen_paths = HashSet::new();
         let mut resolved = Vec::new();
 
-        while let Some(dep) = queue.pop() {
+        while let Some((dep, depth)) = queue.pop_front() {
             let (path, source) = match resolve_dependency_locator(&dep) {
                 Some(v) => v,
                 None => continue,
@@ -135,6 +156,10 @@ impl ExecutionNode {
                 sha256,
             });
 
+            if max_depth.is_some_and(|max| depth >= max) {
+                continue;
+            }
+
             let canonicals =
                 extract_dependency_canonicals(&content, &primary_index, &fallback_index);
             for canonical in canonicals {
@@ -153,22 +178,28 @@ impl ExecutionNode {
                             continue;
                         }
 
-                        queue.push(DependencyLocator {
-                            name: candidate.name.clone(),
-                            primary_path: Some(candidate.path.clone()),
-                            fallback_path: fallback_candidates.first().map(|f| f.path.clone()),
-                        });
+                        queue.push_back((
+                            DependencyLocator {
+                                name: candidate.name.clone(),
+                                primary_path: Some(candidate.path.clone()),
+                                fallback_path: fallback_candidates.first().map(|f| f.path.clone()),
+                            },
+                            depth + 1,
+                        ));
                     }
                 } else {
                     for candidate in fallback_candidates {
                         if candidate.path == path || candidate.path == self.input_path {
                             continue;
                         }
-                        queue.push(DependencyLocator {
-                            name: candidate.name.clone(),
-                            primary_path: None,
-                            fallback_path: Some(candidate.path.clone()),
-                        });
+                        queue.push_back((
+                            DependencyLocator {
+                                name: candidate.name.clone(),
+                                primary_path: None,
+                                fallback_path: Some(candidate.path.clone()),
+                            },
+                            depth + 1,
+                        ));
                     }
                 }
             }
@@ -1,33 +1,82 @@
 use anyhow::{Context, Result};
+use regex::Regex;
 use serde::Serialize;
 use serde_json::json;
 use sha2::{Digest, Sha256};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
 mod agent_executor;
+mod aliases;
+mod cfg_expr;
+mod checkpoint;
 mod compilation_fix;
 mod dependency_graph;
+mod patch_policy;
 mod progress;
 mod project_structure;
 
 use agent_executor::AgentExecutor;
+use cfg_expr::{extract_leading_cfg, CfgExpr};
 use dependency_graph::{build_execution_plan, DependencyArtifact, ExecutionNode};
 use progress::ProgressIndicator;
 use project_structure::{
-    analyze_specifications, generate_cargo_toml, generate_lib_rs, generate_mod_files, ProjectInfo,
+    analyze_specifications, analyze_specifications_as_workspace,
+    analyze_specifications_incremental, generate_cargo_toml, generate_lib_rs,
+    generate_mod_files, generate_mod_files_for_folders, package_root, resolve_cargo_metadata,
+    resolve_workspace_metadata, verify_generated_project, ProjectInfo,
 };
 use reen::build_tracker::{BuildTracker, Stage};
 use reen::contexts::{AgentModelRegistry, AgentRegistry};
 use reen::registries::{FileAgentModelRegistry, FileAgentRegistry};
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum DiagnosticFormat {
+    /// rustc/`annotate-snippets`-style framed source snippets (the default).
+    Fancy,
+    /// One flat `file:line:col: message` line per diagnostic, for CI logs.
+    Plain,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum TestRunner {
+    /// Use `cargo nextest` when it's installed, falling back to `cargo test` otherwise.
+    #[default]
+    Auto,
+    /// Always use `cargo test`.
+    Cargo,
+    /// Always use `cargo nextest run`, erroring if it isn't installed.
+    Nextest,
+}
+
+#[derive(Clone)]
 pub struct Config {
     pub verbose: bool,
     pub dry_run: bool,
+    pub fast_check: bool,
+    pub package: Option<String>,
+    pub diagnostic_format: DiagnosticFormat,
+    pub no_cache: bool,
+    pub explain_cache: bool,
+}
+
+/// Parses repeatable `--cfg key="value"` CLI arguments and sets the process-wide active cfg
+/// set consulted by `resolve_input_files`. Must be
+/// called at most once, before any stage command runs.
+pub fn init_active_cfgs(raw: &[String]) -> Result<()> {
+    let active = cfg_expr::parse_active_cfgs(raw)?;
+    cfg_expr::set_active_cfgs(active);
+    Ok(())
+}
+
+/// Resolves `.reen/config.toml` `[alias]` shortcuts (e.g. `gen = "create implementation"`)
+/// against raw `argv`, before `clap` ever sees it. Must run ahead of `Cli::parse_from`.
+pub fn expand_argv_aliases(args: Vec<String>) -> Result<Vec<String>> {
+    let config = aliases::load_alias_config()?;
+    aliases::expand_argv_alias(&config, args)
 }
 
 const DRAFTS_DIR: &str = "drafts";
@@ -36,6 +85,9 @@ const SPECIFICATIONS_DIR: &str = "specifications";
 pub async fn create_specification(
     names: Vec<String>,
     clear_cache: bool,
+    force: bool,
+    build_plan: bool,
+    context_depth: Option<usize>,
     config: &Config,
 ) -> Result<()> {
     let names_for_clear = names.clone();
@@ -54,6 +106,22 @@ pub async fn create_specification(
         clear_tracker_stage(&mut tracker, Stage::Specification, &names_for_clear, config)?;
     }
 
+    if build_plan {
+        return emit_specification_build_plan(execution_levels, &tracker);
+    }
+
+    if !config.dry_run {
+        let planned: Vec<(String, PathBuf)> = execution_levels
+            .iter()
+            .flatten()
+            .map(|node| {
+                determine_specification_output_path(&node.input_path, DRAFTS_DIR, SPECIFICATIONS_DIR)
+                    .map(|path| (node.name.clone(), path))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        enforce_overwrite_protection(&tracker, Stage::Specification, &planned, force)?;
+    }
+
     let total_count: usize = execution_levels.iter().map(|level| level.len()).sum();
     println!("Creating specifications for {} draft(s)", total_count);
 
@@ -98,10 +166,6 @@ pub async fn create_specification(
                 for node in nodes {
                     let draft_file = node.input_path.clone();
                     let draft_name = node.name.clone();
-                    let dependency_invalidated = node
-                        .direct_dependency_names()
-                        .iter()
-                        .any(|dep_name| updated_in_run.contains(dep_name));
                     let output_path = determine_specification_output_path(
                         &draft_file,
                         DRAFTS_DIR,
@@ -109,16 +173,16 @@ pub async fn create_specification(
                     )?;
                     progress.start_item(&draft_name);
 
-                    let needs_update = if dependency_invalidated {
-                        true
-                    } else {
-                        tracker.needs_update(
-                            Stage::Specification,
-                            &draft_name,
-                            &draft_file,
-                            &output_path,
-                        )?
-                    };
+                    let (needs_update, dependency_hash) = evaluate_cache(
+                        &tracker,
+                        Stage::Specification,
+                        &node,
+                        &draft_name,
+                        &draft_file,
+                        &output_path,
+                        &updated_in_run,
+                        config,
+                    )?;
                     if !needs_update {
                         if config.verbose {
                             println!("⊚ Skipping {} (up to date)", draft_name);
@@ -127,7 +191,7 @@ pub async fn create_specification(
                         continue;
                     }
 
-                    let dependency_context = match build_dependency_context(&node) {
+                    let dependency_context = match build_dependency_context(&node, context_depth) {
                         Ok(c) => c,
                         Err(e) => {
                             progress.complete_item(&draft_name, false);
@@ -136,7 +200,7 @@ pub async fn create_specification(
                         }
                     };
 
-                    let cfg = *config;
+                    let cfg = config.clone();
                     let executor_clone = executor.clone();
                     tasks.push(tokio::task::spawn(async move {
                         let result = process_specification(
@@ -147,19 +211,21 @@ pub async fn create_specification(
                             dependency_context,
                         )
                         .await;
-                        (draft_name, draft_file, output_path, result)
+                        (draft_name, draft_file, output_path, dependency_hash, result)
                     }));
                 }
 
                 for task in tasks {
-                    let (draft_name, draft_file, output_path, result) = task.await?;
+                    let (draft_name, draft_file, output_path, dependency_hash, result) =
+                        task.await?;
                     match result {
                         Ok(_) => {
-                            tracker.record(
+                            tracker.record_with_dependency_hash(
                                 Stage::Specification,
                                 &draft_name,
                                 &draft_file,
                                 &output_path,
+                                &dependency_hash,
                             )?;
                             updated_count += 1;
                             updated_in_run.insert(draft_name.clone());
@@ -178,10 +244,6 @@ pub async fn create_specification(
                 for node in nodes {
                     let draft_file = node.input_path.clone();
                     let draft_name = node.name.clone();
-                    let dependency_invalidated = node
-                        .direct_dependency_names()
-                        .iter()
-                        .any(|dep_name| updated_in_run.contains(dep_name));
                     let output_path = determine_specification_output_path(
                         &draft_file,
                         DRAFTS_DIR,
@@ -189,16 +251,16 @@ pub async fn create_specification(
                     )?;
 
                     progress.start_item(&draft_name);
-                    let needs_update = if dependency_invalidated {
-                        true
-                    } else {
-                        tracker.needs_update(
-                            Stage::Specification,
-                            &draft_name,
-                            &draft_file,
-                            &output_path,
-                        )?
-                    };
+                    let (needs_update, dependency_hash) = evaluate_cache(
+                        &tracker,
+                        Stage::Specification,
+                        &node,
+                        &draft_name,
+                        &draft_file,
+                        &output_path,
+                        &updated_in_run,
+                        config,
+                    )?;
                     if !needs_update {
                         if config.verbose {
                             println!("⊚ Skipping {} (up to date)", draft_name);
@@ -207,7 +269,7 @@ pub async fn create_specification(
                         continue;
                     }
 
-                    let dependency_context = build_dependency_context(&node)?;
+                    let dependency_context = build_dependency_context(&node, context_depth)?;
                     match process_specification(
                         &executor,
                         &draft_file,
@@ -218,11 +280,12 @@ pub async fn create_specification(
                     .await
                     {
                         Ok(_) => {
-                            tracker.record(
+                            tracker.record_with_dependency_hash(
                                 Stage::Specification,
                                 &draft_name,
                                 &draft_file,
                                 &output_path,
+                                &dependency_hash,
                             )?;
                             updated_count += 1;
                             updated_in_run.insert(draft_name.clone());
@@ -537,6 +600,12 @@ pub async fn create_implementation(
     names: Vec<String>,
     max_compile_fix_attempts: usize,
     clear_cache: bool,
+    force: bool,
+    build_plan: bool,
+    context_depth: Option<usize>,
+    fix_deps: bool,
+    workspace: bool,
+    verify: bool,
     config: &Config,
 ) -> Result<()> {
     let names_for_clear = names.clone();
@@ -558,12 +627,30 @@ pub async fn create_implementation(
         )?;
     }
 
+    let execution_levels = build_implementation_execution_plan(context_files)?;
+
+    if build_plan {
+        return emit_implementation_build_plan(execution_levels, &tracker);
+    }
+
+    if !config.dry_run {
+        let planned: Vec<(String, PathBuf)> = execution_levels
+            .iter()
+            .flatten()
+            .map(|node| {
+                let context_file = resolve_implementation_context_file(&node.input_path)?;
+                determine_implementation_output_path(&context_file, SPECIFICATIONS_DIR)
+                    .map(|path| (node.name.clone(), path))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        enforce_overwrite_protection(&tracker, Stage::Implementation, &planned, force)?;
+    }
+
     // Check if any specifications need to be regenerated first
     if tracker.upstream_changed(Stage::Implementation, "")? {
         println!("⚠ Upstream specifications have changed. Run 'reen create specification' first.");
     }
 
-    let execution_levels = build_implementation_execution_plan(context_files)?;
     let total_count: usize = execution_levels.iter().map(|level| level.len()).sum();
     println!("Creating implementation for {} context(s)", total_count);
 
@@ -574,21 +661,56 @@ pub async fn create_implementation(
 
     let spec_dir = PathBuf::from(SPECIFICATIONS_DIR);
     let drafts_dir = PathBuf::from(DRAFTS_DIR);
-    let project_info = analyze_specifications(&spec_dir, Some(&drafts_dir))
-        .context("Failed to analyze specifications")?;
+    let project_info = if workspace {
+        analyze_specifications_as_workspace(&spec_dir, Some(&drafts_dir))
+            .context("Failed to analyze specifications")?
+    } else {
+        analyze_specifications(&spec_dir, Some(&drafts_dir))
+            .context("Failed to analyze specifications")?
+    };
 
     let output_dir = PathBuf::from(".");
 
-    generate_cargo_toml(&project_info, &output_dir).context("Failed to generate Cargo.toml")?;
+    generate_cargo_toml(&project_info, &output_dir, verify).context("Failed to generate Cargo.toml")?;
 
-    generate_lib_rs(&project_info, &output_dir).context("Failed to generate lib.rs")?;
+    generate_lib_rs(&project_info, &output_dir, verify).context("Failed to generate lib.rs")?;
 
-    generate_mod_files(&project_info, &output_dir).context("Failed to generate mod.rs files")?;
+    generate_mod_files(&project_info, &output_dir, verify).context("Failed to generate mod.rs files")?;
 
     if config.verbose {
         println!("✓ Project structure generated");
     }
 
+    if verify {
+        match verify_generated_project(&project_info, &output_dir) {
+            Ok(diagnostics) if diagnostics.is_empty() => {
+                if config.verbose {
+                    println!("✓ Generated project verified clean");
+                }
+            }
+            Ok(diagnostics) => {
+                println!("⚠ {} issue(s) found verifying generated project:", diagnostics.len());
+                for d in &diagnostics {
+                    match &d.originating_spec {
+                        Some(spec) => println!("  {}:{} (from {}): {}", d.file, d.line, spec, d.message.lines().next().unwrap_or("")),
+                        None => println!("  {}:{}: {}", d.file, d.line, d.message.lines().next().unwrap_or("")),
+                    }
+                }
+            }
+            Err(e) => println!("⚠ Failed to verify generated project: {}", e),
+        }
+    }
+
+    let resolved_dependencies = match resolve_cargo_metadata(&output_dir) {
+        Ok(deps) => deps,
+        Err(e) => {
+            if config.verbose {
+                println!("⚠ Failed to resolve cargo metadata: {}", e);
+            }
+            Vec::new()
+        }
+    };
+
     let mut recent_generated_files: Vec<PathBuf> = Vec::new();
     for p in [
         PathBuf::from("Cargo.toml"),
@@ -622,10 +744,6 @@ pub async fn create_implementation(
         for node in level_nodes {
             let context_file = resolve_implementation_context_file(&node.input_path)?;
             let context_name = node.name.clone();
-            let dependency_invalidated = node
-                .direct_dependency_names()
-                .iter()
-                .any(|dep_name| updated_in_run.contains(dep_name));
             let output_path =
                 determine_implementation_output_path(&context_file, SPECIFICATIONS_DIR)?;
             progress.start_item(&context_name);
@@ -636,16 +754,16 @@ pub async fn create_implementation(
                 continue;
             }
 
-            let needs_update = if dependency_invalidated {
-                true
-            } else {
-                tracker.needs_update(
-                    Stage::Implementation,
-                    &context_name,
-                    &context_file,
-                    &output_path,
-                )?
-            };
+            let (needs_update, dependency_hash) = evaluate_cache(
+                &tracker,
+                Stage::Implementation,
+                &node,
+                &context_name,
+                &context_file,
+                &output_path,
+                &updated_in_run,
+                config,
+            )?;
 
             if !needs_update {
                 if config.verbose {
@@ -655,20 +773,34 @@ pub async fn create_implementation(
                 continue;
             }
 
-            let mut dependency_context = build_dependency_context(&node)?;
+            let mut dependency_context = build_dependency_context(&node, context_depth)?;
             if let Some(target_type_name) = infer_target_type_name(&context_file)? {
                 dependency_context.insert("target_type_name".to_string(), json!(target_type_name));
             }
-            runnable.push((context_file, context_name, output_path, dependency_context));
+            if !resolved_dependencies.is_empty() {
+                dependency_context.insert(
+                    "resolved_dependencies".to_string(),
+                    json!(resolved_dependencies),
+                );
+            }
+            runnable.push((
+                context_file,
+                context_name,
+                output_path,
+                dependency_hash,
+                dependency_context,
+            ));
         }
 
         if can_parallel {
             if config.verbose {
                 println!("Parallel execution enabled for create_implementation");
             }
-            let cfg = *config;
+            let cfg = config.clone();
             let mut tasks = Vec::new();
-            for (context_file, context_name, output_path, dependency_context) in runnable {
+            for (context_file, context_name, output_path, dependency_hash, dependency_context) in
+                runnable
+            {
                 let executor_clone = executor.clone();
                 tasks.push(tokio::task::spawn(async move {
                     let result = process_implementation(
@@ -679,18 +811,20 @@ pub async fn create_implementation(
                         dependency_context,
                     )
                     .await;
-                    (context_name, context_file, output_path, result)
+                    (context_name, context_file, output_path, dependency_hash, result)
                 }));
             }
             for task in tasks {
-                let (context_name, context_file, output_path, result) = task.await?;
+                let (context_name, context_file, output_path, dependency_hash, result) =
+                    task.await?;
                 match result {
                     Ok(_) => {
-                        tracker.record(
+                        tracker.record_with_dependency_hash(
                             Stage::Implementation,
                             &context_name,
                             &context_file,
                             &output_path,
+                            &dependency_hash,
                         )?;
                         updated_count += 1;
                         updated_in_run.insert(context_name.clone());
@@ -713,7 +847,9 @@ pub async fn create_implementation(
                 }
             }
         } else {
-            for (context_file, context_name, output_path, dependency_context) in runnable {
+            for (context_file, context_name, output_path, dependency_hash, dependency_context) in
+                runnable
+            {
                 if config.verbose {
                     println!("Processing context: {}", context_name);
                 }
@@ -727,11 +863,12 @@ pub async fn create_implementation(
                 .await
                 {
                     Ok(_) => {
-                        tracker.record(
+                        tracker.record_with_dependency_hash(
                             Stage::Implementation,
                             &context_name,
                             &context_file,
                             &output_path,
+                            &dependency_hash,
                         )?;
                         updated_count += 1;
                         updated_in_run.insert(context_name.clone());
@@ -757,7 +894,7 @@ pub async fn create_implementation(
     }
 
     if !config.dry_run {
-        validate_generated_rust_layout(Path::new("."))?;
+        validate_generated_rust_layout(Path::new("."), fix_deps, config.diagnostic_format)?;
     }
 
     // Automatic compile + bounded auto-fix loop to restore build validity.
@@ -770,6 +907,16 @@ pub async fn create_implementation(
     )
     .await?;
 
+    // Idiom pass: clippy + rustfmt feedback loop now that the build is valid.
+    compilation_fix::polish_implementation(
+        config,
+        max_compile_fix_attempts,
+        Path::new("."),
+        &project_info,
+        &recent_generated_files,
+    )
+    .await?;
+
     // Save tracker
     tracker.save()?;
 
@@ -1210,7 +1357,12 @@ fn has_unfinished_specification(path: &Path, context_name: &str, stage_name: &st
     Ok(false)
 }
 
-pub async fn create_tests(names: Vec<String>, clear_cache: bool, config: &Config) -> Result<()> {
+pub async fn create_tests(
+    names: Vec<String>,
+    max_test_fix_attempts: usize,
+    clear_cache: bool,
+    config: &Config,
+) -> Result<()> {
     let names_for_clear = names.clone();
     let context_files = resolve_input_files(SPECIFICATIONS_DIR, names, "md")?;
 
@@ -1219,15 +1371,9 @@ pub async fn create_tests(names: Vec<String>, clear_cache: bool, config: &Config
         return Ok(());
     }
 
-    // Clear build-tracker entries for tests stage if requested.
-    // Note: test generation does not currently use build-tracker caching,
-    // but we support the flag for consistency.
+    let mut tracker = BuildTracker::load()?;
     if clear_cache {
-        let mut tracker = BuildTracker::load()?;
         clear_tracker_stage(&mut tracker, Stage::Tests, &names_for_clear, config)?;
-        if !config.dry_run {
-            tracker.save()?;
-        }
     }
 
     let execution_levels =
@@ -1235,11 +1381,18 @@ pub async fn create_tests(names: Vec<String>, clear_cache: bool, config: &Config
     let total_count: usize = execution_levels.iter().map(|level| level.len()).sum();
     println!("Creating tests for {} context(s)", total_count);
 
+    let spec_dir = PathBuf::from(SPECIFICATIONS_DIR);
+    let drafts_dir = PathBuf::from(DRAFTS_DIR);
+    let project_info = analyze_specifications(&spec_dir, Some(&drafts_dir))
+        .context("Failed to analyze specifications")?;
+
     let executor = Arc::new(AgentExecutor::new("create_test", config)?);
     let can_parallel = executor.can_run_parallel().unwrap_or(false);
 
     let mut progress = ProgressIndicator::new(total_count);
     let mut had_unspecified = false;
+    let mut recent_generated_files: Vec<PathBuf> = Vec::new();
+    let mut updated_in_run: HashSet<String> = HashSet::new();
     for (level_idx, level_nodes) in execution_levels.into_iter().enumerate() {
         if config.verbose {
             println!(
@@ -1253,35 +1406,74 @@ pub async fn create_tests(names: Vec<String>, clear_cache: bool, config: &Config
         for node in level_nodes {
             let context_file = node.input_path.clone();
             let context_name = node.name.clone();
-            let dependency_context = build_dependency_context(&node)?;
+            let output_path = determine_tests_output_path(&context_file, SPECIFICATIONS_DIR)?;
             progress.start_item(&context_name);
-            runnable.push((context_file, context_name, dependency_context));
+
+            let (needs_update, dependency_hash) = evaluate_cache(
+                &tracker,
+                Stage::Tests,
+                &node,
+                &context_name,
+                &context_file,
+                &output_path,
+                &updated_in_run,
+                config,
+            )?;
+            if !needs_update {
+                if config.verbose {
+                    println!("⊚ Skipping {} (up to date)", context_name);
+                }
+                progress.complete_item(&context_name, true);
+                continue;
+            }
+
+            let dependency_context = build_dependency_context(&node, None)?;
+            runnable.push((
+                context_file,
+                context_name,
+                output_path,
+                dependency_hash,
+                dependency_context,
+            ));
         }
 
         if can_parallel {
             if config.verbose {
                 println!("Parallel execution enabled for create_test");
             }
-            let cfg = *config;
+            let cfg = config.clone();
             let mut tasks = Vec::new();
-            for (context_file, context_name, dependency_context) in runnable {
+            for (context_file, context_name, output_path, dependency_hash, dependency_context) in
+                runnable
+            {
                 let executor_clone = executor.clone();
                 tasks.push(tokio::task::spawn(async move {
                     let result = process_tests(
                         &executor_clone,
                         &context_file,
                         &context_name,
+                        &output_path,
                         &cfg,
                         dependency_context,
                     )
                     .await;
-                    (context_name, result)
+                    (context_name, context_file, output_path, dependency_hash, result)
                 }));
             }
             for task in tasks {
-                let (context_name, result) = task.await?;
+                let (context_name, context_file, output_path, dependency_hash, result) =
+                    task.await?;
                 match result {
                     Ok(_) => {
+                        tracker.record_with_dependency_hash(
+                            Stage::Tests,
+                            &context_name,
+                            &context_file,
+                            &output_path,
+                            &dependency_hash,
+                        )?;
+                        updated_in_run.insert(context_name.clone());
+                        recent_generated_files.push(output_path.clone());
                         progress.complete_item(&context_name, true);
                         if config.verbose {
                             println!("✓ Successfully created tests for {}", context_name);
@@ -1297,7 +1489,9 @@ pub async fn create_tests(names: Vec<String>, clear_cache: bool, config: &Config
                 }
             }
         } else {
-            for (context_file, context_name, dependency_context) in runnable {
+            for (context_file, context_name, output_path, dependency_hash, dependency_context) in
+                runnable
+            {
                 if config.verbose {
                     println!("Processing context: {}", context_name);
                 }
@@ -1305,12 +1499,22 @@ pub async fn create_tests(names: Vec<String>, clear_cache: bool, config: &Config
                     &executor,
                     &context_file,
                     &context_name,
+                    &output_path,
                     config,
                     dependency_context,
                 )
                 .await
                 {
                     Ok(_) => {
+                        tracker.record_with_dependency_hash(
+                            Stage::Tests,
+                            &context_name,
+                            &context_file,
+                            &output_path,
+                            &dependency_hash,
+                        )?;
+                        updated_in_run.insert(context_name.clone());
+                        recent_generated_files.push(output_path.clone());
                         progress.complete_item(&context_name, true);
                         if config.verbose {
                             println!("✓ Successfully created tests for {}", context_name);
@@ -1328,12 +1532,23 @@ pub async fn create_tests(names: Vec<String>, clear_cache: bool, config: &Config
         }
     }
 
+    tracker.save()?;
     progress.finish();
+
     if had_unspecified {
         anyhow::bail!("Unfinished specifications were detected. Aborting.");
-    } else {
-        Ok(())
     }
+
+    compilation_fix::verify_and_fix_tests(
+        config,
+        max_test_fix_attempts,
+        Path::new("."),
+        &project_info,
+        &recent_generated_files,
+    )
+    .await?;
+
+    Ok(())
 }
 
 fn clear_tracker_stage(
@@ -1380,6 +1595,38 @@ fn clear_tracker_stage(
     Ok(())
 }
 
+/// Aborts a `create` run before any generation happens if `planned` names an output file that
+/// already exists but isn't one `tracker` recognizes as an artifact it generated itself — a
+/// hand-written file, or one left over from a tool/run this tracker never saw. Files the
+/// tracker already tracks are left to the normal cache-freshness check (skipped if unchanged,
+/// regenerated if stale) rather than blocked here, so ordinary re-runs aren't affected.
+fn enforce_overwrite_protection(
+    tracker: &BuildTracker,
+    stage: Stage,
+    planned: &[(String, PathBuf)],
+    force: bool,
+) -> Result<()> {
+    if force {
+        return Ok(());
+    }
+
+    let mut unexpected: Vec<String> = planned
+        .iter()
+        .filter(|(name, path)| path.exists() && !tracker.is_tracked(stage, name))
+        .map(|(_, path)| format!("  {}", path.display()))
+        .collect();
+    if unexpected.is_empty() {
+        return Ok(());
+    }
+    unexpected.sort();
+
+    anyhow::bail!(
+        "refusing to overwrite {} existing file(s) not previously generated by reen (pass --force to overwrite anyway):\n{}",
+        unexpected.len(),
+        unexpected.join("\n")
+    );
+}
+
 #[derive(Serialize)]
 struct CacheAgentInput {
     draft_content: Option<String>,
@@ -1438,13 +1685,13 @@ fn clear_stage_agent_cache_dirs(stage: Stage, config: &Config) -> Result<usize>
                 continue;
             }
         };
-        let model = match model_registry.get_model(agent_name) {
-            Ok(m) => m,
-            Err(e) => {
+        let model = match model_registry.get_model(agent_name).map(|m| m.into_iter().next()) {
+            Ok(Some(m)) => m,
+            Ok(None) | Err(_) => {
                 if config.verbose {
                     eprintln!(
-                        "Skipping agent cache clear for '{}': failed to resolve model ({})",
-                        agent_name, e
+                        "Skipping agent cache clear for '{}': failed to resolve model",
+                        agent_name
                     );
                 }
                 continue;
@@ -1472,19 +1719,55 @@ fn clear_stage_agent_cache_entries_by_name(
     names: &[String],
     config: &Config,
 ) -> Result<usize> {
-    let names_vec = names.to_vec();
+    let candidates = stage_cache_candidates(stage, names.to_vec())?;
+
+    if config.dry_run {
+        println!(
+            "[DRY RUN] Would clear {} agent response cache entrie(s) for {:?}: {}",
+            candidates.len(),
+            stage,
+            names.join(", ")
+        );
+        return Ok(0);
+    }
+
+    let agent_registry = FileAgentRegistry::new(None);
+    let model_registry = FileAgentModelRegistry::new(None, None, None);
     let mut removed = 0usize;
+    for (agent_name, input) in candidates {
+        if clear_single_agent_cache_entry(
+            &agent_registry,
+            &model_registry,
+            &agent_name,
+            &input,
+            config,
+        )? {
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Resolves the `(agent_name, input)` pairs that currently exist for `stage`, matching
+/// `names` (or every file in the stage's input directory when empty). Shared by targeted
+/// cache clears and `cache gc`'s reachability scan, so both agree on what "currently exists"
+/// means without re-deriving the dependency walk twice.
+fn stage_cache_candidates(
+    stage: Stage,
+    names: Vec<String>,
+) -> Result<Vec<(String, CacheAgentInput)>> {
     let mut candidates: Vec<(String, CacheAgentInput)> = Vec::new();
 
     match stage {
         Stage::Specification => {
-            let files = resolve_input_files(DRAFTS_DIR, names_vec, "md")?;
+            let files = resolve_input_files(DRAFTS_DIR, names, "md")?;
             let levels = build_execution_plan(files, DRAFTS_DIR, None)?;
             for node in levels.into_iter().flatten() {
                 let draft_content = fs::read_to_string(&node.input_path).with_context(|| {
                     format!("Failed to read draft file: {}", node.input_path.display())
                 })?;
-                let additional = build_dependency_context(&node)?;
+                let additional = build_dependency_context(&node, None)?;
                 let agent_name =
                     determine_specification_agent(&node.input_path, DRAFTS_DIR).to_string();
                 candidates.push((
@@ -1498,7 +1781,7 @@ fn clear_stage_agent_cache_entries_by_name(
             }
         }
         Stage::Implementation => {
-            let files = resolve_input_files(SPECIFICATIONS_DIR, names_vec, "md")?;
+            let files = resolve_input_files(SPECIFICATIONS_DIR, names, "md")?;
             let levels = build_implementation_execution_plan(files)?;
             for node in levels.into_iter().flatten() {
                 let context_file = resolve_implementation_context_file(&node.input_path)?;
@@ -1508,7 +1791,7 @@ fn clear_stage_agent_cache_entries_by_name(
                         context_file.display()
                     )
                 })?;
-                let mut additional = build_dependency_context(&node)?;
+                let mut additional = build_dependency_context(&node, None)?;
                 if let Some(target_type_name) = infer_target_type_name(&context_file)? {
                     additional.insert("target_type_name".to_string(), json!(target_type_name));
                 }
@@ -1523,7 +1806,7 @@ fn clear_stage_agent_cache_entries_by_name(
             }
         }
         Stage::Tests => {
-            let files = resolve_input_files(SPECIFICATIONS_DIR, names_vec, "md")?;
+            let files = resolve_input_files(SPECIFICATIONS_DIR, names, "md")?;
             let levels = build_execution_plan(files, SPECIFICATIONS_DIR, Some(DRAFTS_DIR))?;
             for node in levels.into_iter().flatten() {
                 let context_content = fs::read_to_string(&node.input_path).with_context(|| {
@@ -1532,7 +1815,7 @@ fn clear_stage_agent_cache_entries_by_name(
                         node.input_path.display()
                     )
                 })?;
-                let additional = build_dependency_context(&node)?;
+                let additional = build_dependency_context(&node, None)?;
                 candidates.push((
                     "create_test".to_string(),
                     CacheAgentInput {
@@ -1546,31 +1829,7 @@ fn clear_stage_agent_cache_entries_by_name(
         Stage::Compile => {}
     }
 
-    if config.dry_run {
-        println!(
-            "[DRY RUN] Would clear {} agent response cache entrie(s) for {:?}: {}",
-            candidates.len(),
-            stage,
-            names.join(", ")
-        );
-        return Ok(0);
-    }
-
-    let agent_registry = FileAgentRegistry::new(None);
-    let model_registry = FileAgentModelRegistry::new(None, None, None);
-    for (agent_name, input) in candidates {
-        if clear_single_agent_cache_entry(
-            &agent_registry,
-            &model_registry,
-            &agent_name,
-            &input,
-            config,
-        )? {
-            removed += 1;
-        }
-    }
-
-    Ok(removed)
+    Ok(candidates)
 }
 
 fn clear_single_agent_cache_entry(
@@ -1593,13 +1852,13 @@ fn clear_single_agent_cache_entry(
         }
     };
 
-    let model = match model_registry.get_model(agent_name) {
-        Ok(m) => m,
-        Err(e) => {
+    let model = match model_registry.get_model(agent_name).map(|m| m.into_iter().next()) {
+        Ok(Some(m)) => m,
+        Ok(None) | Err(_) => {
             if config.verbose {
                 eprintln!(
-                    "Skipping targeted agent cache clear for '{}': failed to resolve model ({})",
-                    agent_name, e
+                    "Skipping targeted agent cache clear for '{}': failed to resolve model",
+                    agent_name
                 );
             }
             return Ok(false);
@@ -1621,6 +1880,9 @@ fn clear_single_agent_cache_entry(
                 cache_path.display()
             )
         })?;
+        if let Some(cache_dir) = cache_path.parent() {
+            reen::contexts::remove_manifest_entry(cache_dir, &cache_key);
+        }
         return Ok(true);
     }
     Ok(false)
@@ -1637,6 +1899,7 @@ async fn process_tests(
     executor: &AgentExecutor,
     context_file: &Path,
     context_name: &str,
+    output_path: &Path,
     config: &Config,
     additional_context: HashMap<String, serde_json::Value>,
 ) -> Result<()> {
@@ -1657,65 +1920,520 @@ async fn process_tests(
         .execute_with_conversation_with_seed(&context_content, context_name, additional_context)
         .await?;
 
+    let code = extract_code_from_output(&test_result, context_name);
+
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent).context("Failed to create tests output directory")?;
+    }
+    fs::write(output_path, code).context("Failed to write tests file")?;
+
     if config.verbose {
-        println!("Test creation result: {}", test_result);
+        println!("✓ Written tests to: {}", output_path.display());
     }
 
     Ok(())
 }
 
-fn build_dependency_context(node: &ExecutionNode) -> Result<HashMap<String, serde_json::Value>> {
-    let mut context = HashMap::new();
-    let direct_dependencies = node.resolve_direct_dependencies()?;
-    let (primary_root, fallback_root) = if node.input_path.starts_with(SPECIFICATIONS_DIR) {
-        (SPECIFICATIONS_DIR, Some(DRAFTS_DIR))
-    } else {
-        (DRAFTS_DIR, None)
-    };
-    let dependency_closure = node.resolve_dependency_closure(primary_root, fallback_root)?;
+/// Serializes a specification execution plan to JSON on stdout without running any agent,
+/// mirroring how `cargo build --build-plan` exposes the compile graph for external tooling.
+fn emit_specification_build_plan(
+    execution_levels: Vec<Vec<ExecutionNode>>,
+    tracker: &BuildTracker,
+) -> Result<()> {
+    let mut levels_json = Vec::new();
 
-    // Expose full closure via direct_dependencies so existing agent prompts
-    // receive transitive context without prompt/template changes.
-    let value = json!(dependency_closure);
-    context.insert("direct_dependencies".to_string(), value.clone());
-    context.insert(
-        "direct_dependencies_only".to_string(),
-        json!(direct_dependencies),
-    );
-    context.insert("dependency_closure".to_string(), value.clone());
-    // Backward compatibility with agent prompts that still reference mcp_context
-    context.insert("mcp_context".to_string(), value);
+    for level in execution_levels {
+        let mut level_json = Vec::new();
+        for node in level {
+            let agent = determine_specification_agent(&node.input_path, DRAFTS_DIR);
+            let output_path =
+                determine_specification_output_path(&node.input_path, DRAFTS_DIR, SPECIFICATIONS_DIR)?;
+            let needs_update = tracker.needs_update(
+                Stage::Specification,
+                &node.name,
+                &node.input_path,
+                &output_path,
+            )?;
+
+            level_json.push(json!({
+                "name": node.name,
+                "input_path": node.input_path.display().to_string(),
+                "output_path": output_path.display().to_string(),
+                "agent": agent,
+                "direct_dependencies": node.direct_dependency_names(),
+                "needs_update": needs_update,
+            }));
+        }
+        levels_json.push(level_json);
+    }
 
-    let implemented_dependencies = build_implemented_dependency_context(&dependency_closure)?;
-    context.insert(
-        "implemented_dependencies".to_string(),
-        json!(implemented_dependencies),
-    );
-    Ok(context)
+    println!("{}", serde_json::to_string_pretty(&levels_json)?);
+    Ok(())
 }
 
-fn build_implementation_execution_plan(
-    spec_files: Vec<PathBuf>,
-) -> Result<Vec<Vec<ExecutionNode>>> {
-    let mut draft_inputs = Vec::new();
-    for spec_file in spec_files {
-        let draft_path = determine_draft_input_path(&spec_file, SPECIFICATIONS_DIR, DRAFTS_DIR)?;
-        if draft_path.exists() {
-            draft_inputs.push(draft_path);
-        } else {
-            draft_inputs.push(spec_file);
+/// Monitors `drafts/` and `specifications/` and, on change, re-derives the affected subgraph
+/// and re-runs specification/implementation generation only for the changed node and its
+/// transitive downstream dependents. Turns reen into an interactive authoring loop instead of
+/// a one-shot batch command.
+pub async fn watch(config: &Config) -> Result<()> {
+    use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    println!("Watching {}/ and {}/ for changes (Ctrl+C to stop)...", DRAFTS_DIR, SPECIFICATIONS_DIR);
+
+    let (tx, rx) = channel::<notify::Result<notify::Event>>();
+    let mut watcher: RecommendedWatcher =
+        notify::recommended_watcher(tx).context("Failed to create file watcher")?;
+
+    for dir in [DRAFTS_DIR, SPECIFICATIONS_DIR] {
+        let path = PathBuf::from(dir);
+        if path.exists() {
+            watcher
+                .watch(&path, RecursiveMode::Recursive)
+                .with_context(|| format!("Failed to watch directory: {}", dir))?;
         }
     }
 
-    build_execution_plan(draft_inputs, DRAFTS_DIR, None)
-}
+    // Cached across rebuilds so repeated edits don't pay executor setup cost each pass.
+    let mut spec_executors: HashMap<String, Arc<AgentExecutor>> = HashMap::new();
+    let mut impl_executors: HashMap<String, Arc<AgentExecutor>> = HashMap::new();
+    // Cached across rebuilds so project-structure regeneration only touches folders whose
+    // modules or type names actually changed, instead of rescanning and rewriting everything.
+    let mut project_info_cache: Option<ProjectInfo> = None;
 
-fn resolve_implementation_context_file(node_input_path: &Path) -> Result<PathBuf> {
-    if node_input_path.starts_with(DRAFTS_DIR) {
-        determine_specification_output_path(node_input_path, DRAFTS_DIR, SPECIFICATIONS_DIR)
-    } else {
-        Ok(node_input_path.to_path_buf())
-    }
+    loop {
+        let Ok(first_event) = rx.recv() else {
+            break; // Watcher channel closed
+        };
+
+        let mut changed_paths: HashSet<PathBuf> = HashSet::new();
+        collect_changed_paths(&first_event, &mut changed_paths);
+
+        // Debounce rapid successive writes: collect further events over a short window
+        // before rebuilding, so e.g. an editor's save-then-rename pair triggers one rebuild.
+        let debounce_window = Duration::from_millis(300);
+        while let Ok(event) = rx.recv_timeout(debounce_window) {
+            collect_changed_paths(&event, &mut changed_paths);
+        }
+
+        if changed_paths.is_empty() {
+            continue;
+        }
+
+        println!(
+            "⟳ Detected {} change(s), rebuilding affected node(s)...",
+            changed_paths.len()
+        );
+
+        if let Err(e) = run_watch_pass(
+            &changed_paths,
+            &mut spec_executors,
+            &mut impl_executors,
+            &mut project_info_cache,
+            config,
+        )
+        .await
+        {
+            eprintln!("✗ Watch rebuild failed: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+fn collect_changed_paths(event: &notify::Result<notify::Event>, changed: &mut HashSet<PathBuf>) {
+    if let Ok(event) = event {
+        for path in &event.paths {
+            changed.insert(path.clone());
+        }
+    }
+}
+
+/// Returns the names of `all_nodes` that are reachable from `changed_names` by following
+/// dependency edges forward, i.e. the changed nodes plus everything that transitively depends
+/// on them.
+fn transitive_dependents(
+    all_nodes: &[ExecutionNode],
+    changed_names: &HashSet<String>,
+) -> HashSet<String> {
+    let mut affected = changed_names.clone();
+
+    let mut grew = true;
+    while grew {
+        grew = false;
+        for node in all_nodes {
+            if affected.contains(&node.name) {
+                continue;
+            }
+            if node
+                .direct_dependency_names()
+                .iter()
+                .any(|dep| affected.contains(dep))
+            {
+                affected.insert(node.name.clone());
+                grew = true;
+            }
+        }
+    }
+
+    affected
+}
+
+async fn run_watch_pass(
+    changed_paths: &HashSet<PathBuf>,
+    spec_executors: &mut HashMap<String, Arc<AgentExecutor>>,
+    impl_executors: &mut HashMap<String, Arc<AgentExecutor>>,
+    project_info_cache: &mut Option<ProjectInfo>,
+    config: &Config,
+) -> Result<()> {
+    let changed_names: HashSet<String> = changed_paths
+        .iter()
+        .filter_map(|p| p.file_stem().and_then(|s| s.to_str()).map(|s| s.to_string()))
+        .collect();
+
+    if changed_names.is_empty() {
+        return Ok(());
+    }
+
+    let mut tracker = BuildTracker::load()?;
+
+    let draft_files = resolve_input_files(DRAFTS_DIR, Vec::new(), "md").unwrap_or_default();
+    if !draft_files.is_empty() {
+        let all_nodes: Vec<ExecutionNode> = build_execution_plan(draft_files, DRAFTS_DIR, None)?
+            .into_iter()
+            .flatten()
+            .collect();
+        let affected = transitive_dependents(&all_nodes, &changed_names);
+
+        for node in &all_nodes {
+            if !affected.contains(&node.name) {
+                continue;
+            }
+
+            let draft_file = node.input_path.clone();
+            let output_path = determine_specification_output_path(
+                &draft_file,
+                DRAFTS_DIR,
+                SPECIFICATIONS_DIR,
+            )?;
+            let agent_name = determine_specification_agent(&draft_file, DRAFTS_DIR).to_string();
+            let executor = get_or_create_executor(spec_executors, &agent_name, config)?;
+            let dependency_context = build_dependency_context(node, None)?;
+
+            match process_specification(&executor, &draft_file, &node.name, config, dependency_context).await {
+                Ok(_) => tracker.record(Stage::Specification, &node.name, &draft_file, &output_path)?,
+                Err(e) => eprintln!("✗ Failed to create specification for {}: {}", node.name, e),
+            }
+        }
+    }
+
+    let context_files = resolve_input_files(SPECIFICATIONS_DIR, Vec::new(), "md").unwrap_or_default();
+    if !context_files.is_empty() {
+        let all_nodes: Vec<ExecutionNode> =
+            build_execution_plan(context_files, SPECIFICATIONS_DIR, Some(DRAFTS_DIR))?
+                .into_iter()
+                .flatten()
+                .collect();
+        let affected = transitive_dependents(&all_nodes, &changed_names);
+        let executor = get_or_create_executor(impl_executors, "create_implementation", config)?;
+
+        for node in &all_nodes {
+            if !affected.contains(&node.name) {
+                continue;
+            }
+
+            let context_file = resolve_implementation_context_file(&node.input_path)?;
+            let output_path = determine_implementation_output_path(&context_file, SPECIFICATIONS_DIR)?;
+            let dependency_context = build_dependency_context(node, None)?;
+
+            match process_implementation(&executor, &context_file, &node.name, config, dependency_context).await {
+                Ok(_) => tracker.record(Stage::Implementation, &node.name, &context_file, &output_path)?,
+                Err(e) => eprintln!("✗ Failed to create implementation for {}: {}", node.name, e),
+            }
+        }
+
+        let spec_dir = PathBuf::from(SPECIFICATIONS_DIR);
+        let drafts_dir = PathBuf::from(DRAFTS_DIR);
+        let previous = project_info_cache.take().unwrap_or_default();
+        match analyze_specifications_incremental(&spec_dir, Some(&drafts_dir), previous) {
+            Ok((updated, changed_folders)) => {
+                if !changed_folders.is_empty() {
+                    let output_dir = PathBuf::from(".");
+                    if let Err(e) = generate_cargo_toml(&updated, &output_dir, false) {
+                        eprintln!("✗ Failed to regenerate Cargo.toml: {}", e);
+                    }
+                    if let Err(e) = generate_lib_rs(&updated, &output_dir, false) {
+                        eprintln!("✗ Failed to regenerate lib.rs: {}", e);
+                    }
+                    if let Err(e) =
+                        generate_mod_files_for_folders(&updated, &output_dir, &changed_folders)
+                    {
+                        eprintln!("✗ Failed to regenerate mod.rs files: {}", e);
+                    }
+                }
+                *project_info_cache = Some(updated);
+            }
+            Err(e) => eprintln!("✗ Failed to analyze specifications: {}", e),
+        }
+    }
+
+    tracker.save()?;
+    Ok(())
+}
+
+/// Gets or lazily creates the `AgentExecutor` for `agent_name`, reusing it across watch
+/// rebuilds instead of paying executor setup cost on every pass.
+fn get_or_create_executor(
+    executors: &mut HashMap<String, Arc<AgentExecutor>>,
+    agent_name: &str,
+    config: &Config,
+) -> Result<Arc<AgentExecutor>> {
+    if !executors.contains_key(agent_name) {
+        executors.insert(
+            agent_name.to_string(),
+            Arc::new(AgentExecutor::new(agent_name, config)?),
+        );
+    }
+    executors
+        .get(agent_name)
+        .cloned()
+        .context("missing executor")
+}
+
+/// Treats any member of `node`'s full transitive dependency closure — not just its direct
+/// dependencies — that was touched `updated_in_run` as an invalidation trigger, so a change
+/// deep in a dependency chain still forces regeneration of everything downstream of it.
+fn is_dependency_invalidated(node: &ExecutionNode, updated_in_run: &HashSet<String>) -> bool {
+    if node
+        .direct_dependency_names()
+        .iter()
+        .any(|dep_name| updated_in_run.contains(dep_name))
+    {
+        return true;
+    }
+
+    let (primary_root, fallback_root) = dependency_roots_for(node);
+
+    match node.resolve_dependency_closure(primary_root, fallback_root) {
+        Ok(closure) => closure.iter().any(|dep| updated_in_run.contains(&dep.name)),
+        Err(_) => false,
+    }
+}
+
+/// Picks the dependency resolution roots for `node` based on which stage's input it is:
+/// a specification node's dependencies may live in either `specifications/` or (for ones not
+/// yet promoted) `drafts/`, while a draft node's dependencies only live in `drafts/`.
+fn dependency_roots_for(node: &ExecutionNode) -> (&'static str, Option<&'static str>) {
+    if node.input_path.starts_with(SPECIFICATIONS_DIR) {
+        (SPECIFICATIONS_DIR, Some(DRAFTS_DIR))
+    } else {
+        (DRAFTS_DIR, None)
+    }
+}
+
+/// Combines `node`'s resolved dependency closure into a single hash, so the build tracker can
+/// detect a dependency that changed content since this file was last built even across
+/// separate invocations (`is_dependency_invalidated` only catches dominoes within one run).
+/// Reuses the sha256 each `DependencyArtifact` already carries rather than re-hashing files.
+fn compute_dependency_hash(
+    node: &ExecutionNode,
+    primary_root: &str,
+    fallback_root: Option<&str>,
+) -> Result<String> {
+    let mut hashes: Vec<(String, String)> = node
+        .resolve_dependency_closure(primary_root, fallback_root)?
+        .into_iter()
+        .map(|dep| (dep.name, dep.sha256))
+        .collect();
+    hashes.sort_unstable();
+
+    let mut hasher = Sha256::new();
+    for (name, sha256) in hashes {
+        hasher.update(name.as_bytes());
+        hasher.update(b":");
+        hasher.update(sha256.as_bytes());
+        hasher.update(b"\n");
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Decides whether `name` needs regeneration and returns the dependency hash to persist
+/// alongside the decision (via `BuildTracker::record_with_dependency_hash`) so callers don't
+/// need to recompute it after a successful build. Combines three invalidation sources: an
+/// explicit `--no-cache` override, a dependency regenerated earlier in this same run
+/// (`is_dependency_invalidated`), and the build tracker's own persisted input/output/
+/// dependency hash comparison (which catches a dependency that changed in an earlier, separate
+/// invocation). When `config.explain_cache` is set, prints the reason for any rebuild.
+#[allow(clippy::too_many_arguments)]
+fn evaluate_cache(
+    tracker: &BuildTracker,
+    stage: Stage,
+    node: &ExecutionNode,
+    name: &str,
+    input_path: &Path,
+    output_path: &Path,
+    updated_in_run: &HashSet<String>,
+    config: &Config,
+) -> Result<(bool, String)> {
+    let (primary_root, fallback_root) = dependency_roots_for(node);
+    let dependency_hash = compute_dependency_hash(node, primary_root, fallback_root)?;
+
+    if config.no_cache {
+        if config.explain_cache {
+            println!("↻ Rebuilding {} (--no-cache forces a full rebuild)", name);
+        }
+        return Ok((true, dependency_hash));
+    }
+
+    if is_dependency_invalidated(node, updated_in_run) {
+        if config.explain_cache {
+            println!(
+                "↻ Rebuilding {} (a dependency was regenerated earlier in this run)",
+                name
+            );
+        }
+        return Ok((true, dependency_hash));
+    }
+
+    let (needs_update, reason) = tracker.needs_update_with_dependency_hash(
+        stage,
+        name,
+        input_path,
+        output_path,
+        &dependency_hash,
+    )?;
+    if needs_update && config.explain_cache {
+        println!("↻ Rebuilding {} ({})", name, reason);
+    }
+    Ok((needs_update, dependency_hash))
+}
+
+/// Builds the dependency context map for `node`, exposing both its direct dependencies
+/// and the transitive closure up to `context_depth` hops away (`None` = unbounded,
+/// `Some(1)` = direct-only). Bounding the closure keeps large dependency graphs from
+/// blowing the agent's token budget.
+fn build_dependency_context(
+    node: &ExecutionNode,
+    context_depth: Option<usize>,
+) -> Result<HashMap<String, serde_json::Value>> {
+    let mut context = HashMap::new();
+    let direct_dependencies = node.resolve_direct_dependencies()?;
+    let (primary_root, fallback_root) = if node.input_path.starts_with(SPECIFICATIONS_DIR) {
+        (SPECIFICATIONS_DIR, Some(DRAFTS_DIR))
+    } else {
+        (DRAFTS_DIR, None)
+    };
+    let dependency_closure =
+        node.resolve_dependency_closure_with_depth(primary_root, fallback_root, context_depth)?;
+
+    // Expose full closure via direct_dependencies so existing agent prompts
+    // receive transitive context without prompt/template changes.
+    let value = json!(dependency_closure);
+    context.insert("direct_dependencies".to_string(), value.clone());
+    context.insert(
+        "direct_dependencies_only".to_string(),
+        json!(direct_dependencies),
+    );
+    context.insert("dependency_closure".to_string(), value.clone());
+    // Backward compatibility with agent prompts that still reference mcp_context
+    context.insert("mcp_context".to_string(), value);
+
+    let implemented_dependencies = build_implemented_dependency_context(&dependency_closure)?;
+    context.insert(
+        "implemented_dependencies".to_string(),
+        json!(implemented_dependencies),
+    );
+
+    let active_cfgs = detect_active_cfgs();
+    if !active_cfgs.is_empty() {
+        context.insert("active_cfgs".to_string(), json!(active_cfgs));
+    }
+
+    Ok(context)
+}
+
+/// Captures the active `cfg` set via `rustc --print cfg`, so agents generating code/tests
+/// know which `#[cfg(...)]` gates are actually active instead of guessing from target
+/// triples. Cached for the process lifetime since the active cfg set doesn't change
+/// mid-run.
+fn detect_active_cfgs() -> &'static [String] {
+    static CFGS: OnceLock<Vec<String>> = OnceLock::new();
+    CFGS.get_or_init(|| {
+        Command::new("rustc")
+            .arg("--print")
+            .arg("cfg")
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| {
+                String::from_utf8_lossy(&o.stdout)
+                    .lines()
+                    .map(|l| l.trim().to_string())
+                    .filter(|l| !l.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    })
+}
+
+fn build_implementation_execution_plan(
+    spec_files: Vec<PathBuf>,
+) -> Result<Vec<Vec<ExecutionNode>>> {
+    let mut draft_inputs = Vec::new();
+    for spec_file in spec_files {
+        let draft_path = determine_draft_input_path(&spec_file, SPECIFICATIONS_DIR, DRAFTS_DIR)?;
+        if draft_path.exists() {
+            draft_inputs.push(draft_path);
+        } else {
+            draft_inputs.push(spec_file);
+        }
+    }
+
+    build_execution_plan(draft_inputs, DRAFTS_DIR, None)
+}
+
+/// Serializes an implementation execution plan to JSON on stdout without running any agent.
+fn emit_implementation_build_plan(
+    execution_levels: Vec<Vec<ExecutionNode>>,
+    tracker: &BuildTracker,
+) -> Result<()> {
+    let mut levels_json = Vec::new();
+
+    for level in execution_levels {
+        let mut level_json = Vec::new();
+        for node in level {
+            let context_file = resolve_implementation_context_file(&node.input_path)?;
+            let output_path = determine_implementation_output_path(&context_file, SPECIFICATIONS_DIR)?;
+            let needs_update = tracker.needs_update(
+                Stage::Implementation,
+                &node.name,
+                &context_file,
+                &output_path,
+            )?;
+
+            level_json.push(json!({
+                "name": node.name,
+                "input_path": context_file.display().to_string(),
+                "output_path": output_path.display().to_string(),
+                "agent": "create_implementation",
+                "direct_dependencies": node.direct_dependency_names(),
+                "needs_update": needs_update,
+            }));
+        }
+        levels_json.push(level_json);
+    }
+
+    println!("{}", serde_json::to_string_pretty(&levels_json)?);
+    Ok(())
+}
+
+fn resolve_implementation_context_file(node_input_path: &Path) -> Result<PathBuf> {
+    if node_input_path.starts_with(DRAFTS_DIR) {
+        determine_specification_output_path(node_input_path, DRAFTS_DIR, SPECIFICATIONS_DIR)
+    } else {
+        Ok(node_input_path.to_path_buf())
+    }
 }
 
 fn build_implemented_dependency_context(
@@ -1774,32 +2492,71 @@ fn build_implemented_dependency_context(
     Ok(artifacts)
 }
 
-pub async fn compile(config: &Config) -> Result<()> {
-    println!("Compiling project with cargo build...");
+pub async fn compile(check: bool, release: bool, config: &Config) -> Result<()> {
+    let cargo_subcommand = if check { "check" } else { "build" };
+    println!(
+        "Compiling project with cargo {}{}...",
+        cargo_subcommand,
+        if release { " --release" } else { "" }
+    );
 
     if config.dry_run {
-        println!("[DRY RUN] Would run: cargo build");
+        println!("[DRY RUN] Would run: cargo {}{}", cargo_subcommand, if release { " --release" } else { "" });
         return Ok(());
     }
 
-    let output = Command::new("cargo")
-        .arg("build")
-        .output()
-        .context("Failed to execute cargo build")?;
+    let output =
+        compilation_fix::run_cargo_build(Path::new("."), check, release, config.package.as_deref())?;
 
-    if config.verbose || !output.status.success() {
-        print!("{}", String::from_utf8_lossy(&output.stdout));
-        eprint!("{}", String::from_utf8_lossy(&output.stderr));
+    if config.verbose {
+        print!("{}", output.stdout);
+        eprint!("{}", output.stderr);
+    }
+    if !output.status_success {
+        compilation_fix::print_diagnostics(&output.diagnostics);
     }
 
-    if output.status.success() {
+    if output.status_success {
         println!("✓ Build successful");
         Ok(())
     } else {
-        anyhow::bail!("Build failed");
+        anyhow::bail!("Build failed with {} diagnostic(s)", output.diagnostics.len());
     }
 }
 
+pub async fn checkpoint(config: &Config) -> Result<()> {
+    if config.dry_run {
+        println!("[DRY RUN] Would open a checkpoint");
+        return Ok(());
+    }
+
+    let depth = checkpoint::open()?;
+    println!("✓ Opened checkpoint (depth {})", depth);
+    Ok(())
+}
+
+pub async fn commit(config: &Config) -> Result<()> {
+    if config.dry_run {
+        println!("[DRY RUN] Would commit the innermost open checkpoint");
+        return Ok(());
+    }
+
+    checkpoint::commit()?;
+    println!("✓ Checkpoint committed");
+    Ok(())
+}
+
+pub async fn rollback(config: &Config) -> Result<()> {
+    if config.dry_run {
+        println!("[DRY RUN] Would roll back the innermost open checkpoint");
+        return Ok(());
+    }
+
+    checkpoint::rollback()?;
+    println!("✓ Checkpoint rolled back");
+    Ok(())
+}
+
 pub async fn fix(max_compile_fix_attempts: usize, config: &Config) -> Result<()> {
     println!(
         "Attempting to restore compilation (max_attempts={})...",
@@ -1836,18 +2593,37 @@ pub async fn fix(max_compile_fix_attempts: usize, config: &Config) -> Result<()>
         }
     }
 
-    compilation_fix::ensure_compiles_with_auto_fix(
+    checkpoint::open().context("Failed to open checkpoint for compilation-fix loop")?;
+
+    let result = compilation_fix::ensure_compiles_with_auto_fix(
         config,
         max_compile_fix_attempts,
         project_root,
         &project_info,
         &recent_files,
     )
-    .await
+    .await;
+
+    match result {
+        Ok(()) => {
+            checkpoint::commit().context("Failed to commit checkpoint after successful fix")?;
+            Ok(())
+        }
+        Err(err) => {
+            checkpoint::rollback().with_context(|| {
+                format!("Failed to roll back checkpoint after fix loop failed: {}", err)
+            })?;
+            eprintln!("✗ Compilation-fix loop failed; rolled back to the pre-fix checkpoint");
+            Err(err)
+        }
+    }
 }
 
-pub async fn run(args: Vec<String>, config: &Config) -> Result<()> {
-    println!("Building and running project with cargo run...");
+pub async fn run(args: Vec<String>, release: bool, config: &Config) -> Result<()> {
+    println!(
+        "Building and running project with cargo run{}...",
+        if release { " --release" } else { "" }
+    );
 
     if config.dry_run {
         let args_str = if args.is_empty() {
@@ -1855,12 +2631,22 @@ pub async fn run(args: Vec<String>, config: &Config) -> Result<()> {
         } else {
             format!(" -- {}", args.join(" "))
         };
-        println!("[DRY RUN] Would run: cargo run{}", args_str);
+        println!(
+            "[DRY RUN] Would run: cargo run{}{}",
+            if release { " --release" } else { "" },
+            args_str
+        );
         return Ok(());
     }
 
     let mut cmd = Command::new("cargo");
     cmd.arg("run");
+    if release {
+        cmd.arg("--release");
+    }
+    if let Some(package) = config.package.as_deref() {
+        cmd.arg("-p").arg(package);
+    }
 
     // Add separator and arguments if any were provided
     if !args.is_empty() {
@@ -1886,25 +2672,57 @@ pub async fn run(args: Vec<String>, config: &Config) -> Result<()> {
     }
 }
 
-pub async fn test(config: &Config) -> Result<()> {
-    println!("Testing project with cargo test...");
+pub async fn test(
+    release: bool,
+    runner: TestRunner,
+    status_level: Option<String>,
+    filter: Option<String>,
+    config: &Config,
+) -> Result<()> {
+    let use_nextest = match runner {
+        TestRunner::Cargo => false,
+        TestRunner::Nextest => {
+            if !compilation_fix::nextest_available() {
+                anyhow::bail!(
+                    "cargo-nextest is not installed; install it with `cargo install cargo-nextest` \
+                     or drop --runner nextest to fall back to cargo test"
+                );
+            }
+            true
+        }
+        TestRunner::Auto => compilation_fix::nextest_available(),
+    };
+    let runner_label = if use_nextest { "cargo nextest run" } else { "cargo test" };
+
+    println!(
+        "Testing project with {}{}...",
+        runner_label,
+        if release { " --release" } else { "" }
+    );
 
     if config.dry_run {
-        println!("[DRY RUN] Would run: cargo test");
+        println!(
+            "[DRY RUN] Would run: {}{}",
+            runner_label,
+            if release { " --release" } else { "" }
+        );
         return Ok(());
     }
 
-    let output = Command::new("cargo")
-        .arg("test")
-        .output()
-        .context("Failed to execute cargo test")?;
-
-    if config.verbose || !output.status.success() {
-        print!("{}", String::from_utf8_lossy(&output.stdout));
-        eprint!("{}", String::from_utf8_lossy(&output.stderr));
-    }
+    let summary = if use_nextest {
+        compilation_fix::run_cargo_nextest(
+            Path::new("."),
+            release,
+            config.package.as_deref(),
+            status_level.as_deref(),
+            filter.as_deref(),
+        )?
+    } else {
+        compilation_fix::run_cargo_test(Path::new("."), release, config.package.as_deref())?
+    };
+    compilation_fix::print_test_summary(&summary);
 
-    if output.status.success() {
+    if summary.failed == 0 {
         println!("✓ Tests passed");
         Ok(())
     } else {
@@ -1913,14 +2731,13 @@ pub async fn test(config: &Config) -> Result<()> {
 }
 
 pub async fn clear_cache(target: &str, names: Vec<String>, config: &Config) -> Result<()> {
-    let stage = match target {
+    let alias_config = aliases::load_alias_config()?;
+    let (target, names) = aliases::resolve_alias(&alias_config, target, names)?;
+    let stage = match target.as_str() {
         "specification" | "specifications" => Stage::Specification,
         "implementation" | "implementations" => Stage::Implementation,
         "test" | "tests" => Stage::Tests,
-        other => anyhow::bail!(
-            "Unsupported cache target '{}'. Expected specification(s), implementation(s), or test(s).",
-            other
-        ),
+        other => unreachable!("resolve_alias guarantees a base target, got '{}'", other),
     };
 
     if config.dry_run {
@@ -1961,15 +2778,109 @@ pub async fn clear_cache(target: &str, names: Vec<String>, config: &Config) -> R
     Ok(())
 }
 
+/// Reclaims `.reen/` agent response cache entries that no longer correspond to any
+/// currently-resolvable draft/specification file: whole `instructions_model_hash` directories
+/// for agents/models that no longer appear in any stage, and individual stale `.cache` files
+/// within directories that are still referenced. Manifests are rewritten to match.
+pub async fn cache_gc(config: &Config) -> Result<()> {
+    let reen_dir = PathBuf::from(".reen");
+    if !reen_dir.exists() {
+        println!("No cache directory found; nothing to collect");
+        return Ok(());
+    }
+
+    let agent_registry = FileAgentRegistry::new(None);
+    let model_registry = FileAgentModelRegistry::new(None, None, None);
+
+    let mut live_keys: HashMap<String, HashSet<String>> = HashMap::new();
+    for stage in [Stage::Specification, Stage::Implementation, Stage::Tests] {
+        for (agent_name, input) in stage_cache_candidates(stage, Vec::new())? {
+            let Ok(instructions) = agent_registry.get_specification(&agent_name) else {
+                continue;
+            };
+            let Some(model) = model_registry.get_model(&agent_name).ok().and_then(|m| m.into_iter().next()) else {
+                continue;
+            };
+            let hash_dir = instructions_model_hash(&instructions, &model.name);
+            let input_json = serde_json::to_string(&input).unwrap_or_else(|_| "{}".to_string());
+            let mut hasher = Sha256::new();
+            hasher.update(format!("{}:{}", instructions, input_json).as_bytes());
+            let cache_key = hex::encode(hasher.finalize());
+            live_keys.entry(hash_dir).or_default().insert(cache_key);
+        }
+    }
+
+    let mut removed_dirs = 0usize;
+    let mut removed_files = 0usize;
+
+    for entry in fs::read_dir(&reen_dir).context("Failed to read .reen directory")? {
+        let entry = entry?;
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let Some(dir_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+
+        let Some(live_set) = live_keys.get(dir_name) else {
+            if config.dry_run {
+                println!("[DRY RUN] Would remove stale cache directory {}", path.display());
+            } else {
+                fs::remove_dir_all(&path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+            removed_dirs += 1;
+            continue;
+        };
+
+        for file_entry in fs::read_dir(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?
+        {
+            let file_entry = file_entry?;
+            let file_path = file_entry.path();
+            if file_path.extension().and_then(|s| s.to_str()) != Some("cache") {
+                continue;
+            }
+            let Some(key) = file_path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            if live_set.contains(key) {
+                continue;
+            }
+            if config.dry_run {
+                println!("[DRY RUN] Would remove stale cache file {}", file_path.display());
+            } else {
+                fs::remove_file(&file_path)
+                    .with_context(|| format!("Failed to remove {}", file_path.display()))?;
+                reen::contexts::remove_manifest_entry(&path, key);
+            }
+            removed_files += 1;
+        }
+    }
+
+    if config.dry_run {
+        println!(
+            "[DRY RUN] Would remove {} stale cache directorie(s) and {} stale cache file(s)",
+            removed_dirs, removed_files
+        );
+    } else {
+        println!(
+            "✓ Cache GC removed {} stale cache directorie(s) and {} stale cache file(s)",
+            removed_dirs, removed_files
+        );
+    }
+    Ok(())
+}
+
 pub async fn clear_artifacts(target: &str, names: Vec<String>, config: &Config) -> Result<()> {
-    match target {
+    let alias_config = aliases::load_alias_config()?;
+    let (target, names) = aliases::resolve_alias(&alias_config, target, names)?;
+    match target.as_str() {
         "specification" | "specifications" => clear_specification_artifacts(names, config),
         "implementation" | "implementations" => clear_implementation_artifacts(names, config),
         "test" | "tests" => clear_test_artifacts(names, config),
-        other => anyhow::bail!(
-            "Unsupported clear target '{}'. Expected specification(s), implementation(s), or test(s).",
-            other
-        ),
+        other => unreachable!("resolve_alias guarantees a base target, got '{}'", other),
     }
 }
 
@@ -2026,18 +2937,31 @@ fn clear_specification_artifacts(names: Vec<String>, config: &Config) -> Result<
     Ok(())
 }
 
+/// Resolves the directory that implementation/test artifacts should be cleared from, honoring
+/// `config.package` when the project is a cargo workspace. Falls back to `.` when `cargo
+/// metadata` fails (no workspace, e.g. a source snapshot without a manifest) or the requested
+/// package can't be uniquely resolved, preserving the historical single-crate behavior.
+fn resolve_package_root(config: &Config) -> PathBuf {
+    resolve_workspace_metadata(Path::new("."))
+        .ok()
+        .and_then(|packages| package_root(&packages, config.package.as_deref()))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
 fn clear_implementation_artifacts(names: Vec<String>, config: &Config) -> Result<()> {
     let spec_files = resolve_input_files(SPECIFICATIONS_DIR, names, "md")?;
     if spec_files.is_empty() {
         println!("No implementation artifacts found");
         return Ok(());
     }
+    let root = resolve_package_root(config);
     let mut removed = 0usize;
     let mut found = 0usize;
 
     for spec_file in spec_files {
         found += 1;
-        let output_path = determine_implementation_output_path(&spec_file, SPECIFICATIONS_DIR)?;
+        let output_path =
+            root.join(determine_implementation_output_path(&spec_file, SPECIFICATIONS_DIR)?);
         if output_path.exists() {
             if config.dry_run {
                 println!("[DRY RUN] Would remove {}", output_path.display());
@@ -2059,8 +2983,8 @@ fn clear_implementation_artifacts(names: Vec<String>, config: &Config) -> Result
             );
         }
     } else {
-        remove_dir_if_empty(Path::new("src/data"))?;
-        remove_dir_if_empty(Path::new("src/contexts"))?;
+        remove_dir_if_empty(&root.join("src/data"))?;
+        remove_dir_if_empty(&root.join("src/contexts"))?;
         if removed == 0 {
             println!("No matching implementation artifacts found");
         } else {
@@ -2079,6 +3003,7 @@ fn clear_test_artifacts(names: Vec<String>, config: &Config) -> Result<()> {
         println!("No test artifacts found");
         return Ok(());
     }
+    let root = resolve_package_root(config);
     let mut candidates = Vec::new();
     let mut found = 0usize;
 
@@ -2087,10 +3012,10 @@ fn clear_test_artifacts(names: Vec<String>, config: &Config) -> Result<()> {
         let Some(stem) = spec_file.file_stem().and_then(|s| s.to_str()) else {
             continue;
         };
-        candidates.push(PathBuf::from("tests").join(format!("{}.rs", stem)));
-        candidates.push(PathBuf::from("tests").join(format!("{}_test.rs", stem)));
-        candidates.push(PathBuf::from("tests/generated").join(format!("{}.rs", stem)));
-        candidates.push(PathBuf::from("tests/generated").join(format!("{}_test.rs", stem)));
+        candidates.push(root.join("tests").join(format!("{}.rs", stem)));
+        candidates.push(root.join("tests").join(format!("{}_test.rs", stem)));
+        candidates.push(root.join("tests/generated").join(format!("{}.rs", stem)));
+        candidates.push(root.join("tests/generated").join(format!("{}_test.rs", stem)));
     }
 
     let mut removed = 0usize;
@@ -2113,7 +3038,7 @@ fn clear_test_artifacts(names: Vec<String>, config: &Config) -> Result<()> {
             println!("[DRY RUN] Would remove {} test artifact file(s)", removed);
         }
     } else {
-        remove_dir_if_empty(Path::new("tests/generated"))?;
+        remove_dir_if_empty(&root.join("tests/generated"))?;
         if removed == 0 {
             println!("No matching test artifacts found");
         } else {
@@ -2150,11 +3075,11 @@ fn resolve_input_files(dir: &str, names: Vec<String>, extension: &str) -> Result
         return Ok(Vec::new());
     }
 
-    if names.is_empty() {
-        // Process files in order: data/, contexts/, then root
+    let candidates = if names.is_empty() {
+        // Gather every matching file from data/, contexts/, and root; final ordering is
+        // decided below by `order_by_type_dependencies` rather than by directory.
         let mut files = Vec::new();
 
-        // 1. Process data/ folder first
         let data_dir = dir_path.join("data");
         if data_dir.exists() && data_dir.is_dir() {
             let entries = fs::read_dir(&data_dir)
@@ -2168,7 +3093,6 @@ fn resolve_input_files(dir: &str, names: Vec<String>, extension: &str) -> Result
             }
         }
 
-        // 2. Process contexts/ folder second
         let contexts_dir = dir_path.join("contexts");
         if contexts_dir.exists() && contexts_dir.is_dir() {
             let entries = fs::read_dir(&contexts_dir)
@@ -2182,7 +3106,6 @@ fn resolve_input_files(dir: &str, names: Vec<String>, extension: &str) -> Result
             }
         }
 
-        // 3. Process root files last
         let entries =
             fs::read_dir(&dir_path).context(format!("Failed to read {} directory", dir))?;
         for entry in entries {
@@ -2194,7 +3117,7 @@ fn resolve_input_files(dir: &str, names: Vec<String>, extension: &str) -> Result
             }
         }
 
-        Ok(files)
+        files
     } else {
         // When specific names are provided, search in order: data/, contexts/, then root
         let mut files = Vec::new();
@@ -2228,8 +3151,158 @@ fn resolve_input_files(dir: &str, names: Vec<String>, extension: &str) -> Result
                 );
             }
         }
-        Ok(files)
+        files
+    };
+
+    order_by_type_dependencies(filter_cfg_gated_files(candidates)?)
+}
+
+/// Drops files whose leading `# cfg(...)` front-matter line evaluates false against the
+/// active configuration set from `--cfg key="value"` CLI flags, so specs can be written
+/// once and targeted at optional features without manually excluding them per invocation.
+/// Files with no leading cfg line (the common case) are always kept.
+fn filter_cfg_gated_files(files: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    let active = cfg_expr::active_cfgs();
+    let mut kept = Vec::with_capacity(files.len());
+    for path in files {
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        match extract_leading_cfg(&content) {
+            Some(expr) if !expr.evaluate(active) => continue,
+            _ => kept.push(path),
+        }
     }
+    Ok(kept)
+}
+
+/// Orders `files` so a file referencing another file's inferred type is emitted after it,
+/// modeled on a worklist compiler's dependency-ordered codegen pass: each file's markdown title
+/// gives it a type name (`extract_markdown_title_type`, falling back to its pascal-cased file
+/// stem), and PascalCase tokens or explicit `Depends on:` lines in its body that match another
+/// file's type name become edges. A DFS topological sort tracks a "currently visiting" stack and
+/// bails with the full cycle chain (e.g. "Order → Customer → Order") instead of silently
+/// guessing an order for mutually recursive specs. An explicit `Depends on:` reference that
+/// names no known type gets a "File not found"-style warning, same as an unresolved CLI name.
+fn order_by_type_dependencies(files: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    if files.len() <= 1 {
+        return Ok(files);
+    }
+
+    let mut path_to_type: HashMap<usize, String> = HashMap::new();
+    let mut type_to_index: HashMap<String, usize> = HashMap::new();
+    let mut contents = Vec::with_capacity(files.len());
+
+    for (idx, path) in files.iter().enumerate() {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        let type_name = extract_markdown_title_type(&content)
+            .or_else(|| {
+                path.file_stem()
+                    .and_then(|s| s.to_str())
+                    .and_then(to_pascal_case_title)
+            })
+            .unwrap_or_else(|| format!("File{}", idx));
+        type_to_index.entry(type_name.clone()).or_insert(idx);
+        path_to_type.insert(idx, type_name);
+        contents.push(content);
+    }
+
+    let token_re = Regex::new(r"\b[A-Z][A-Za-z0-9]*\b").expect("valid PascalCase token regex");
+    let depends_on_re =
+        Regex::new(r"(?im)^\s*depends\s+on\s*:\s*(.+)\s*$").expect("valid depends-on regex");
+
+    let mut edges: Vec<Vec<usize>> = vec![Vec::new(); files.len()];
+    for (idx, content) in contents.iter().enumerate() {
+        let own_type = path_to_type[&idx].as_str();
+        let mut referenced = HashSet::new();
+
+        for m in token_re.find_iter(content) {
+            let token = m.as_str();
+            if token == own_type {
+                continue;
+            }
+            if let Some(&dep_idx) = type_to_index.get(token) {
+                if dep_idx != idx {
+                    referenced.insert(dep_idx);
+                }
+            }
+        }
+
+        for captures in depends_on_re.captures_iter(content) {
+            let Some(raw) = captures.get(1) else {
+                continue;
+            };
+            for token in raw.as_str().split(',') {
+                let trimmed = token.trim();
+                if trimmed.is_empty() || trimmed == own_type {
+                    continue;
+                }
+                match type_to_index.get(trimmed) {
+                    Some(&dep_idx) if dep_idx != idx => {
+                        referenced.insert(dep_idx);
+                    }
+                    Some(_) => {}
+                    None => {
+                        eprintln!(
+                            "Warning: Referenced type not found: {} (declared in {})",
+                            trimmed,
+                            files[idx].display()
+                        );
+                    }
+                }
+            }
+        }
+
+        edges[idx] = referenced.into_iter().collect();
+        edges[idx].sort_unstable();
+    }
+
+    enum Mark {
+        Visiting,
+        Done,
+    }
+
+    fn visit(
+        idx: usize,
+        edges: &[Vec<usize>],
+        path_to_type: &HashMap<usize, String>,
+        marks: &mut HashMap<usize, Mark>,
+        stack: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<()> {
+        match marks.get(&idx) {
+            Some(Mark::Done) => return Ok(()),
+            Some(Mark::Visiting) => {
+                let mut chain: Vec<&str> = stack
+                    .iter()
+                    .skip_while(|&&s| s != idx)
+                    .map(|s| path_to_type[s].as_str())
+                    .collect();
+                chain.push(path_to_type[&idx].as_str());
+                anyhow::bail!("Circular dependency detected: {}", chain.join(" → "));
+            }
+            None => {}
+        }
+
+        marks.insert(idx, Mark::Visiting);
+        stack.push(idx);
+        for &dep in &edges[idx] {
+            visit(dep, edges, path_to_type, marks, stack, order)?;
+        }
+        stack.pop();
+        marks.insert(idx, Mark::Done);
+        order.push(idx);
+        Ok(())
+    }
+
+    let mut marks: HashMap<usize, Mark> = HashMap::new();
+    let mut stack = Vec::new();
+    let mut order = Vec::with_capacity(files.len());
+    for idx in 0..files.len() {
+        visit(idx, &edges, &path_to_type, &mut marks, &mut stack, &mut order)?;
+    }
+
+    Ok(order.into_iter().map(|idx| files[idx].clone()).collect())
 }
 
 /// Determines the specification output path preserving folder structure
@@ -2434,15 +3507,150 @@ fn to_pascal_case_title(s: &str) -> Option<String> {
     }
 }
 
-fn validate_generated_rust_layout(project_root: &Path) -> Result<()> {
+/// A single layout-validation finding anchored to a source location, rendered either as a
+/// rustc/`annotate-snippets`-style framed snippet or, behind `--format=plain`, a flat
+/// `file:line:col: message` line for CI logs that don't render multi-line frames well.
+#[derive(Debug, Clone)]
+struct LayoutDiagnostic {
+    file: PathBuf,
+    line: usize,
+    column: usize,
+    span_len: usize,
+    source_line: String,
+    message: String,
+}
+
+impl LayoutDiagnostic {
+    /// Builds a diagnostic pointing at `needle`'s first occurrence in `content` (on the first
+    /// line that contains it), measuring the column/underline in characters rather than bytes.
+    fn at_needle(file: &Path, content: &str, needle: &str, message: String) -> Self {
+        for (line_no, line_content) in content.lines().enumerate() {
+            if let Some(byte_col) = line_content.find(needle) {
+                let column = line_content[..byte_col].chars().count() + 1;
+                return Self {
+                    file: file.to_path_buf(),
+                    line: line_no + 1,
+                    column,
+                    span_len: needle.chars().count(),
+                    source_line: line_content.to_string(),
+                    message,
+                };
+            }
+        }
+        Self::at_file_start(file, message)
+    }
+
+    /// Builds a diagnostic for `line_content` (1-indexed `line`), underlining `span` (a
+    /// substring of `line_content`, matched from its first occurrence) in full.
+    fn at_line(file: &Path, line: usize, line_content: &str, span: &str, message: String) -> Self {
+        let byte_col = line_content.find(span).unwrap_or(0);
+        let column = line_content[..byte_col].chars().count() + 1;
+        Self {
+            file: file.to_path_buf(),
+            line,
+            column,
+            span_len: span.chars().count().max(1),
+            source_line: line_content.to_string(),
+            message,
+        }
+    }
+
+    /// Builds a diagnostic with no specific source line to underline (e.g. a missing
+    /// `Cargo.toml` dependency entry, which is an absence rather than an offending span).
+    fn at_file_start(file: &Path, message: String) -> Self {
+        let first_line = fs::read_to_string(file)
+            .ok()
+            .and_then(|content| content.lines().next().map(|l| l.to_string()))
+            .unwrap_or_default();
+        Self {
+            file: file.to_path_buf(),
+            line: 1,
+            column: 1,
+            span_len: 0,
+            source_line: first_line,
+            message,
+        }
+    }
+}
+
+/// Renders layout-validation diagnostics, grouped by file with several annotations sharing one
+/// snippet per file. `DiagnosticFormat::Plain` instead prints one flat `file:line:col: message`
+/// line per diagnostic, for CI logs that don't render multi-line frames well.
+fn render_layout_diagnostics(issues: &[LayoutDiagnostic], format: DiagnosticFormat) -> String {
+    if matches!(format, DiagnosticFormat::Plain) {
+        let mut msg = String::from("Generated implementation layout validation failed:\n");
+        for issue in issues {
+            msg.push_str(&format!(
+                "  - {}:{}:{}: {}\n",
+                issue.file.display(),
+                issue.line,
+                issue.column,
+                issue.message
+            ));
+        }
+        return msg.trim_end().to_string();
+    }
+
+    let mut files: Vec<&PathBuf> = Vec::new();
+    for issue in issues {
+        if !files.contains(&&issue.file) {
+            files.push(&issue.file);
+        }
+    }
+
+    let mut out = String::from("error: generated implementation layout validation failed\n");
+    for file in files {
+        out.push_str(&format!(" --> {}\n", file.display()));
+        out.push_str("  |\n");
+        for issue in issues.iter().filter(|i| &i.file == file) {
+            let gutter = issue.line.to_string();
+            let pad = " ".repeat(gutter.len());
+            out.push_str(&format!("{} | {}\n", gutter, issue.source_line));
+            if issue.span_len > 0 {
+                let caret_offset = " ".repeat(issue.column.saturating_sub(1));
+                let carets = "^".repeat(issue.span_len);
+                out.push_str(&format!(
+                    "{} | {}{} {}\n",
+                    pad, caret_offset, carets, issue.message
+                ));
+            } else {
+                out.push_str(&format!("{} | {}\n", pad, issue.message));
+            }
+        }
+        out.push_str("  |\n");
+    }
+    out.trim_end().to_string()
+}
+
+/// Crate roots injected behind `--fix-deps` when generated code references them but
+/// `Cargo.toml` doesn't declare them yet. Crates not listed here fall back to `"*"`.
+const DEFAULT_DEPENDENCY_VERSIONS: &[(&str, &str)] = &[
+    ("base64", "0.22"),
+    ("sha2", "0.10"),
+    ("serde", "1"),
+    ("serde_json", "1"),
+    ("anyhow", "1"),
+    ("chrono", "0.4"),
+    ("regex", "1"),
+    ("hex", "0.4"),
+    ("rand", "0.8"),
+    ("uuid", "1"),
+    ("tokio", "1"),
+];
+
+fn validate_generated_rust_layout(
+    project_root: &Path,
+    fix_deps: bool,
+    format: DiagnosticFormat,
+) -> Result<()> {
     let src_dir = project_root.join("src");
     if !src_dir.exists() {
         return Ok(());
     }
 
-    let mut issues = Vec::new();
-    let mut needs_base64 = false;
-    let mut needs_sha2 = false;
+    let mut issues: Vec<LayoutDiagnostic> = Vec::new();
+    let mut referenced_crates: BTreeSet<String> = BTreeSet::new();
+    let mut referenced_features: Vec<(String, PathBuf)> = Vec::new();
 
     for module_dir in [src_dir.join("data"), src_dir.join("contexts")] {
         let mod_rs = module_dir.join("mod.rs");
@@ -2457,57 +3665,220 @@ fn validate_generated_rust_layout(project_root: &Path) -> Result<()> {
             .with_context(|| format!("Failed to read generated source: {}", file.display()))?;
 
         if content.contains("crate::types::") {
-            issues.push(format!(
-                "{} uses `crate::types::...`; project structure uses `crate::data`/`crate::contexts`.",
-                file.display()
+            issues.push(LayoutDiagnostic::at_needle(
+                &file,
+                &content,
+                "crate::types::",
+                "uses `crate::types::...`; project structure uses `crate::data`/`crate::contexts`."
+                    .to_string(),
             ));
         }
 
-        if content.contains("base64::") {
-            needs_base64 = true;
+        referenced_crates.extend(extract_referenced_crate_roots(&content));
+        for feature in extract_cfg_feature_references(&content) {
+            referenced_features.push((feature, file.clone()));
         }
-        if content.contains("sha2::") {
-            needs_sha2 = true;
+    }
+
+    reconcile_cargo_dependencies(project_root, &referenced_crates, fix_deps, &mut issues)?;
+    check_cfg_features_declared(project_root, &referenced_features, &mut issues)?;
+
+    if issues.is_empty() {
+        return Ok(());
+    }
+
+    anyhow::bail!(render_layout_diagnostics(&issues, format))
+}
+
+/// Collects every `feature = "name"` referenced in `content`'s `#[cfg(...)]`/`#![cfg(...)]`
+/// attributes, so generated code gated on a feature that doesn't exist can be flagged instead
+/// of silently never compiling under any feature combination.
+fn extract_cfg_feature_references(content: &str) -> Vec<String> {
+    static CFG_ATTR_RE: OnceLock<Regex> = OnceLock::new();
+    let cfg_attr_re =
+        CFG_ATTR_RE.get_or_init(|| Regex::new(r"#!?\[cfg\(([^)]*)\)\]").expect("valid cfg attribute regex"));
+
+    let mut features = Vec::new();
+    for captures in cfg_attr_re.captures_iter(content) {
+        let Some(inner) = captures.get(1) else {
+            continue;
+        };
+        if let Ok(expr) = CfgExpr::parse(inner.as_str()) {
+            expr.referenced_features(&mut features);
         }
     }
+    features
+}
+
+/// Diffs `referenced` features against `Cargo.toml`'s `[features]` table (if present),
+/// recording one diagnostic per collected reference to a feature the manifest doesn't
+/// declare (a file that gates code on the same feature twice is reported twice).
+fn check_cfg_features_declared(
+    project_root: &Path,
+    referenced: &[(String, PathBuf)],
+    issues: &mut Vec<LayoutDiagnostic>,
+) -> Result<()> {
+    if referenced.is_empty() {
+        return Ok(());
+    }
 
     let cargo_toml = project_root.join("Cargo.toml");
-    if cargo_toml.exists() {
-        let cargo_content = fs::read_to_string(&cargo_toml)
-            .with_context(|| format!("Failed to read {}", cargo_toml.display()))?;
-        if needs_base64 && !cargo_content.contains("\nbase64") {
-            issues.push(
-                "Cargo.toml is missing dependency `base64` while generated code references it."
-                    .to_string(),
-            );
+    if !cargo_toml.exists() {
+        return Ok(());
+    }
+
+    let cargo_content = fs::read_to_string(&cargo_toml)
+        .with_context(|| format!("Failed to read {}", cargo_toml.display()))?;
+    let manifest: toml::Value = cargo_content
+        .parse()
+        .with_context(|| format!("Failed to parse {}", cargo_toml.display()))?;
+
+    let declared: HashSet<String> = manifest
+        .get("features")
+        .and_then(|v| v.as_table())
+        .map(|table| table.keys().cloned().collect())
+        .unwrap_or_default();
+
+    for (feature, file) in referenced {
+        if declared.contains(feature) {
+            continue;
         }
-        if needs_sha2 && !cargo_content.contains("\nsha2") {
-            issues.push(
-                "Cargo.toml is missing dependency `sha2` while generated code references it."
-                    .to_string(),
-            );
+        let content = fs::read_to_string(file)
+            .with_context(|| format!("Failed to read generated source: {}", file.display()))?;
+        issues.push(LayoutDiagnostic::at_needle(
+            file,
+            &content,
+            &format!("feature = \"{}\"", feature),
+            format!(
+                "gates code on feature `{}`, which has no matching entry under [features] in Cargo.toml.",
+                feature
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Collects the set of external crate roots a generated source file actually references: the
+/// leading path segment of every `use` statement and every `foo::bar` path, excluding the
+/// language-reserved roots `std`/`core`/`alloc`/`crate`/`self`/`super`. Line comments are
+/// stripped first so a stray mention inside a `//` comment doesn't count as a real reference.
+/// Only lowercase-leading segments are considered, since crate and module names are
+/// conventionally snake_case while `Type::method` paths are not.
+fn extract_referenced_crate_roots(content: &str) -> BTreeSet<String> {
+    static PATH_ROOT_RE: OnceLock<Regex> = OnceLock::new();
+    let path_root_re = PATH_ROOT_RE.get_or_init(|| {
+        Regex::new(r"(?:^|[^\w:])([a-z_][a-z0-9_]*)::").expect("valid path-root regex")
+    });
+
+    let reserved = ["std", "core", "alloc", "crate", "self", "super"];
+    let mut roots = BTreeSet::new();
+
+    for line in content.lines() {
+        let code = match line.find("//") {
+            Some(idx) => &line[..idx],
+            None => line,
+        };
+        for captures in path_root_re.captures_iter(code) {
+            if let Some(root) = captures.get(1) {
+                let root = root.as_str();
+                if !reserved.contains(&root) {
+                    roots.insert(root.to_string());
+                }
+            }
         }
     }
 
-    if issues.is_empty() {
+    roots
+}
+
+/// Diffs `referenced` crate roots against `Cargo.toml`'s parsed `[dependencies]`/
+/// `[dev-dependencies]` tables. Behind `fix_deps`, injects any missing entries (using
+/// `DEFAULT_DEPENDENCY_VERSIONS`, falling back to `"*"`) into the parsed table and re-serializes
+/// the manifest; otherwise records a "missing dependency" issue per crate, same as before.
+fn reconcile_cargo_dependencies(
+    project_root: &Path,
+    referenced: &BTreeSet<String>,
+    fix_deps: bool,
+    issues: &mut Vec<LayoutDiagnostic>,
+) -> Result<()> {
+    let cargo_toml = project_root.join("Cargo.toml");
+    if !cargo_toml.exists() {
         return Ok(());
     }
 
-    let mut msg = String::from("Generated implementation layout validation failed:\n");
-    for issue in issues {
-        msg.push_str(&format!("  - {}\n", issue));
+    let cargo_content = fs::read_to_string(&cargo_toml)
+        .with_context(|| format!("Failed to read {}", cargo_toml.display()))?;
+    let mut manifest: toml::Value = cargo_content
+        .parse()
+        .with_context(|| format!("Failed to parse {}", cargo_toml.display()))?;
+
+    let declared = declared_dependency_names(&manifest);
+    let missing: Vec<&String> = referenced
+        .iter()
+        .filter(|name| !declared.contains(*name))
+        .collect();
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    if !fix_deps {
+        for name in missing {
+            issues.push(LayoutDiagnostic::at_file_start(
+                &cargo_toml,
+                format!(
+                    "Cargo.toml is missing dependency `{}` while generated code references it.",
+                    name
+                ),
+            ));
+        }
+        return Ok(());
+    }
+
+    let deps_table = manifest
+        .as_table_mut()
+        .context("Cargo.toml is not a table")?
+        .entry("dependencies")
+        .or_insert_with(|| toml::Value::Table(Default::default()))
+        .as_table_mut()
+        .context("Cargo.toml [dependencies] is not a table")?;
+
+    for name in missing {
+        let version = DEFAULT_DEPENDENCY_VERSIONS
+            .iter()
+            .find(|(crate_name, _)| *crate_name == name.as_str())
+            .map(|(_, version)| *version)
+            .unwrap_or("*");
+        deps_table.insert(name.clone(), toml::Value::String(version.to_string()));
+    }
+
+    let serialized =
+        toml::to_string_pretty(&manifest).context("Failed to serialize Cargo.toml")?;
+    fs::write(&cargo_toml, serialized)
+        .with_context(|| format!("Failed to write {}", cargo_toml.display()))?;
+
+    Ok(())
+}
+
+fn declared_dependency_names(manifest: &toml::Value) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for table_name in ["dependencies", "dev-dependencies"] {
+        if let Some(table) = manifest.get(table_name).and_then(|v| v.as_table()) {
+            names.extend(table.keys().cloned());
+        }
     }
-    anyhow::bail!(msg.trim_end().to_string())
+    names
 }
 
-fn validate_mod_exports(mod_file: &Path, issues: &mut Vec<String>) -> Result<()> {
+fn validate_mod_exports(mod_file: &Path, issues: &mut Vec<LayoutDiagnostic>) -> Result<()> {
     let content = fs::read_to_string(mod_file)
         .with_context(|| format!("Failed to read {}", mod_file.display()))?;
     let Some(parent) = mod_file.parent() else {
         return Ok(());
     };
 
-    for line in content.lines() {
+    for (line_idx, line) in content.lines().enumerate() {
         let trimmed = line.trim();
         if !(trimmed.starts_with("pub use ") && trimmed.ends_with(';')) {
             continue;
@@ -2522,11 +3893,16 @@ fn validate_mod_exports(mod_file: &Path, issues: &mut Vec<String>) -> Result<()>
 
         let module_file = parent.join(format!("{}.rs", module_name));
         if !module_file.exists() {
-            issues.push(format!(
-                "{} exports `{}` but module file {} does not exist.",
-                mod_file.display(),
-                path,
-                module_file.display()
+            issues.push(LayoutDiagnostic::at_line(
+                mod_file,
+                line_idx + 1,
+                line,
+                trimmed,
+                format!(
+                    "exports `{}` but module file {} does not exist.",
+                    path,
+                    module_file.display()
+                ),
             ));
             continue;
         }
@@ -2543,11 +3919,16 @@ fn validate_mod_exports(mod_file: &Path, issues: &mut Vec<String>) -> Result<()>
             .iter()
             .any(|needle| module_content.contains(needle))
         {
-            issues.push(format!(
-                "{} exports `{}` but {} does not declare a matching public type.",
-                mod_file.display(),
-                path,
-                module_file.display()
+            issues.push(LayoutDiagnostic::at_line(
+                mod_file,
+                line_idx + 1,
+                line,
+                trimmed,
+                format!(
+                    "exports `{}` but {} does not declare a matching public type.",
+                    path,
+                    module_file.display()
+                ),
             ));
         }
     }
@@ -2659,11 +4040,74 @@ fn determine_implementation_output_path(
     Ok(output_path)
 }
 
+/// Determines the generated test file path preserving folder structure under tests/.
+///
+/// Maps:
+/// - specifications/data/X.md → tests/data/x_test.rs
+/// - specifications/contexts/X.md → tests/contexts/x_test.rs
+/// - specifications/app.md → tests/main_test.rs
+fn determine_tests_output_path(context_file: &Path, specifications_dir: &str) -> Result<PathBuf> {
+    let context_path = context_file.to_path_buf();
+    let specifications_path = PathBuf::from(specifications_dir);
+
+    let relative_path = match context_path.strip_prefix(&specifications_path) {
+        Ok(rel) => rel.to_path_buf(),
+        Err(_) => {
+            let context_components: Vec<_> = context_path.components().collect();
+            let specifications_components: Vec<_> = specifications_path.components().collect();
+
+            if context_components.len() > specifications_components.len()
+                && context_components
+                    .iter()
+                    .zip(specifications_components.iter())
+                    .all(|(a, b)| a == b)
+            {
+                PathBuf::from_iter(
+                    context_components
+                        .iter()
+                        .skip(specifications_components.len()),
+                )
+            } else {
+                let context_str = context_file.to_str().unwrap_or("");
+                if context_str.starts_with(specifications_dir) {
+                    let rel_str = &context_str[specifications_dir.len()..].trim_start_matches('/');
+                    PathBuf::from(rel_str)
+                } else {
+                    context_path
+                        .file_name()
+                        .map(PathBuf::from)
+                        .unwrap_or_else(|| PathBuf::from(""))
+                }
+            }
+        }
+    };
+
+    let output_dir = match relative_path.parent() {
+        Some(parent) if parent.as_os_str().is_empty() => PathBuf::from("tests"),
+        Some(parent) => PathBuf::from("tests").join(parent),
+        None => PathBuf::from("tests"),
+    };
+
+    let file_stem = relative_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .context("Invalid context filename")?;
+
+    let output_filename = if file_stem.eq_ignore_ascii_case("app") {
+        "main_test.rs".to_string()
+    } else {
+        format!("{}_test.rs", file_stem.to_ascii_lowercase())
+    };
+
+    Ok(output_dir.join(output_filename))
+}
+
 #[cfg(test)]
 mod tests {
     use super::{
-        determine_specification_output_path, extract_compile_error_message,
-        extract_implementation_review_error_section, should_apply_review_fix,
+        determine_specification_output_path, determine_tests_output_path,
+        extract_compile_error_message, extract_implementation_review_error_section,
+        should_apply_review_fix,
     };
     use std::path::Path;
 
@@ -2715,6 +4159,24 @@ msg.push_str("- Missing accessor.\n");
         assert_eq!(path, Path::new("drafts/contexts/game_loop.md"));
     }
 
+    #[test]
+    fn maps_specification_path_to_tests_output_path() {
+        let path = determine_tests_output_path(
+            Path::new("specifications/data/ledger.md"),
+            "specifications",
+        )
+        .expect("path mapping");
+        assert_eq!(path, Path::new("tests/data/ledger_test.rs"));
+    }
+
+    #[test]
+    fn maps_app_specification_to_main_test() {
+        let path =
+            determine_tests_output_path(Path::new("specifications/app.md"), "specifications")
+                .expect("path mapping");
+        assert_eq!(path, Path::new("tests/main_test.rs"));
+    }
+
     #[test]
     fn applies_review_fix_only_for_nonempty_changes() {
         assert!(!should_apply_review_fix("hello", "hello"));
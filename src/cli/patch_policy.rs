@@ -0,0 +1,143 @@
+//! Path-scoped patch policy: restricts which files an agent-produced diff is allowed to
+//! create or modify. Modeled on Mercurial/git sparse-checkout narrowspecs rather than a flat
+//! glob list, so a policy can express "everything under src/ except src/generated/" with a
+//! small include/exclude rule set instead of enumerating every permitted path.
+
+use anyhow::{bail, Result};
+
+/// One `path:`/`rootfilesin:` narrowspec rule, normalized to a `/`-separated, prefix- and
+/// suffix-slash-free directory (or file) path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Rule {
+    /// `path:dir` — matches `dir` itself and everything recursively beneath it.
+    Path(String),
+    /// `rootfilesin:dir` — matches only files directly inside `dir`, non-recursively.
+    RootFilesIn(String),
+}
+
+impl Rule {
+    fn parse(spec: &str) -> Result<Self> {
+        if let Some(rest) = spec.strip_prefix("path:") {
+            Ok(Rule::Path(normalize(rest)))
+        } else if let Some(rest) = spec.strip_prefix("rootfilesin:") {
+            Ok(Rule::RootFilesIn(normalize(rest)))
+        } else {
+            bail!(
+                "Unrecognized patch policy rule (expected a `path:` or `rootfilesin:` prefix): {}",
+                spec
+            )
+        }
+    }
+
+    fn matches(&self, rel_path: &str) -> bool {
+        match self {
+            Rule::Path(dir) => {
+                dir.is_empty() || rel_path == dir || rel_path.starts_with(&format!("{}/", dir))
+            }
+            Rule::RootFilesIn(dir) => match rel_path.rsplit_once('/') {
+                Some((parent, _file)) => parent == dir,
+                None => dir.is_empty(),
+            },
+        }
+    }
+}
+
+fn normalize(s: &str) -> String {
+    s.trim().trim_matches('/').to_string()
+}
+
+/// Matches a repo-relative path against a compiled set of include/exclude narrowspec rules.
+#[derive(Debug, Clone)]
+pub enum PatchPolicy {
+    /// No restriction: every path matches. The default when no policy is configured.
+    Always,
+    /// No path matches.
+    Never,
+    Rules {
+        include: Vec<Rule>,
+        exclude: Vec<Rule>,
+    },
+}
+
+impl PatchPolicy {
+    /// No restriction: every path is permitted.
+    pub fn allow_all() -> Self {
+        PatchPolicy::Always
+    }
+
+    /// Compiles `include`/`exclude` narrowspec rule lists (each a `path:...` or
+    /// `rootfilesin:...` string) into a policy matching included-minus-excluded. An empty
+    /// `include` list yields a policy that matches nothing, mirroring narrowspec's own
+    /// "no include rules means nothing is in scope" convention.
+    pub fn compile(include: &[String], exclude: &[String]) -> Result<Self> {
+        if include.is_empty() {
+            return Ok(PatchPolicy::Never);
+        }
+        let include = include.iter().map(|s| Rule::parse(s)).collect::<Result<Vec<_>>>()?;
+        let exclude = exclude.iter().map(|s| Rule::parse(s)).collect::<Result<Vec<_>>>()?;
+        Ok(PatchPolicy::Rules { include, exclude })
+    }
+
+    /// True if `rel_path` (a repo-relative, `/`-separated path with any `a/`/`b/` diff
+    /// prefix already stripped) is permitted by this policy.
+    pub fn matches(&self, rel_path: &str) -> bool {
+        match self {
+            PatchPolicy::Always => true,
+            PatchPolicy::Never => false,
+            PatchPolicy::Rules { include, exclude } => {
+                include.iter().any(|r| r.matches(rel_path))
+                    && !exclude.iter().any(|r| r.matches(rel_path))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allow_all_matches_everything() {
+        let policy = PatchPolicy::allow_all();
+        assert!(policy.matches("src/lib.rs"));
+        assert!(policy.matches("Cargo.toml"));
+    }
+
+    #[test]
+    fn empty_include_matches_nothing() {
+        let policy = PatchPolicy::compile(&[], &[]).unwrap();
+        assert!(!policy.matches("src/lib.rs"));
+    }
+
+    #[test]
+    fn path_rule_matches_recursively() {
+        let policy = PatchPolicy::compile(&["path:src".to_string()], &[]).unwrap();
+        assert!(policy.matches("src/lib.rs"));
+        assert!(policy.matches("src/cli/mod.rs"));
+        assert!(!policy.matches("tests/it.rs"));
+        assert!(!policy.matches("srcfoo/lib.rs"));
+    }
+
+    #[test]
+    fn rootfilesin_rule_is_not_recursive() {
+        let policy = PatchPolicy::compile(&["rootfilesin:src".to_string()], &[]).unwrap();
+        assert!(policy.matches("src/lib.rs"));
+        assert!(!policy.matches("src/cli/mod.rs"));
+    }
+
+    #[test]
+    fn exclude_narrows_include() {
+        let policy = PatchPolicy::compile(
+            &["path:src".to_string()],
+            &["path:src/generated".to_string()],
+        )
+        .unwrap();
+        assert!(policy.matches("src/lib.rs"));
+        assert!(!policy.matches("src/generated/schema.rs"));
+    }
+
+    #[test]
+    fn rejects_unrecognized_prefix() {
+        assert!(PatchPolicy::compile(&["src/lib.rs".to_string()], &[]).is_err());
+    }
+}
@@ -1,7 +1,12 @@
 use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
 /// Information about the project structure extracted from specifications
 #[derive(Debug, Default)]
@@ -14,10 +19,335 @@ pub struct ProjectInfo {
     pub dependencies: HashMap<String, String>,
     /// Package name
     pub package_name: String,
+    /// One entry per top-level spec folder when analyzed in workspace mode; empty when the
+    /// project is a single flat crate (the common case).
+    pub workspace: Vec<CrateInfo>,
+    /// Last-seen modification time (seconds since epoch) of every scanned spec file, keyed
+    /// by its path relative to `spec_dir`. Used by [`analyze_specifications_incremental`] to
+    /// skip re-analyzing files that haven't changed since the previous pass.
+    pub mtimes: HashMap<String, u64>,
+    /// Per-module `#[cfg(...)]` gating and feature requirements, parsed from a spec's
+    /// front matter and keyed the same way as `type_names` (`folder/module_name`).
+    pub module_cfgs: HashMap<String, ModuleMeta>,
+    /// `[package]` metadata overrides collected from spec front matter. Any spec may supply
+    /// these; the last one encountered during the scan wins for each individual field.
+    pub package_meta: PackageMeta,
+}
+
+/// `#[cfg(...)]` gating for a single generated module, parsed from a spec file's front
+/// matter (`feature = "..."`, `cfg = ["unix", "target_os = \"linux\""]`).
+#[derive(Debug, Clone, Default)]
+pub struct ModuleMeta {
+    pub feature: Option<String>,
+    pub cfg: Vec<String>,
+}
+
+impl ModuleMeta {
+    /// Renders this module's gating as `#[cfg(...)]` attribute lines, one per requirement
+    /// (a feature gate and any raw `cfg` expressions), in a stable order.
+    fn cfg_attribute_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(feature) = &self.feature {
+            lines.push(format!("#[cfg(feature = \"{}\")]\n", feature));
+        }
+        for expr in &self.cfg {
+            lines.push(format!("#[cfg({})]\n", expr));
+        }
+        lines
+    }
+}
+
+/// `[package]` field overrides sourced from spec front matter, falling back to the
+/// generator's own defaults (`version = "0.1.0"`, `edition = "2021"`) when unset.
+#[derive(Debug, Clone, Default)]
+pub struct PackageMeta {
+    pub edition: Option<String>,
+    pub version: Option<String>,
+    pub authors: Vec<String>,
+    pub description: Option<String>,
+}
+
+/// One member crate of a generated Cargo workspace, corresponding to a single top-level
+/// folder under `spec_dir` (e.g. `contexts/`, `data/`).
+#[derive(Debug, Clone, Default)]
+pub struct CrateInfo {
+    /// The crate's package name, e.g. `myproject-contexts`.
+    pub name: String,
+    /// The top-level spec folder this crate was derived from, e.g. `contexts`.
+    pub folder: String,
+    /// Module names belonging to this crate (same shape as `ProjectInfo::modules`' values).
+    pub modules: Vec<String>,
+    /// Type names for this crate's modules, keyed by bare module name (no folder prefix).
+    pub type_names: HashMap<String, String>,
+    /// Folders of other workspace crates this crate's generated code references types from.
+    pub depends_on: Vec<String>,
 }
 
 /// Analyzes all specifications and extracts project structure information
 pub fn analyze_specifications(spec_dir: &Path, draft_dir: Option<&Path>) -> Result<ProjectInfo> {
+    analyze_specifications_impl(spec_dir, draft_dir, false)
+}
+
+/// Like [`analyze_specifications`], but additionally partitions the result into one member
+/// crate per top-level spec folder (`project_info.workspace`), so the generator functions can
+/// emit a multi-crate Cargo workspace instead of a single flat crate.
+pub fn analyze_specifications_as_workspace(
+    spec_dir: &Path,
+    draft_dir: Option<&Path>,
+) -> Result<ProjectInfo> {
+    analyze_specifications_impl(spec_dir, draft_dir, true)
+}
+
+/// Re-analyzes `spec_dir` against a `previous` result, only re-reading spec files whose
+/// mtime has changed since `previous` was produced, and removing the `modules`/`type_names`
+/// entries of any file that's been deleted. Returns the updated [`ProjectInfo`] along with
+/// the set of top-level folders holding a spec file that was added, modified (by mtime) or
+/// deleted, so the caller can regenerate only the affected `Cargo.toml`/`lib.rs`/`mod.rs`
+/// outputs instead of the whole project. A folder is included whenever one of its files was
+/// re-read, even if its module set and type names come out unchanged.
+pub fn analyze_specifications_incremental(
+    spec_dir: &Path,
+    draft_dir: Option<&Path>,
+    mut previous: ProjectInfo,
+) -> Result<(ProjectInfo, HashSet<String>)> {
+    check_module_name_collisions(spec_dir)?;
+    let crate_map = load_crate_map()?;
+
+    let mut current_mtimes: HashMap<String, u64> = HashMap::new();
+    collect_spec_mtimes(spec_dir, spec_dir, &mut current_mtimes)?;
+
+    let mut changed_folders: HashSet<String> = HashSet::new();
+
+    let removed: Vec<String> = previous
+        .mtimes
+        .keys()
+        .filter(|rel| !current_mtimes.contains_key(*rel))
+        .cloned()
+        .collect();
+    for rel in &removed {
+        let (folder, module_name) = folder_and_module_for(rel);
+        remove_module_entry(&mut previous, &folder, &module_name);
+        previous.mtimes.remove(rel);
+        changed_folders.insert(top_level_folder(&folder));
+    }
+
+    for (rel, mtime) in &current_mtimes {
+        if previous.mtimes.get(rel) == Some(mtime) {
+            continue;
+        }
+        let (folder, module_name) = folder_and_module_for(rel);
+        // Drop any stale entry before re-analyzing, so a changed file doesn't leave behind
+        // its old type name or a duplicate module listing.
+        remove_module_entry(&mut previous, &folder, &module_name);
+
+        let path = spec_dir.join(rel);
+        analyze_spec_file(spec_dir, &path, draft_dir, &crate_map, &mut previous)?;
+        previous.mtimes.insert(rel.clone(), *mtime);
+        changed_folders.insert(top_level_folder(&folder));
+    }
+
+    Ok((previous, changed_folders))
+}
+
+fn folder_and_module_for(relative_spec_path: &str) -> (String, String) {
+    let path = Path::new(relative_spec_path);
+    let folder = path
+        .parent()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let module_name = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("unknown")
+        .to_lowercase();
+    (folder, module_name)
+}
+
+fn remove_module_entry(project_info: &mut ProjectInfo, folder: &str, module_name: &str) {
+    if let Some(modules) = project_info.modules.get_mut(folder) {
+        modules.retain(|m| m != module_name);
+        if modules.is_empty() {
+            project_info.modules.remove(folder);
+        }
+    }
+    let key = if folder.is_empty() {
+        module_name.to_string()
+    } else {
+        format!("{}/{}", folder, module_name)
+    };
+    project_info.type_names.remove(&key);
+    project_info.module_cfgs.remove(&key);
+}
+
+fn collect_spec_mtimes(
+    base_dir: &Path,
+    current_dir: &Path,
+    out: &mut HashMap<String, u64>,
+) -> Result<()> {
+    if !current_dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(current_dir)
+        .with_context(|| format!("Failed to read directory: {}", current_dir.display()))?
+    {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_spec_mtimes(base_dir, &path, out)?;
+        } else if path.extension().and_then(|s| s.to_str()) == Some("md") {
+            let relative = path
+                .strip_prefix(base_dir)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .to_string();
+            let mtime = fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .map(|t| {
+                    t.duration_since(std::time::UNIX_EPOCH)
+                        .map(|d| d.as_secs())
+                        .unwrap_or(0)
+                })
+                .unwrap_or(0);
+            out.insert(relative, mtime);
+        }
+    }
+    Ok(())
+}
+
+/// Regenerates `mod.rs` files for only the top-level folders in `folders`, rather than every
+/// folder in `project_info`, so an incremental watch pass doesn't rewrite outputs that haven't
+/// actually changed. Crate `src/lib.rs` files are not written here (see [`generate_lib_rs`]).
+pub fn generate_mod_files_for_folders(
+    project_info: &ProjectInfo,
+    output_dir: &Path,
+    folders: &HashSet<String>,
+) -> Result<()> {
+    let scoped_modules: HashMap<String, Vec<String>> = project_info
+        .modules
+        .iter()
+        .filter(|(folder, _)| folders.contains(&top_level_folder(folder)))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+
+    let scoped = ProjectInfo {
+        modules: scoped_modules,
+        type_names: project_info.type_names.clone(),
+        dependencies: project_info.dependencies.clone(),
+        package_name: project_info.package_name.clone(),
+        workspace: project_info
+            .workspace
+            .iter()
+            .filter(|c| folders.contains(&c.folder))
+            .cloned()
+            .collect(),
+        mtimes: HashMap::new(),
+        module_cfgs: project_info.module_cfgs.clone(),
+        package_meta: project_info.package_meta.clone(),
+    };
+
+    generate_mod_files(&scoped, output_dir, false)
+}
+
+/// A `cargo check` diagnostic produced against a generated project, with the originating
+/// spec file filled in where it can be confidently traced back (currently: diagnostics whose
+/// message names a type from `project_info.type_names`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerationDiagnostic {
+    pub file: String,
+    pub line: u32,
+    pub message: String,
+    pub originating_spec: Option<String>,
+}
+
+/// Post-generation verification stage: runs `rustfmt` over every generated `.rs` file, then
+/// `cargo check --message-format=json` against `output_dir`, mapping diagnostics back to the
+/// spec file that produced the offending type where possible (e.g. a bad type name from
+/// `extract_type_name`/`to_pascal_case_title` shows up as a generation problem pointing at
+/// the spec, not just a raw rustc error at a generated path).
+pub fn verify_generated_project(
+    project_info: &ProjectInfo,
+    output_dir: &Path,
+) -> Result<Vec<GenerationDiagnostic>> {
+    for path in collect_generated_rs_files(project_info, output_dir) {
+        run_rustfmt_on_file(&path);
+    }
+
+    let output = Command::new("cargo")
+        .arg("check")
+        .arg("--message-format=json")
+        .current_dir(output_dir)
+        .output()
+        .context("Failed to execute cargo check")?;
+
+    // type name -> folder/module key, so a diagnostic mentioning that type can be traced to
+    // the spec file that declared it.
+    let spec_for_type: HashMap<&str, &str> = project_info
+        .type_names
+        .iter()
+        .map(|(key, type_name)| (type_name.as_str(), key.as_str()))
+        .collect();
+
+    let mut diagnostics = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Ok(value) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        if value.get("reason").and_then(|r| r.as_str()) != Some("compiler-message") {
+            continue;
+        }
+        let Some(message) = value.get("message") else {
+            continue;
+        };
+        let text = message
+            .get("rendered")
+            .and_then(|r| r.as_str())
+            .unwrap_or_default()
+            .to_string();
+        let Some(span) = message
+            .get("spans")
+            .and_then(|s| s.as_array())
+            .and_then(|arr| arr.first())
+        else {
+            continue;
+        };
+        let file = span
+            .get("file_name")
+            .and_then(|f| f.as_str())
+            .unwrap_or_default()
+            .to_string();
+        let line_no = span.get("line_start").and_then(|l| l.as_u64()).unwrap_or(0) as u32;
+
+        let originating_spec = spec_for_type
+            .iter()
+            .find(|(type_name, _)| text.contains(**type_name))
+            .map(|(_, key)| format!("{}.md", key));
+
+        diagnostics.push(GenerationDiagnostic {
+            file,
+            line: line_no,
+            message: text,
+            originating_spec,
+        });
+    }
+
+    Ok(diagnostics)
+}
+
+fn collect_generated_rs_files(project_info: &ProjectInfo, output_dir: &Path) -> Vec<PathBuf> {
+    if project_info.workspace.is_empty() {
+        return vec![output_dir.join("src/lib.rs")];
+    }
+    project_info
+        .workspace
+        .iter()
+        .map(|krate| output_dir.join(&krate.folder).join("src/lib.rs"))
+        .collect()
+}
+
+fn analyze_specifications_impl(
+    spec_dir: &Path,
+    draft_dir: Option<&Path>,
+    workspace: bool,
+) -> Result<ProjectInfo> {
     let mut project_info = ProjectInfo {
         package_name: spec_dir
             .file_name()
@@ -32,16 +362,229 @@ pub fn analyze_specifications(spec_dir: &Path, draft_dir: Option<&Path>) -> Resu
         .dependencies
         .insert("tracing".to_string(), "0.1".to_string());
 
+    // Reject specs whose module names would collide once generated, before doing any
+    // further work (e.g. `Account.md` and `account.md` both fold to the `account` module).
+    check_module_name_collisions(spec_dir)?;
+
     // Scan all specification files
-    scan_directory(spec_dir, spec_dir, draft_dir, &mut project_info)?;
+    let crate_map = load_crate_map()?;
+    scan_directory(spec_dir, spec_dir, draft_dir, &crate_map, &mut project_info)?;
+    collect_spec_mtimes(spec_dir, spec_dir, &mut project_info.mtimes)?;
+
+    if workspace {
+        project_info.workspace = build_workspace_crates(spec_dir, &project_info)?;
+    }
 
     Ok(project_info)
 }
 
+/// Partitions `project_info`'s flat folder -> modules map into one [`CrateInfo`] per
+/// top-level folder, then discovers cross-crate type references by re-scanning each spec
+/// file for CamelCase tokens that name a type owned by a different top-level folder.
+/// Modeled on `rust-analyzer`'s `CargoWorkspace`: crate-nodes plus reference-derived edges,
+/// topologically checked for cycles (a `data` crate depending on a `contexts` crate that in
+/// turn depends back on `data` is rejected rather than silently emitted).
+fn build_workspace_crates(spec_dir: &Path, project_info: &ProjectInfo) -> Result<Vec<CrateInfo>> {
+    let mut crates: HashMap<String, CrateInfo> = HashMap::new();
+
+    for (folder_key, modules) in &project_info.modules {
+        let top = top_level_folder(folder_key);
+        let entry = crates.entry(top.clone()).or_insert_with(|| CrateInfo {
+            name: format!("{}-{}", project_info.package_name, top),
+            folder: top.clone(),
+            modules: Vec::new(),
+            type_names: HashMap::new(),
+            depends_on: Vec::new(),
+        });
+        entry.modules.extend(modules.iter().cloned());
+    }
+
+    for (key, type_name) in &project_info.type_names {
+        let top = top_level_folder(key);
+        if let Some(module) = key.split('/').next_back() {
+            if let Some(entry) = crates.get_mut(&top) {
+                entry.type_names.insert(module.to_string(), type_name.clone());
+            }
+        }
+    }
+
+    // type name -> owning crate folder, so a CamelCase reference found in another crate's
+    // specs can be resolved to a `path` dependency edge.
+    let owner_of_type: HashMap<String, String> = crates
+        .values()
+        .flat_map(|c| c.type_names.values().map(move |t| (t.clone(), c.folder.clone())))
+        .collect();
+
+    let mut edges: HashMap<String, HashSet<String>> = HashMap::new();
+    collect_cross_crate_edges(spec_dir, spec_dir, &owner_of_type, &mut edges)?;
+
+    for (folder, deps) in &edges {
+        if let Some(entry) = crates.get_mut(folder) {
+            let mut deps: Vec<String> = deps.iter().cloned().collect();
+            deps.sort();
+            entry.depends_on = deps;
+        }
+    }
+
+    check_workspace_dependency_cycles(&crates)?;
+
+    let mut result: Vec<CrateInfo> = crates.into_values().collect();
+    result.sort_by(|a, b| a.folder.cmp(&b.folder));
+    Ok(result)
+}
+
+fn top_level_folder(path: &str) -> String {
+    path.split('/').next().unwrap_or(path).to_string()
+}
+
+fn collect_cross_crate_edges(
+    base_dir: &Path,
+    current_dir: &Path,
+    owner_of_type: &HashMap<String, String>,
+    edges: &mut HashMap<String, HashSet<String>>,
+) -> Result<()> {
+    if !current_dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(current_dir)
+        .with_context(|| format!("Failed to read directory: {}", current_dir.display()))?
+    {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_cross_crate_edges(base_dir, &path, owner_of_type, edges)?;
+        } else if path.extension().and_then(|s| s.to_str()) == Some("md") {
+            let relative_path = path.strip_prefix(base_dir).unwrap_or(&path);
+            let Some(parent) = relative_path.parent() else {
+                continue;
+            };
+            let folder = parent.to_string_lossy().to_string();
+            if folder.is_empty() {
+                continue;
+            }
+            let own_crate = top_level_folder(&folder);
+
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            for token in content.split(|c: char| !c.is_ascii_alphanumeric() && c != '_') {
+                if let Some(owner) = owner_of_type.get(token) {
+                    if *owner != own_crate {
+                        edges.entry(own_crate.clone()).or_default().insert(owner.clone());
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Depth-first cycle check over the `depends_on` edges so two crates can never depend on
+/// each other, directly or transitively.
+fn check_workspace_dependency_cycles(crates: &HashMap<String, CrateInfo>) -> Result<()> {
+    #[derive(PartialEq)]
+    enum State {
+        Visiting,
+        Done,
+    }
+    let mut state: HashMap<&str, State> = HashMap::new();
+
+    fn visit<'a>(
+        folder: &'a str,
+        crates: &'a HashMap<String, CrateInfo>,
+        state: &mut HashMap<&'a str, State>,
+        path: &mut Vec<&'a str>,
+    ) -> Result<()> {
+        match state.get(folder) {
+            Some(State::Done) => return Ok(()),
+            Some(State::Visiting) => {
+                path.push(folder);
+                anyhow::bail!(
+                    "Cyclic workspace crate dependency detected: {}",
+                    path.join(" -> ")
+                );
+            }
+            None => {}
+        }
+        state.insert(folder, State::Visiting);
+        path.push(folder);
+        if let Some(krate) = crates.get(folder) {
+            for dep in &krate.depends_on {
+                visit(dep, crates, state, path)?;
+            }
+        }
+        path.pop();
+        state.insert(folder, State::Done);
+        Ok(())
+    }
+
+    for folder in crates.keys() {
+        let mut path = Vec::new();
+        visit(folder, crates, &mut state, &mut path)?;
+    }
+    Ok(())
+}
+
+/// Walks `spec_dir` and errors if two spec files in the same folder would fold to the same
+/// module name once lowercased (e.g. `Account.md` and `account.md` both become `account`),
+/// naming both offending paths so the conflict is easy to resolve.
+fn check_module_name_collisions(spec_dir: &Path) -> Result<()> {
+    let mut seen: HashMap<String, HashMap<String, PathBuf>> = HashMap::new();
+    collect_module_stems(spec_dir, spec_dir, &mut seen)
+}
+
+fn collect_module_stems(
+    base_dir: &Path,
+    current_dir: &Path,
+    seen: &mut HashMap<String, HashMap<String, PathBuf>>,
+) -> Result<()> {
+    if !current_dir.is_dir() {
+        return Ok(());
+    }
+
+    let entries = fs::read_dir(current_dir)
+        .with_context(|| format!("Failed to read directory: {}", current_dir.display()))?;
+
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+
+        if path.is_dir() {
+            collect_module_stems(base_dir, &path, seen)?;
+        } else if path.extension().and_then(|s| s.to_str()) == Some("md") {
+            let relative_path = path.strip_prefix(base_dir).unwrap_or(&path);
+            let folder = relative_path
+                .parent()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let stem = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("unknown")
+                .to_string();
+            let lower = stem.to_lowercase();
+
+            let folder_map = seen.entry(folder.clone()).or_default();
+            if let Some(existing) = folder_map.get(&lower) {
+                anyhow::bail!(
+                    "Module name collision in '{}': '{}' and '{}' both resolve to module '{}' (case-insensitive)",
+                    if folder.is_empty() { "." } else { &folder },
+                    existing.display(),
+                    path.display(),
+                    lower
+                );
+            }
+            folder_map.insert(lower, path.clone());
+        }
+    }
+
+    Ok(())
+}
+
 fn scan_directory(
     base_dir: &Path,
     current_dir: &Path,
     draft_dir: Option<&Path>,
+    crate_map: &HashMap<String, String>,
     project_info: &mut ProjectInfo,
 ) -> Result<()> {
     if !current_dir.is_dir() {
@@ -57,25 +600,63 @@ fn scan_directory(
 
         if path.is_dir() {
             // Recursively scan subdirectories
-            scan_directory(base_dir, &path, draft_dir, project_info)?;
+            scan_directory(base_dir, &path, draft_dir, crate_map, project_info)?;
         } else if path.extension().and_then(|s| s.to_str()) == Some("md") {
             // Process specification file
-            analyze_spec_file(base_dir, &path, draft_dir, project_info)?;
+            analyze_spec_file(base_dir, &path, draft_dir, crate_map, project_info)?;
         }
     }
 
     Ok(())
 }
 
+/// Raw shape of a spec file's optional `---`-delimited front matter, parsed as TOML (the
+/// same format `.reen/config.toml` and `reen-deps.toml` already use elsewhere in this repo).
+#[derive(Debug, Clone, Default, Deserialize)]
+struct SpecFrontMatter {
+    feature: Option<String>,
+    #[serde(default)]
+    cfg: Vec<String>,
+    edition: Option<String>,
+    version: Option<String>,
+    #[serde(default)]
+    authors: Vec<String>,
+    description: Option<String>,
+}
+
+/// Splits a leading `---\n...\n---` front-matter block off of `content`, returning the
+/// parsed metadata (if present and valid TOML) and the remaining spec body. A file with no
+/// front matter, or an unparsable block, is treated as having none — the whole content is
+/// then used as the spec body, same as before front matter support existed.
+fn split_front_matter(content: &str) -> (Option<SpecFrontMatter>, &str) {
+    let Some(rest) = content.strip_prefix("---\n") else {
+        return (None, content);
+    };
+    let Some(end) = rest.find("\n---") else {
+        return (None, content);
+    };
+
+    let front_matter_text = &rest[..end];
+    let body = rest[end + 4..].strip_prefix('\n').unwrap_or(&rest[end + 4..]);
+
+    match toml::from_str::<SpecFrontMatter>(front_matter_text) {
+        Ok(front_matter) => (Some(front_matter), body),
+        Err(_) => (None, content),
+    }
+}
+
 fn analyze_spec_file(
     base_dir: &Path,
     spec_path: &Path,
     draft_dir: Option<&Path>,
+    crate_map: &HashMap<String, String>,
     project_info: &mut ProjectInfo,
 ) -> Result<()> {
     // Read specification content
-    let content = fs::read_to_string(spec_path)
+    let raw_content = fs::read_to_string(spec_path)
         .with_context(|| format!("Failed to read spec file: {}", spec_path.display()))?;
+    let (front_matter, content) = split_front_matter(&raw_content);
+    let content = content.to_string();
 
     // Extract module path
     let relative_path = spec_path.strip_prefix(base_dir).unwrap_or(spec_path);
@@ -94,23 +675,48 @@ fn analyze_spec_file(
             .or_insert_with(Vec::new)
             .push(module_name.clone());
 
+        let key = if folder.is_empty() {
+            module_name.clone()
+        } else {
+            format!("{}/{}", folder, module_name)
+        };
+
         // Extract type name from corresponding draft first (if available), then from specification.
         let draft_type_name = draft_dir
             .and_then(|draft_root| read_draft_type_name(draft_root, relative_path).ok())
             .flatten();
         let type_name = draft_type_name.or_else(|| extract_type_name(&content));
         if let Some(type_name) = type_name {
-            let key = if folder.is_empty() {
-                module_name
-            } else {
-                format!("{}/{}", folder, module_name)
-            };
-            project_info.type_names.insert(key, type_name);
+            project_info.type_names.insert(key.clone(), type_name);
+        }
+
+        if let Some(front_matter) = &front_matter {
+            if front_matter.feature.is_some() || !front_matter.cfg.is_empty() {
+                project_info.module_cfgs.insert(
+                    key,
+                    ModuleMeta {
+                        feature: front_matter.feature.clone(),
+                        cfg: front_matter.cfg.clone(),
+                    },
+                );
+            }
+            if let Some(edition) = &front_matter.edition {
+                project_info.package_meta.edition = Some(edition.clone());
+            }
+            if let Some(version) = &front_matter.version {
+                project_info.package_meta.version = Some(version.clone());
+            }
+            if !front_matter.authors.is_empty() {
+                project_info.package_meta.authors = front_matter.authors.clone();
+            }
+            if let Some(description) = &front_matter.description {
+                project_info.package_meta.description = Some(description.clone());
+            }
         }
     }
 
     // Detect dependencies from content
-    detect_dependencies(&content, project_info);
+    detect_dependencies(&content, project_info, crate_map);
 
     Ok(())
 }
@@ -200,67 +806,400 @@ fn to_pascal_case_title(s: &str) -> Option<String> {
     }
 }
 
-fn detect_dependencies(content: &str, project_info: &mut ProjectInfo) {
-    // Detect serde (from Serialization section or Serialize/Deserialize keywords)
-    if content.contains("Serialize") || content.contains("Deserialize") || content.contains("serde")
-    {
-        project_info.dependencies.insert(
-            "serde".to_string(),
-            r#"{ version = "1.0", features = ["derive"] }"#.to_string(),
-        );
+const DEPS_CONFIG_FILE: &str = "reen-deps.toml";
+
+/// One `[dependencies]` entry in `reen-deps.toml`: either a bare version string
+/// (`anyhow = "1.0"`) or a detailed table with features (`serde = { version = "1.0",
+/// features = ["derive"] }`), mirroring how `aliases::AliasEntry` lets a config table mix
+/// simple and compound forms.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum DepEntry {
+    Simple(String),
+    Detailed {
+        version: String,
+        #[serde(default)]
+        features: Vec<String>,
+    },
+}
+
+impl DepEntry {
+    /// Renders this entry the same way `ProjectInfo::dependencies` values are already
+    /// stored: a plain `"1.0"` string, or a `{ version = "...", features = [...] }` literal.
+    fn to_manifest_value(&self) -> String {
+        match self {
+            DepEntry::Simple(version) => version.clone(),
+            DepEntry::Detailed { version, features } if features.is_empty() => version.clone(),
+            DepEntry::Detailed { version, features } => {
+                let feature_list = features
+                    .iter()
+                    .map(|f| format!("\"{}\"", f))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!(
+                    "{{ version = \"{}\", features = [{}] }}",
+                    version, feature_list
+                )
+            }
+        }
     }
+}
 
-    // Detect chrono (from DateTime types, Utc, Timestamp references)
-    if content.contains("DateTime")
-        || content.contains("chrono")
-        || content.contains("Utc::now")
-        || content.contains("Timestamp")
-    {
-        project_info.dependencies.insert(
-            "chrono".to_string(),
-            r#"{ version = "0.4", features = ["serde"] }"#.to_string(),
-        );
+#[derive(Debug, Clone, Default, Deserialize)]
+struct RawDepsConfig {
+    #[serde(default)]
+    dependencies: HashMap<String, DepEntry>,
+}
+
+/// The crate-root-segment -> manifest-value map used to resolve imports discovered by
+/// [`detect_dependencies`]. Built from hardcoded defaults, then overridden/extended by an
+/// optional `reen-deps.toml` in the current directory.
+fn default_crate_map() -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    map.insert(
+        "serde".to_string(),
+        r#"{ version = "1.0", features = ["derive"] }"#.to_string(),
+    );
+    map.insert(
+        "chrono".to_string(),
+        r#"{ version = "0.4", features = ["serde"] }"#.to_string(),
+    );
+    map.insert("anyhow".to_string(), "1.0".to_string());
+    map.insert("base64".to_string(), "0.22".to_string());
+    map.insert("sha2".to_string(), "0.10".to_string());
+    map
+}
+
+/// Loads `reen-deps.toml` relative to the current directory, if present, layering its
+/// entries on top of [`default_crate_map`]. A missing file is not an error.
+fn load_crate_map() -> Result<HashMap<String, String>> {
+    let mut map = default_crate_map();
+
+    let path = Path::new(DEPS_CONFIG_FILE);
+    if !path.exists() {
+        return Ok(map);
+    }
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let raw: RawDepsConfig =
+        toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))?;
+    for (name, entry) in raw.dependencies {
+        map.insert(name, entry.to_manifest_value());
     }
+    Ok(map)
+}
 
-    // Detect anyhow (from Result types or error handling)
-    if content.contains("anyhow") {
-        project_info
-            .dependencies
-            .insert("anyhow".to_string(), "1.0".to_string());
+/// Extracts the contents of every fenced ` ```rust ` code block in `content`.
+fn extract_rust_code_blocks(content: &str) -> Vec<String> {
+    let re = Regex::new(r"(?s)```rust\s*\n(.*?)```").expect("static regex is valid");
+    re.captures_iter(content)
+        .map(|caps| caps[1].to_string())
+        .collect()
+}
+
+/// Finds crate-root segments referenced via `use <crate>::...;` statements or bare
+/// `<crate>::` path expressions within a block of Rust code, ignoring the
+/// `crate`/`self`/`super`/`std` roots (which never name an external dependency).
+fn scan_crate_references(code: &str) -> HashSet<String> {
+    let use_re = Regex::new(r"use\s+([A-Za-z_][A-Za-z0-9_]*)\s*(?:::|;)").expect("static regex is valid");
+    let path_re = Regex::new(r"\b([A-Za-z_][A-Za-z0-9_]*)::").expect("static regex is valid");
+
+    let mut found = HashSet::new();
+    for re in [&use_re, &path_re] {
+        for caps in re.captures_iter(code) {
+            let root = caps[1].to_string();
+            if !matches!(root.as_str(), "crate" | "self" | "super" | "std") {
+                found.insert(root);
+            }
+        }
     }
+    found
+}
 
-    // Detect base64
-    if content.contains("base64") || content.contains("Base64") || content.contains("RFC 4648") {
-        project_info
-            .dependencies
-            .insert("base64".to_string(), "0.22".to_string());
+/// Detects external dependencies referenced by a specification's embedded Rust code
+/// blocks, resolving each crate-root reference through `crate_map` (defaults plus any
+/// `reen-deps.toml` overrides) instead of matching bare substrings against the whole file.
+fn detect_dependencies(
+    content: &str,
+    project_info: &mut ProjectInfo,
+    crate_map: &HashMap<String, String>,
+) {
+    for block in extract_rust_code_blocks(content) {
+        for root in scan_crate_references(&block) {
+            if let Some(manifest_value) = crate_map.get(&root) {
+                project_info
+                    .dependencies
+                    .insert(root, manifest_value.clone());
+            }
+        }
     }
+}
 
-    // Detect sha2/SHA256 hashing
-    if content.contains("sha2")
-        || content.contains("Sha256")
-        || content.contains("SHA256")
-        || content.contains("sha256")
-    {
-        project_info
-            .dependencies
-            .insert("sha2".to_string(), "0.10".to_string());
+/// A dependency as resolved by `cargo metadata`, including the feature set cargo
+/// actually activated for it (as opposed to the feature requests we wrote into
+/// Cargo.toml ourselves).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedDependency {
+    pub name: String,
+    pub version: String,
+    pub features: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+struct CargoMetadataCache {
+    cargo_toml_hash: String,
+    dependencies: Vec<ResolvedDependency>,
+}
+
+/// Resolves the manifest's external dependencies by shelling out to `cargo metadata`,
+/// so `create_implementation` can ground generated code in the crates/features cargo
+/// actually resolved instead of whatever `detect_dependencies` guessed from spec text.
+/// The parsed result is cached alongside the manifest, keyed by a hash of Cargo.toml,
+/// so it's only recomputed when the manifest changes.
+pub fn resolve_cargo_metadata(output_dir: &Path) -> Result<Vec<ResolvedDependency>> {
+    let cargo_toml_path = output_dir.join("Cargo.toml");
+    let manifest = fs::read_to_string(&cargo_toml_path)
+        .with_context(|| format!("Failed to read {}", cargo_toml_path.display()))?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(manifest.as_bytes());
+    let manifest_hash = hex::encode(hasher.finalize());
+
+    let cache_path = output_dir.join(".reen").join("cargo_metadata_cache.json");
+    if let Ok(existing) = fs::read_to_string(&cache_path) {
+        if let Ok(cache) = serde_json::from_str::<CargoMetadataCache>(&existing) {
+            if cache.cargo_toml_hash == manifest_hash {
+                return Ok(cache.dependencies);
+            }
+        }
+    }
+
+    let output = Command::new("cargo")
+        .arg("metadata")
+        .arg("--format-version=1")
+        .current_dir(output_dir)
+        .output()
+        .context("Failed to execute cargo metadata")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
     }
+
+    let metadata: Value = serde_json::from_slice(&output.stdout)
+        .context("Failed to parse cargo metadata output")?;
+    let dependencies = parse_resolved_dependencies(&metadata);
+
+    let cache = CargoMetadataCache {
+        cargo_toml_hash: manifest_hash,
+        dependencies: dependencies.clone(),
+    };
+    if let Some(parent) = cache_path.parent() {
+        fs::create_dir_all(parent).ok();
+    }
+    fs::write(
+        &cache_path,
+        serde_json::to_string_pretty(&cache).unwrap_or_default(),
+    )
+    .ok();
+
+    Ok(dependencies)
 }
 
-/// Generates Cargo.toml for the project
-pub fn generate_cargo_toml(project_info: &ProjectInfo, output_dir: &Path) -> Result<()> {
+fn parse_resolved_dependencies(metadata: &Value) -> Vec<ResolvedDependency> {
+    let root_id = metadata
+        .get("resolve")
+        .and_then(|r| r.get("root"))
+        .and_then(|r| r.as_str());
+
+    let features_by_id: HashMap<String, Vec<String>> = metadata
+        .get("resolve")
+        .and_then(|r| r.get("nodes"))
+        .and_then(|n| n.as_array())
+        .map(|nodes| {
+            nodes
+                .iter()
+                .filter_map(|node| {
+                    let id = node.get("id")?.as_str()?.to_string();
+                    let features = node
+                        .get("features")
+                        .and_then(|f| f.as_array())
+                        .map(|arr| {
+                            arr.iter()
+                                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    Some((id, features))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    metadata
+        .get("packages")
+        .and_then(|p| p.as_array())
+        .map(|pkgs| {
+            pkgs.iter()
+                .filter_map(|pkg| {
+                    let id = pkg.get("id")?.as_str()?.to_string();
+                    if Some(id.as_str()) == root_id {
+                        return None;
+                    }
+                    let name = pkg.get("name")?.as_str()?.to_string();
+                    let version = pkg.get("version")?.as_str()?.to_string();
+                    let features = features_by_id.get(&id).cloned().unwrap_or_default();
+                    Some(ResolvedDependency {
+                        name,
+                        version,
+                        features,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// A single build target (lib, bin, test, ...) belonging to a workspace package.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceTarget {
+    pub kind: Vec<String>,
+    pub name: String,
+    pub src_path: String,
+}
+
+/// A workspace member as reported by `cargo metadata`, used to resolve `-p <name>` and
+/// the member's own `src/`/`tests/` roots instead of assuming a single crate at `.`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspacePackage {
+    pub name: String,
+    pub manifest_path: String,
+    pub targets: Vec<WorkspaceTarget>,
+}
+
+/// Shells out to `cargo metadata --format-version=1` and parses the workspace's member
+/// packages and targets, so callers can target an individual workspace member by name
+/// instead of assuming a single crate rooted at `.`. Unlike [`resolve_cargo_metadata`],
+/// this is not cached: workspace membership changes rarely and is read once per command.
+pub fn resolve_workspace_metadata(output_dir: &Path) -> Result<Vec<WorkspacePackage>> {
+    let output = Command::new("cargo")
+        .arg("metadata")
+        .arg("--format-version=1")
+        .arg("--no-deps")
+        .current_dir(output_dir)
+        .output()
+        .context("Failed to execute cargo metadata")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let metadata: Value = serde_json::from_slice(&output.stdout)
+        .context("Failed to parse cargo metadata output")?;
+
+    let members: HashSet<String> = metadata
+        .get("workspace_members")
+        .and_then(|m| m.as_array())
+        .map(|arr| arr.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
+        .unwrap_or_default();
+
+    Ok(metadata
+        .get("packages")
+        .and_then(|p| p.as_array())
+        .map(|pkgs| {
+            pkgs.iter()
+                .filter_map(|pkg| {
+                    let id = pkg.get("id")?.as_str()?.to_string();
+                    if !members.is_empty() && !members.contains(&id) {
+                        return None;
+                    }
+                    let name = pkg.get("name")?.as_str()?.to_string();
+                    let manifest_path = pkg.get("manifest_path")?.as_str()?.to_string();
+                    let targets = pkg
+                        .get("targets")
+                        .and_then(|t| t.as_array())
+                        .map(|arr| {
+                            arr.iter()
+                                .filter_map(|t| {
+                                    let kind = t
+                                        .get("kind")?
+                                        .as_array()?
+                                        .iter()
+                                        .filter_map(|k| k.as_str().map(|s| s.to_string()))
+                                        .collect();
+                                    let name = t.get("name")?.as_str()?.to_string();
+                                    let src_path = t.get("src_path")?.as_str()?.to_string();
+                                    Some(WorkspaceTarget { kind, name, src_path })
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    Some(WorkspacePackage {
+                        name,
+                        manifest_path,
+                        targets,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// Resolves the filesystem directory (the manifest's parent directory) for `package`
+/// within `packages`, or the sole member's directory when there's exactly one and no
+/// name was requested. Returns `None` when the package can't be uniquely resolved, so
+/// callers can fall back to the conventional `.`-rooted `src/`/`tests/` layout.
+pub fn package_root(packages: &[WorkspacePackage], package: Option<&str>) -> Option<PathBuf> {
+    let resolved = match package {
+        Some(name) => packages.iter().find(|p| p.name == name)?,
+        None if packages.len() == 1 => &packages[0],
+        None => return None,
+    };
+    Path::new(&resolved.manifest_path).parent().map(PathBuf::from)
+}
+
+/// Generates Cargo.toml for the project. When `project_info.workspace` is non-empty, emits a
+/// root workspace manifest plus one member-crate manifest per [`CrateInfo`] instead of a
+/// single flat crate. `verify` has no effect here (a manifest isn't run through `rustfmt`)
+/// but is accepted so callers can pass the same flag to all three generator functions; see
+/// [`verify_generated_project`] for the actual post-generation check.
+pub fn generate_cargo_toml(project_info: &ProjectInfo, output_dir: &Path, verify: bool) -> Result<()> {
+    let _ = verify;
+    if !project_info.workspace.is_empty() {
+        return generate_workspace_cargo_tomls(project_info, output_dir);
+    }
+
     let cargo_toml_path = output_dir.join("Cargo.toml");
+    let meta = &project_info.package_meta;
 
     let mut content = String::new();
     content.push_str(&format!(
         "[package]\n\
          name = \"{}\"\n\
-         version = \"0.1.0\"\n\
-         edition = \"2021\"\n\
-         \n",
-        project_info.package_name
+         version = \"{}\"\n\
+         edition = \"{}\"\n",
+        project_info.package_name,
+        meta.version.as_deref().unwrap_or("0.1.0"),
+        meta.edition.as_deref().unwrap_or("2021"),
     ));
+    if !meta.authors.is_empty() {
+        let authors_list = meta
+            .authors
+            .iter()
+            .map(|a| format!("\"{}\"", a))
+            .collect::<Vec<_>>()
+            .join(", ");
+        content.push_str(&format!("authors = [{}]\n", authors_list));
+    }
+    if let Some(description) = &meta.description {
+        content.push_str(&format!("description = \"{}\"\n", description));
+    }
+    content.push('\n');
 
     // Add [lib] section
     content.push_str(&format!(
@@ -292,15 +1231,28 @@ pub fn generate_cargo_toml(project_info: &ProjectInfo, output_dir: &Path) -> Res
     context_features.sort();
     context_features.dedup();
 
-    if !context_features.is_empty() {
+    // Front-matter-declared features aren't limited to `contexts/` and aren't enabled by
+    // default - a module only compiles in when its feature is explicitly selected.
+    let mut declared_features: Vec<String> = project_info
+        .module_cfgs
+        .values()
+        .filter_map(|meta| meta.feature.clone())
+        .collect();
+    declared_features.sort();
+    declared_features.dedup();
+    declared_features.retain(|f| !context_features.contains(f));
+
+    if !context_features.is_empty() || !declared_features.is_empty() {
         content.push_str("\n[features]\n");
-        let default_list = context_features
-            .iter()
-            .map(|f| format!("\"{}\"", f))
-            .collect::<Vec<_>>()
-            .join(", ");
-        content.push_str(&format!("default = [{}]\n", default_list));
-        for feature in &context_features {
+        if !context_features.is_empty() {
+            let default_list = context_features
+                .iter()
+                .map(|f| format!("\"{}\"", f))
+                .collect::<Vec<_>>()
+                .join(", ");
+            content.push_str(&format!("default = [{}]\n", default_list));
+        }
+        for feature in context_features.iter().chain(declared_features.iter()) {
             content.push_str(&format!("{} = []\n", feature));
         }
     }
@@ -315,8 +1267,79 @@ pub fn generate_cargo_toml(project_info: &ProjectInfo, output_dir: &Path) -> Res
     Ok(())
 }
 
-/// Generates src/lib.rs with module declarations
-pub fn generate_lib_rs(project_info: &ProjectInfo, output_dir: &Path) -> Result<()> {
+fn generate_workspace_cargo_tomls(project_info: &ProjectInfo, output_dir: &Path) -> Result<()> {
+    let root_path = output_dir.join("Cargo.toml");
+    let mut members: Vec<&str> = project_info.workspace.iter().map(|c| c.folder.as_str()).collect();
+    members.sort();
+    let members_list = members
+        .iter()
+        .map(|m| format!("    \"{}\",\n", m))
+        .collect::<String>();
+    let root_content = format!("[workspace]\nmembers = [\n{}]\nresolver = \"2\"\n", members_list);
+    fs::write(&root_path, root_content)
+        .with_context(|| format!("Failed to write Cargo.toml to {}", root_path.display()))?;
+
+    let mut deps: Vec<_> = project_info.dependencies.iter().collect();
+    deps.sort_by_key(|(k, _)| *k);
+
+    for krate in &project_info.workspace {
+        let crate_dir = output_dir.join(&krate.folder);
+        fs::create_dir_all(&crate_dir)
+            .with_context(|| format!("Failed to create crate directory: {}", crate_dir.display()))?;
+
+        let mut content = String::new();
+        content.push_str(&format!(
+            "[package]\n\
+             name = \"{}\"\n\
+             version = \"0.1.0\"\n\
+             edition = \"2021\"\n\
+             \n",
+            krate.name
+        ));
+        content.push_str(&format!(
+            "[lib]\n\
+             name = \"{}\"\n\
+             path = \"src/lib.rs\"\n\
+             \n",
+            krate.name.replace('-', "_")
+        ));
+
+        content.push_str("[dependencies]\n");
+        for (name, version) in &deps {
+            if version.starts_with('{') {
+                content.push_str(&format!("{} = {}\n", name, version));
+            } else {
+                content.push_str(&format!("{} = \"{}\"\n", name, version));
+            }
+        }
+        for dep_folder in &krate.depends_on {
+            if let Some(dep_crate) = project_info.workspace.iter().find(|c| &c.folder == dep_folder) {
+                content.push_str(&format!(
+                    "{} = {{ path = \"../{}\" }}\n",
+                    dep_crate.name.replace('-', "_"),
+                    dep_folder
+                ));
+            }
+        }
+
+        let manifest_path = crate_dir.join("Cargo.toml");
+        fs::write(&manifest_path, content).with_context(|| {
+            format!("Failed to write Cargo.toml to {}", manifest_path.display())
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Generates src/lib.rs with module declarations. When `project_info.workspace` is non-empty,
+/// writes one `<folder>/src/lib.rs` per member crate instead of a single root `src/lib.rs`.
+/// When `verify` is true, each written file is immediately passed through `rustfmt` (best
+/// effort; a missing `rustfmt` binary is not a hard error).
+pub fn generate_lib_rs(project_info: &ProjectInfo, output_dir: &Path, verify: bool) -> Result<()> {
+    if !project_info.workspace.is_empty() {
+        return generate_workspace_lib_rs(project_info, output_dir, verify);
+    }
+
     let lib_rs_path = output_dir.join("src/lib.rs");
 
     // Ensure src directory exists
@@ -337,6 +1360,24 @@ pub fn generate_lib_rs(project_info: &ProjectInfo, output_dir: &Path) -> Result<
         }
     }
 
+    // Reject top-level folders that would collide once turned into `pub mod` declarations
+    // (e.g. `Contexts/` and `contexts/` both lowering to `mod contexts`).
+    let mut folders_by_lower: HashMap<String, String> = HashMap::new();
+    for folder in &folders {
+        let lower = folder.to_lowercase();
+        if let Some(existing) = folders_by_lower.get(&lower) {
+            if existing != folder {
+                anyhow::bail!(
+                    "Top-level folder name collision: '{}' and '{}' both resolve to module '{}' (case-insensitive)",
+                    existing,
+                    folder,
+                    lower
+                );
+            }
+        }
+        folders_by_lower.insert(lower, folder.clone());
+    }
+
     // Declare modules
     let mut folders_vec: Vec<_> = folders.into_iter().collect();
     folders_vec.sort();
@@ -352,20 +1393,103 @@ pub fn generate_lib_rs(project_info: &ProjectInfo, output_dir: &Path) -> Result<
 
     fs::write(&lib_rs_path, content)
         .with_context(|| format!("Failed to write lib.rs to {}", lib_rs_path.display()))?;
+    if verify {
+        run_rustfmt_on_file(&lib_rs_path);
+    }
+
+    Ok(())
+}
+
+/// Runs `rustfmt` on a single generated file, in place. Failures (missing binary, syntax
+/// errors in freshly-generated code) are logged and swallowed rather than propagated, since
+/// formatting is a polish step and shouldn't block generation from succeeding.
+fn run_rustfmt_on_file(path: &Path) {
+    match Command::new("rustfmt").arg(path).output() {
+        Ok(output) if !output.status.success() => {
+            eprintln!(
+                "⚠ rustfmt reported issues for {}: {}",
+                path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Err(e) => {
+            eprintln!("⚠ Failed to run rustfmt on {}: {}", path.display(), e);
+        }
+        Ok(_) => {}
+    }
+}
+
+fn generate_workspace_lib_rs(project_info: &ProjectInfo, output_dir: &Path, verify: bool) -> Result<()> {
+    for krate in &project_info.workspace {
+        let src_dir = output_dir.join(&krate.folder).join("src");
+        fs::create_dir_all(&src_dir)
+            .with_context(|| format!("Failed to create src directory: {}", src_dir.display()))?;
+
+        let mut modules = krate.modules.clone();
+        modules.sort();
+        modules.dedup();
+
+        let mut content = String::new();
+        content.push_str("// Auto-generated by reen - do not edit manually\n\n");
+        for module in &modules {
+            let key = format!("{}/{}", krate.folder, module);
+            if let Some(module_meta) = project_info.module_cfgs.get(&key) {
+                for line in module_meta.cfg_attribute_lines() {
+                    content.push_str(&line);
+                }
+            }
+            content.push_str(&format!("mod {};\n", module));
+        }
+        content.push_str("\n// Re-export public items\n");
+        for module in &modules {
+            let type_name = krate
+                .type_names
+                .get(module)
+                .cloned()
+                .unwrap_or_else(|| to_pascal_case(module));
+            let key = format!("{}/{}", krate.folder, module);
+            if let Some(module_meta) = project_info.module_cfgs.get(&key) {
+                for line in module_meta.cfg_attribute_lines() {
+                    content.push_str(&line);
+                }
+            }
+            content.push_str(&format!("pub use {}::{};\n", module, type_name));
+        }
 
+        let lib_rs_path = src_dir.join("lib.rs");
+        fs::write(&lib_rs_path, content)
+            .with_context(|| format!("Failed to write lib.rs to {}", lib_rs_path.display()))?;
+        if verify {
+            run_rustfmt_on_file(&lib_rs_path);
+        }
+    }
     Ok(())
 }
 
-/// Generates mod.rs files for subdirectories
-pub fn generate_mod_files(project_info: &ProjectInfo, output_dir: &Path) -> Result<()> {
+/// Generates mod.rs files for subdirectories. In workspace mode, each crate's top-level
+/// module declarations live in its own `src/lib.rs` (see [`generate_workspace_lib_rs`]), so
+/// this only has work to do for folders nested more than one level deep under a crate. When
+/// `verify` is true, each written file is immediately passed through `rustfmt`.
+pub fn generate_mod_files(project_info: &ProjectInfo, output_dir: &Path, verify: bool) -> Result<()> {
     let src_dir = output_dir.join("src");
+    let workspace_mode = !project_info.workspace.is_empty();
 
     for (folder, modules) in &project_info.modules {
         if folder.is_empty() {
             continue;
         }
+        if workspace_mode && !folder.contains('/') {
+            // Top-level crate folder: handled by generate_workspace_lib_rs.
+            continue;
+        }
 
-        let mod_rs_path = src_dir.join(folder).join("mod.rs");
+        let mod_rs_path = if workspace_mode {
+            let top = top_level_folder(folder);
+            let rest = folder.strip_prefix(&format!("{}/", top)).unwrap_or(folder);
+            output_dir.join(&top).join("src").join(rest).join("mod.rs")
+        } else {
+            src_dir.join(folder).join("mod.rs")
+        };
 
         // Ensure directory exists
         if let Some(parent) = mod_rs_path.parent() {
@@ -379,8 +1503,15 @@ pub fn generate_mod_files(project_info: &ProjectInfo, output_dir: &Path) -> Resu
         let mut sorted_modules = modules.clone();
         sorted_modules.sort();
 
-        // Declare modules
+        // Declare modules, gated by any `#[cfg(...)]`/`#[cfg(feature = "...")]` the spec's
+        // front matter asked for.
         for module in &sorted_modules {
+            let key = format!("{}/{}", folder, module);
+            if let Some(module_meta) = project_info.module_cfgs.get(&key) {
+                for line in module_meta.cfg_attribute_lines() {
+                    content.push_str(&line);
+                }
+            }
             content.push_str(&format!("mod {};\n", module));
         }
 
@@ -394,11 +1525,19 @@ pub fn generate_mod_files(project_info: &ProjectInfo, output_dir: &Path) -> Resu
                 .cloned()
                 .unwrap_or_else(|| to_pascal_case(module));
 
+            if let Some(module_meta) = project_info.module_cfgs.get(&key) {
+                for line in module_meta.cfg_attribute_lines() {
+                    content.push_str(&line);
+                }
+            }
             content.push_str(&format!("pub use {}::{};\n", module, type_name));
         }
 
         fs::write(&mod_rs_path, content)
             .with_context(|| format!("Failed to write mod.rs to {}", mod_rs_path.display()))?;
+        if verify {
+            run_rustfmt_on_file(&mod_rs_path);
+        }
     }
 
     Ok(())
@@ -1,9 +1,61 @@
+use crate::contexts::cassette::{cassette_path, Cassette, CassetteMode};
+use crate::contexts::session::{Session, SessionMessage};
 use crate::contexts::FileCache;
 use crate::data::Cache;
 use serde::Serialize;
 use serde_json;
 use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Default cap on tool-calling turns `run_python_runner` will drive before giving up, guarding
+/// against a runner that keeps issuing tool calls and never produces a final output.
+const DEFAULT_MAX_TOOL_ITERATIONS: usize = 10;
+
+/// One function the Python runner may invoke mid-execution via the tool-calling loop.
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionDeclaration {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+/// Trait for registering functions the Python runner can call during execution.
+pub trait FunctionRegistry {
+    /// The function declarations to advertise to the runner for this agent.
+    fn list_declarations(&self) -> Vec<FunctionDeclaration>;
+
+    /// Invokes `name` with `args`, returning the JSON result to hand back to the runner.
+    fn call(&self, name: &str, args: serde_json::Value) -> Result<serde_json::Value, ExecutionError>;
+}
+
+/// One variable an agent's template may reference as `{{var.name}}`, with enough metadata for a
+/// caller to resolve it or interactively prompt an operator for a missing value.
+#[derive(Debug, Clone)]
+pub struct VariableDefinition {
+    pub name: String,
+    pub description: String,
+    pub default: Option<String>,
+    pub required: bool,
+    pub choices: Option<Vec<String>>,
+}
+
+/// Metadata surfaced when a required `{{var.*}}` placeholder has no supplied value and no
+/// default, sufficient for a caller (CLI/REPL) to prompt the user for an answer and re-run.
+#[derive(Debug, Clone)]
+pub struct MissingVariableInfo {
+    pub name: String,
+    pub description: String,
+    pub default: Option<String>,
+    pub choices: Option<Vec<String>>,
+}
+
+/// Trait for loading the variable definitions an agent's template exposes under `{{var.*}}`.
+pub trait VariableRegistry {
+    /// The variable definitions declared for `agent_name`, if any.
+    fn variables(&self, agent_name: &str) -> Result<Vec<VariableDefinition>, PopulateError>;
+}
 
 /// Errors that can occur during agent population
 #[derive(Debug)]
@@ -12,6 +64,8 @@ pub enum PopulateError {
     InvalidPlaceholderPath(String),
     AgentNotFound(String),
     InvalidSpecification(String),
+    MissingVariable(MissingVariableInfo),
+    EachTargetNotArray(String),
 }
 
 impl fmt::Display for PopulateError {
@@ -29,6 +83,16 @@ impl fmt::Display for PopulateError {
             PopulateError::InvalidSpecification(details) => {
                 write!(f, "Agent specification is invalid: {}", details)
             }
+            PopulateError::MissingVariable(info) => {
+                write!(
+                    f,
+                    "Variable '{}' requires a value: {}",
+                    info.name, info.description
+                )
+            }
+            PopulateError::EachTargetNotArray(path) => {
+                write!(f, "'{{{{#each {}}}}}' target is not an array", path)
+            }
         }
     }
 }
@@ -81,12 +145,14 @@ impl std::error::Error for AgentRunnerError {}
 
 impl From<PopulateError> for AgentRunnerError {
     fn from(e: PopulateError) -> Self {
+        tracing::error!("[AgentRunner] populate failed, variant={:?}, detail={}", e, e);
         AgentRunnerError::Populate(e)
     }
 }
 
 impl From<ExecutionError> for AgentRunnerError {
     fn from(e: ExecutionError) -> Self {
+        tracing::error!("[AgentRunner] execution failed, variant={:?}, detail={}", e, e);
         AgentRunnerError::Execution(e)
     }
 }
@@ -97,16 +163,92 @@ pub struct AgentSpecification {
     pub system_prompt: String,
 }
 
-/// The result of executing an agent
+/// The result of executing an agent, along with metadata a caller can use to surface
+/// latency/cost stats without having to parse logs.
 #[derive(Debug, Clone)]
 pub struct ExecutionResult {
     pub output: String,
+    /// Whether `output` was served from the on-disk cache rather than a live execution.
+    pub cache_hit: bool,
+    /// Wall-clock time the live execution took, or `None` when served from cache.
+    pub duration: Option<Duration>,
 }
 
-/// A model that can execute an agent
-#[derive(Debug, Clone)]
+impl ExecutionResult {
+    /// Builds a result served directly from the cache, with no execution time to report.
+    fn from_cache(output: String) -> Self {
+        Self {
+            output,
+            cache_hit: true,
+            duration: None,
+        }
+    }
+
+    /// Builds a result produced by a live execution that took `duration` wall-clock time.
+    fn from_execution(output: String, duration: Duration) -> Self {
+        Self {
+            output,
+            cache_hit: false,
+            duration: Some(duration),
+        }
+    }
+}
+
+/// A model that can execute an agent, enriched with the provider-specific settings the Python
+/// runner needs to route and configure the call. Only `name` is required; everything else
+/// defaults to unset and is attached via the `with_*` builders.
+#[derive(Debug, Clone, Serialize)]
 pub struct Model {
     pub name: String,
+    pub provider: Option<String>,
+    pub platform: Option<String>,
+    pub max_tokens: Option<u32>,
+    pub temperature: Option<f64>,
+    pub endpoint: Option<String>,
+}
+
+impl Model {
+    /// Creates a model candidate identified by `name`, with no provider/platform/tuning settings.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            provider: None,
+            platform: None,
+            max_tokens: None,
+            temperature: None,
+            endpoint: None,
+        }
+    }
+
+    /// Attaches the provider this model is served by (e.g. "openai", "anthropic").
+    pub fn with_provider(mut self, provider: impl Into<String>) -> Self {
+        self.provider = Some(provider.into());
+        self
+    }
+
+    /// Attaches the platform/runtime this model executes on (e.g. "api", "bedrock").
+    pub fn with_platform(mut self, platform: impl Into<String>) -> Self {
+        self.platform = Some(platform.into());
+        self
+    }
+
+    /// Attaches a token generation cap for this model.
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Attaches a sampling temperature for this model.
+    pub fn with_temperature(mut self, temperature: f64) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Attaches a non-default endpoint to route this model's requests to.
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
 }
 
 /// Trait for loading agent specifications by name
@@ -117,8 +259,9 @@ pub trait AgentRegistry {
 
 /// Trait for resolving execution models by agent name
 pub trait AgentModelRegistry {
-    /// Get the model to use for a given agent
-    fn get_model(&self, agent_name: &str) -> Result<Model, ExecutionError>;
+    /// Gets the ordered list of candidate models to try for a given agent, most preferred first.
+    /// `run` attempts each in turn, falling back to the next on `ModelNotFound`/`ExecutionFailed`.
+    fn get_model(&self, agent_name: &str) -> Result<Vec<Model>, ExecutionError>;
 }
 
 /// Agent Runner context manages execution of agents with templating and caching
@@ -136,6 +279,179 @@ where
     agent_registry: R,
     /// Registry for resolving execution models
     agent_model_registry: M,
+    /// Registry for the agent's `{{var.*}}` definitions (defaults, required flag, choices)
+    variable_registry: Option<Box<dyn VariableRegistry>>,
+    /// Values already supplied for this invocation's `{{var.*}}` placeholders, consulted before
+    /// any registry-declared default
+    variable_values: HashMap<String, String>,
+    /// Functions the Python runner may call mid-execution via the tool-calling loop
+    function_registry: Option<Box<dyn FunctionRegistry>>,
+    /// Cap on tool-calling turns before `run_python_runner` gives up
+    max_tool_iterations: usize,
+}
+
+/// A lexed fragment of a template: literal text, or the raw content of a `{{...}}` tag.
+/// An unterminated `{{` (no matching `}}`) is left as literal text, matching the template
+/// engine's historic behavior.
+enum TemplateToken {
+    Text(String),
+    Tag(String),
+}
+
+/// A parsed template node. `If`/`Each` carry their already-parsed body (and, for `If`, an
+/// `else` body) so the renderer never re-scans tag text at render time.
+enum TemplateNode {
+    Text(String),
+    Interpolation { path: String, optional: bool },
+    If {
+        path: String,
+        body: Vec<TemplateNode>,
+        else_body: Vec<TemplateNode>,
+    },
+    Each {
+        path: String,
+        body: Vec<TemplateNode>,
+    },
+}
+
+/// One `{{#each}}` iteration's context: the current item and its zero-based index, resolved by
+/// `{{this}}`/`{{this.*}}` and `{{@index}}` respectively. Owns a clone of the item rather than
+/// borrowing it, since the resolved each-target is a value local to the render call and can't
+/// outlive the recursive calls it's pushed across.
+struct EachScope {
+    item: serde_json::Value,
+    index: usize,
+}
+
+/// Splits a template into literal text and tag fragments, without interpreting what's inside a
+/// tag (that's `parse_template_nodes`'s job).
+fn tokenize_template(template: &str) -> Vec<TemplateToken> {
+    let mut tokens = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            tokens.push(TemplateToken::Text(rest[..start].to_string()));
+        }
+
+        let after_open = &rest[start + 2..];
+        match after_open.find("}}") {
+            Some(end) => {
+                tokens.push(TemplateToken::Tag(after_open[..end].trim().to_string()));
+                rest = &after_open[end + 2..];
+            }
+            None => {
+                // Unterminated '{{': treat the rest of the template as literal text.
+                tokens.push(TemplateToken::Text(rest[start..].to_string()));
+                rest = "";
+                break;
+            }
+        }
+    }
+
+    if !rest.is_empty() {
+        tokens.push(TemplateToken::Text(rest.to_string()));
+    }
+
+    tokens
+}
+
+/// Recursive-descent parser over the tokens produced by `tokenize_template`. Consumes tokens
+/// from `*pos` until it hits a tag in `stop_tags` (returned, unconsumed in spirit but advanced
+/// past) or runs out of tokens, building a node tree as it goes.
+fn parse_template_nodes(
+    tokens: &[TemplateToken],
+    pos: &mut usize,
+    stop_tags: &[&str],
+) -> Result<(Vec<TemplateNode>, Option<String>), PopulateError> {
+    let mut nodes = Vec::new();
+
+    while *pos < tokens.len() {
+        match &tokens[*pos] {
+            TemplateToken::Text(text) => {
+                nodes.push(TemplateNode::Text(text.clone()));
+                *pos += 1;
+            }
+            TemplateToken::Tag(tag) => {
+                if stop_tags.contains(&tag.as_str()) {
+                    let matched = tag.clone();
+                    *pos += 1;
+                    return Ok((nodes, Some(matched)));
+                }
+
+                if let Some(path) = tag.strip_prefix("#if ") {
+                    *pos += 1;
+                    let (body, stop) = parse_template_nodes(tokens, pos, &["else", "/if"])?;
+                    let (else_body, _) = if stop.as_deref() == Some("else") {
+                        parse_template_nodes(tokens, pos, &["/if"])?
+                    } else {
+                        (Vec::new(), None)
+                    };
+                    nodes.push(TemplateNode::If {
+                        path: path.trim().to_string(),
+                        body,
+                        else_body,
+                    });
+                } else if let Some(path) = tag.strip_prefix("#each ") {
+                    *pos += 1;
+                    let (body, _) = parse_template_nodes(tokens, pos, &["/each"])?;
+                    nodes.push(TemplateNode::Each {
+                        path: path.trim().to_string(),
+                        body,
+                    });
+                } else {
+                    let (path, optional) = if let Some(stripped) = tag.strip_suffix('?') {
+                        (stripped.to_string(), true)
+                    } else {
+                        (tag.clone(), false)
+                    };
+                    nodes.push(TemplateNode::Interpolation { path, optional });
+                    *pos += 1;
+                }
+            }
+        }
+    }
+
+    Ok((nodes, None))
+}
+
+/// `{{#if}}` truthiness: `null`, `false`, `0`, `""`, and empty arrays/objects are falsy;
+/// everything else is truthy.
+fn is_truthy(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Null => false,
+        serde_json::Value::Bool(b) => *b,
+        serde_json::Value::Number(n) => n.as_f64().map(|f| f != 0.0).unwrap_or(true),
+        serde_json::Value::String(s) => !s.is_empty(),
+        serde_json::Value::Array(a) => !a.is_empty(),
+        serde_json::Value::Object(o) => !o.is_empty(),
+    }
+}
+
+/// Navigates a dotted path (already stripped of its `input`/`this` root) through a JSON value,
+/// treating each segment as an array index when it parses as one, otherwise as an object key.
+fn navigate(root: &serde_json::Value, path: &str) -> Option<serde_json::Value> {
+    let mut current = root;
+
+    for part in path.split('.') {
+        current = match part.parse::<usize>() {
+            Ok(index) => current.get(index)?,
+            Err(_) => current.get(part)?,
+        };
+    }
+
+    Some(current.clone())
+}
+
+/// Converts a resolved placeholder value to the string that replaces it in the rendered output.
+fn value_to_string(value: &serde_json::Value) -> Result<String, PopulateError> {
+    match value {
+        serde_json::Value::String(s) => Ok(s.clone()),
+        serde_json::Value::Number(n) => Ok(n.to_string()),
+        serde_json::Value::Bool(b) => Ok(b.to_string()),
+        serde_json::Value::Null => Ok(String::new()),
+        other => serde_json::to_string(other).map_err(|e| PopulateError::InvalidSpecification(e.to_string())),
+    }
 }
 
 impl<T, R, M> AgentRunner<T, R, M>
@@ -157,9 +473,42 @@ where
             input,
             agent_registry,
             agent_model_registry,
+            variable_registry: None,
+            variable_values: HashMap::new(),
+            function_registry: None,
+            max_tool_iterations: DEFAULT_MAX_TOOL_ITERATIONS,
         }
     }
 
+    /// Attaches a `VariableRegistry` supplying this agent's `{{var.*}}` definitions (defaults,
+    /// required flag, allowed values) for `populate` to resolve against.
+    pub fn with_variable_registry(mut self, registry: Box<dyn VariableRegistry>) -> Self {
+        self.variable_registry = Some(registry);
+        self
+    }
+
+    /// Supplies values an operator has already answered for this invocation's `{{var.*}}`
+    /// placeholders, e.g. from a prior `PopulateError::MissingVariable` prompt.
+    pub fn with_variable_values(mut self, values: HashMap<String, String>) -> Self {
+        self.variable_values = values;
+        self
+    }
+
+    /// Attaches a `FunctionRegistry` advertising functions the Python runner may call mid-run.
+    /// Its declarations are sent with the initial request, and a `tool_call` response is
+    /// dispatched through it until the runner produces a final output.
+    pub fn with_function_registry(mut self, registry: Box<dyn FunctionRegistry>) -> Self {
+        self.function_registry = Some(registry);
+        self
+    }
+
+    /// Overrides the default cap on tool-calling turns `run_python_runner` will drive before
+    /// giving up and returning `ExecutionError::PythonRunnerError`.
+    pub fn with_max_tool_iterations(mut self, max_tool_iterations: usize) -> Self {
+        self.max_tool_iterations = max_tool_iterations;
+        self
+    }
+
     /// Role method: agent.populate
     ///
     /// Runs the templating engine using the agent specifications from the registry
@@ -195,22 +544,117 @@ where
     }
 
     /// Executes the agent via Python runner using stdio communication
+    ///
+    /// When `REEN_CASSETTE` is set, the Python runner is bypassed (in replay mode, entirely) in
+    /// favor of a cassette file keyed by a hash of the request: `record` captures the live
+    /// response for later replay, `replay` serves only from what was already captured, so the
+    /// e2e workflow can run without API access.
     fn execute_via_python(
         &self,
         specification: &AgentSpecification,
         model: &Model,
     ) -> Result<ExecutionResult, ExecutionError> {
-        use std::process::{Command, Stdio};
-        use std::io::Write;
+        let span = tracing::debug_span!("execute_via_python", agent = %self.agent, model = %model.name);
+        let _enter = span.enter();
+
+        let request_json = self.build_request_json(specification, model)?;
+
+        let result = match CassetteMode::from_env() {
+            Some(CassetteMode::Replay) => {
+                let started = Instant::now();
+                let cassette = Cassette::load(cassette_path(&self.agent))
+                    .map_err(|e| ExecutionError::PythonRunnerError(e.to_string()))?;
+                let key = Cassette::key_for(&request_json);
+                let output = cassette
+                    .get(&key)
+                    .map_err(|e| ExecutionError::PythonRunnerError(e.to_string()))?
+                    .to_string();
+                Ok(ExecutionResult::from_execution(output, started.elapsed()))
+            }
+            Some(CassetteMode::Record) => {
+                let result = self.run_python_runner(&request_json)?;
+                let mut cassette = Cassette::load(cassette_path(&self.agent))
+                    .map_err(|e| ExecutionError::PythonRunnerError(e.to_string()))?;
+                cassette
+                    .record(&request_json, &result.output)
+                    .map_err(|e| ExecutionError::PythonRunnerError(e.to_string()))?;
+                Ok(result)
+            }
+            None => self.run_python_runner(&request_json),
+        };
+
+        match &result {
+            Ok(r) => tracing::debug!(
+                "[AgentRunner] execute_via_python, agent={}, model={}, duration_ms={}",
+                self.agent,
+                model.name,
+                r.duration.map(|d| d.as_millis()).unwrap_or(0)
+            ),
+            Err(e) => tracing::error!("[AgentRunner] execute_via_python, agent={}, model={}, failed, error={}", self.agent, model.name, e),
+        }
+
+        result
+    }
+
+    /// Builds the request JSON shared by `execute_via_python` and `run_streaming`: the model and
+    /// populated system prompt, plus a `functions` key when a `FunctionRegistry` is attached.
+    fn build_request_json(&self, specification: &AgentSpecification, model: &Model) -> Result<String, ExecutionError> {
+        let request = self.build_request_value(specification, model)?;
+        serde_json::to_string(&request)
+            .map_err(|e| ExecutionError::PythonRunnerError(format!("Failed to serialize request: {}", e)))
+    }
+
+    /// Like `build_request_json`, but for `run_in_session`: adds a `messages` key carrying the
+    /// session's prior conversation turns alongside this turn's model/system prompt/functions.
+    fn build_session_request_json(
+        &self,
+        specification: &AgentSpecification,
+        model: &Model,
+        history: &[SessionMessage],
+    ) -> Result<String, ExecutionError> {
+        let mut request = self.build_request_value(specification, model)?;
+        request["messages"] = serde_json::to_value(history).map_err(|e| {
+            ExecutionError::PythonRunnerError(format!("Failed to serialize session messages: {}", e))
+        })?;
+
+        serde_json::to_string(&request)
+            .map_err(|e| ExecutionError::PythonRunnerError(format!("Failed to serialize request: {}", e)))
+    }
 
-        // Prepare the request JSON
-        let request = serde_json::json!({
-            "model": model.name,
+    /// Shared base of `build_request_json`/`build_session_request_json`: the model and populated
+    /// system prompt, plus a `functions` key when a `FunctionRegistry` is attached.
+    fn build_request_value(&self, specification: &AgentSpecification, model: &Model) -> Result<serde_json::Value, ExecutionError> {
+        let mut request = serde_json::json!({
+            "model": model,
             "system_prompt": specification.system_prompt
         });
 
-        let request_json = serde_json::to_string(&request)
-            .map_err(|e| ExecutionError::PythonRunnerError(format!("Failed to serialize request: {}", e)))?;
+        if let Some(registry) = self.function_registry.as_ref() {
+            let declarations = registry.list_declarations();
+            request["functions"] = serde_json::to_value(&declarations).map_err(|e| {
+                ExecutionError::PythonRunnerError(format!("Failed to serialize function declarations: {}", e))
+            })?;
+        }
+
+        Ok(request)
+    }
+
+    /// Spawns the Python runner over stdio and drives the tool-calling loop until a terminal
+    /// output frame arrives. The only way a live execution actually happens: cassette record mode
+    /// calls it once and saves the final result, and cassette replay mode never calls it.
+    ///
+    /// Uses line-delimited JSON framing so stdin/stdout stay open across turns: `initial_request`
+    /// is written as the first line, and if the runner answers with a `{"type":"tool_call",...}`
+    /// frame, each call is dispatched through the attached `FunctionRegistry` and a
+    /// `{"type":"tool_result",...}` line is written back for it before the next line is read. A
+    /// response with no recognized `"type":"tool_call"` is treated as the terminal
+    /// `{success, output}` frame. The loop is capped at `max_tool_iterations` turns to guard
+    /// against a runner that never produces a final answer.
+    fn run_python_runner(&self, initial_request: &str) -> Result<ExecutionResult, ExecutionError> {
+        use std::io::{BufRead, BufReader, Read, Write};
+        use std::process::{ChildStdin, Command, Stdio};
+
+        let started = Instant::now();
 
         // Spawn the Python runner
         let mut child = Command::new("python3")
@@ -221,32 +665,100 @@ where
             .spawn()
             .map_err(|e| ExecutionError::PythonRunnerError(format!("Failed to spawn Python runner: {}", e)))?;
 
-        // Write the request to stdin
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin.write_all(request_json.as_bytes())
-                .map_err(|e| ExecutionError::PythonRunnerError(format!("Failed to write to Python runner stdin: {}", e)))?;
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| ExecutionError::PythonRunnerError("Failed to open Python runner stdin".to_string()))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| ExecutionError::PythonRunnerError("Failed to open Python runner stdout".to_string()))?;
+        let mut lines = BufReader::new(stdout).lines();
+
+        fn send_line(stdin: &mut ChildStdin, line: &str) -> Result<(), ExecutionError> {
+            writeln!(stdin, "{}", line)
+                .map_err(|e| ExecutionError::PythonRunnerError(format!("Failed to write to Python runner stdin: {}", e)))
         }
 
-        // Wait for the process to complete and capture output
-        let output = child.wait_with_output()
-            .map_err(|e| ExecutionError::PythonRunnerError(format!("Failed to read Python runner output: {}", e)))?;
+        send_line(&mut stdin, initial_request)?;
 
-        // Check if the process succeeded
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            return Err(ExecutionError::PythonRunnerError(format!(
-                "Python runner failed. Stdout: {} Stderr: {}",
-                stdout, stderr
-            )));
+        let mut final_response: Option<serde_json::Value> = None;
+        for _ in 0..self.max_tool_iterations {
+            let line = match lines.next() {
+                Some(line) => line
+                    .map_err(|e| ExecutionError::PythonRunnerError(format!("Failed to read Python runner output: {}", e)))?,
+                None => break,
+            };
+
+            let response: serde_json::Value = serde_json::from_str(&line)
+                .map_err(|e| ExecutionError::PythonRunnerError(format!("Failed to parse response JSON: {}", e)))?;
+
+            if response["type"].as_str() != Some("tool_call") {
+                final_response = Some(response);
+                break;
+            }
+
+            let registry = self.function_registry.as_ref().ok_or_else(|| {
+                ExecutionError::PythonRunnerError(
+                    "Python runner requested a tool call but no FunctionRegistry is attached".to_string(),
+                )
+            })?;
+
+            for call in response["calls"].as_array().cloned().unwrap_or_default() {
+                let id = call["id"].as_str().unwrap_or("").to_string();
+                let name = call["name"].as_str().unwrap_or("").to_string();
+                let arguments = call["arguments"].clone();
+
+                let result = match registry.call(&name, arguments) {
+                    Ok(value) => value,
+                    Err(e) => serde_json::json!({ "error": e.to_string() }),
+                };
+
+                let tool_result = serde_json::json!({
+                    "type": "tool_result",
+                    "id": id,
+                    "result": result,
+                });
+                let tool_result_json = serde_json::to_string(&tool_result)
+                    .map_err(|e| ExecutionError::PythonRunnerError(format!("Failed to serialize tool result: {}", e)))?;
+                send_line(&mut stdin, &tool_result_json)?;
+            }
         }
 
-        // Parse the response JSON
-        let response_json = String::from_utf8(output.stdout)
-            .map_err(|e| ExecutionError::PythonRunnerError(format!("Invalid UTF-8 in response: {}", e)))?;
+        // Close stdin so the runner observes EOF once we're done driving the conversation.
+        drop(stdin);
+
+        let response = final_response.ok_or_else(|| {
+            ExecutionError::PythonRunnerError(format!(
+                "Python runner did not produce a final output within {} tool-call iteration(s)",
+                self.max_tool_iterations
+            ))
+        })?;
 
-        let response: serde_json::Value = serde_json::from_str(&response_json)
-            .map_err(|e| ExecutionError::PythonRunnerError(format!("Failed to parse response JSON: {}", e)))?;
+        let status = child
+            .wait()
+            .map_err(|e| ExecutionError::PythonRunnerError(format!("Failed to wait for Python runner: {}", e)))?;
+
+        let mut stderr = String::new();
+        if let Some(mut stderr_pipe) = child.stderr.take() {
+            let _ = stderr_pipe.read_to_string(&mut stderr);
+        }
+        let duration = started.elapsed();
+
+        tracing::debug!(
+            "[AgentRunner] run_python_runner, agent={}, exit_status={}, stderr_len={}, duration_ms={}",
+            self.agent,
+            status,
+            stderr.len(),
+            duration.as_millis()
+        );
+
+        if !status.success() {
+            return Err(ExecutionError::PythonRunnerError(format!(
+                "Python runner exited with failure. Stderr: {}",
+                stderr
+            )));
+        }
 
         // Check if execution was successful
         if !response["success"].as_bool().unwrap_or(false) {
@@ -255,13 +767,122 @@ where
         }
 
         // Extract the output
-        let output_text = response["output"].as_str()
+        let output_text = response["output"]
+            .as_str()
             .ok_or_else(|| ExecutionError::PythonRunnerError("No output in response".to_string()))?
             .to_string();
 
-        Ok(ExecutionResult {
-            output: output_text,
-        })
+        Ok(ExecutionResult::from_execution(output_text, duration))
+    }
+
+    /// Spawns the Python runner for a streaming execution: instead of the single buffered
+    /// response `run_python_runner` waits for, the runner is expected to emit a sequence of
+    /// `{"type":"delta","text":"..."}` lines as generation progresses, terminated by a
+    /// `{"type":"done","output":"..."}` frame. Lines are read on a background thread so
+    /// `on_delta` fires as soon as each delta arrives rather than after the whole response
+    /// buffers; a stream that closes without ever sending `done` surfaces as
+    /// `ExecutionError::PythonRunnerError`, the same as any other malformed runner response.
+    fn run_python_runner_streaming(
+        &self,
+        request: &str,
+        mut on_delta: impl FnMut(&str),
+    ) -> Result<ExecutionResult, ExecutionError> {
+        use std::io::{BufRead, BufReader, Read, Write};
+        use std::process::{Command, Stdio};
+        use std::sync::mpsc;
+
+        let started = Instant::now();
+
+        let mut child = Command::new("python3")
+            .arg("runner.py")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| ExecutionError::PythonRunnerError(format!("Failed to spawn Python runner: {}", e)))?;
+
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| ExecutionError::PythonRunnerError("Failed to open Python runner stdin".to_string()))?;
+        writeln!(stdin, "{}", request)
+            .map_err(|e| ExecutionError::PythonRunnerError(format!("Failed to write to Python runner stdin: {}", e)))?;
+        drop(stdin);
+
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| ExecutionError::PythonRunnerError("Failed to open Python runner stdout".to_string()))?;
+
+        let (tx, rx) = mpsc::channel::<String>();
+        let reader_handle = std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(l) => {
+                        if tx.send(l).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        let mut accumulated = String::new();
+        let mut done_output: Option<String> = None;
+        for line in rx {
+            let response: serde_json::Value = serde_json::from_str(&line)
+                .map_err(|e| ExecutionError::PythonRunnerError(format!("Failed to parse response JSON: {}", e)))?;
+
+            match response["type"].as_str() {
+                Some("delta") => {
+                    let text = response["text"].as_str().unwrap_or("");
+                    accumulated.push_str(text);
+                    on_delta(text);
+                }
+                Some("done") => {
+                    let output = response["output"].as_str().unwrap_or(&accumulated).to_string();
+                    done_output = Some(output);
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        // Dropping the receiver end (by returning out of the loop above) makes the reader
+        // thread's next send fail, so it unwinds even if the runner kept emitting lines.
+        let _ = reader_handle.join();
+
+        let status = child
+            .wait()
+            .map_err(|e| ExecutionError::PythonRunnerError(format!("Failed to wait for Python runner: {}", e)))?;
+
+        let mut stderr = String::new();
+        if let Some(mut stderr_pipe) = child.stderr.take() {
+            let _ = stderr_pipe.read_to_string(&mut stderr);
+        }
+        let duration = started.elapsed();
+
+        tracing::debug!(
+            "[AgentRunner] run_python_runner_streaming, agent={}, exit_status={}, stderr_len={}, duration_ms={}",
+            self.agent,
+            status,
+            stderr.len(),
+            duration.as_millis()
+        );
+
+        if !status.success() {
+            return Err(ExecutionError::PythonRunnerError(format!(
+                "Python runner exited with failure. Stderr: {}",
+                stderr
+            )));
+        }
+
+        let output = done_output.ok_or_else(|| {
+            ExecutionError::PythonRunnerError("Python runner stream ended without a 'done' frame".to_string())
+        })?;
+
+        Ok(ExecutionResult::from_execution(output, duration))
     }
 
     /// Generates a hash of agent instructions + model name for folder structure
@@ -276,16 +897,22 @@ where
         hex::encode(result)
     }
 
-    /// Generates a cache key based on agent instructions and input values
+    /// Generates a cache key based on agent instructions, input values, and variable assignments
     ///
-    /// The cache key is a hash of agent_instructions + input_json, ensuring that
-    /// changes to either the instructions or input will result in a cache miss.
+    /// The cache key is a hash of agent_instructions + input_json + variable_values, ensuring
+    /// that changes to any of the three will result in a cache miss (so two runs of the same
+    /// template with different `{{var.*}}` answers don't collide on the same cache entry).
     fn generate_cache_key(&self, agent_instructions: &str) -> String {
         // Serialize the input to JSON to get a stable representation
         let input_json = serde_json::to_string(&self.input).unwrap_or_else(|_| "{}".to_string());
 
-        // Create a composite key from agent instructions and input
-        let composite = format!("{}:{}", agent_instructions, input_json);
+        // Sort variable values by name so the key is stable regardless of insertion order
+        let mut sorted_vars: Vec<(&String, &String)> = self.variable_values.iter().collect();
+        sorted_vars.sort_by_key(|(name, _)| *name);
+        let vars_json = serde_json::to_string(&sorted_vars).unwrap_or_else(|_| "[]".to_string());
+
+        // Create a composite key from agent instructions, input, and variable values
+        let composite = format!("{}:{}:{}", agent_instructions, input_json, vars_json);
 
         // Hash the composite key to get a fixed-size key
         let mut hasher = Sha256::new();
@@ -296,6 +923,18 @@ where
         hex::encode(result)
     }
 
+    /// Like `generate_cache_key`, but folds in the session id and the conversation history
+    /// length, so a resumed session's cache entry never collides with a fresh (session-less)
+    /// run or with an earlier turn of the same session.
+    fn generate_session_cache_key(&self, agent_instructions: &str, session_id: &str, history_len: usize) -> String {
+        let base_key = self.generate_cache_key(agent_instructions);
+
+        let mut hasher = Sha256::new();
+        hasher.update(base_key.as_bytes());
+        hasher.update(format!(":{}:{}", session_id, history_len).as_bytes());
+        hex::encode(hasher.finalize())
+    }
+
     /// Role method: cache.get_cached_artefact
     ///
     /// Creates and returns a FileCache instance configured for this agent and model.
@@ -311,143 +950,343 @@ where
     /// - Mandatory: {{input.prop_name}}
     /// - Optional: {{input.prop_name?}}
     /// - Nested: {{input.prop1.prop2}}
+    /// - Array indexing: {{input.items.0.name}}
+    /// - Variables: {{var.name}} / {{var.name?}}, resolved against supplied values, then the
+    ///   registry's declared default, then (if required) a `MissingVariable` error
+    /// - Conditionals: {{#if input.flag}}...{{else}}...{{/if}}
+    /// - Loops: {{#each input.items}}...{{this}}...{{@index}}...{{/each}}
     fn replace_placeholders(&self, template: &str) -> Result<String, PopulateError> {
         let input_json = serde_json::to_value(&self.input)
             .map_err(|e| PopulateError::InvalidSpecification(e.to_string()))?;
 
-        let mut result = template.to_string();
-        let mut offset = 0;
-
-        // Find all placeholders in the template
-        while let Some(start) = result[offset..].find("{{") {
-            let start = offset + start;
-            if let Some(end_pos) = result[start..].find("}}") {
-                let end = start + end_pos;
-
-                // Extract placeholder content between {{ and }}
-                let placeholder = &result[start + 2..end];
+        let tokens = tokenize_template(template);
+        let mut pos = 0;
+        let (nodes, _) = parse_template_nodes(&tokens, &mut pos, &[])?;
 
-                // Check if it's optional (ends with ?)
-                let (path, is_optional) = if placeholder.ends_with('?') {
-                    (&placeholder[..placeholder.len() - 1], true)
-                } else {
-                    (placeholder, false)
-                };
+        let mut scope_stack = Vec::new();
+        self.render_template_nodes(&nodes, &input_json, &mut scope_stack)
+    }
 
-                // Resolve the path in the input JSON
-                let value = self.resolve_path(&input_json, path)?;
-
-                match value {
-                    Some(v) => {
-                        // Convert value to string
-                        let replacement = match v {
-                            serde_json::Value::String(s) => s.clone(),
-                            serde_json::Value::Number(n) => n.to_string(),
-                            serde_json::Value::Bool(b) => b.to_string(),
-                            serde_json::Value::Null => String::new(),
-                            _ => serde_json::to_string(&v)
-                                .map_err(|e| PopulateError::InvalidSpecification(e.to_string()))?,
-                        };
-
-                        // Replace the placeholder (including the {{ and }})
-                        result.replace_range(start..end + 2, &replacement);
-                        offset = start + replacement.len();
-                    }
-                    None => {
-                        if is_optional {
-                            // Remove optional placeholder (including the {{ and }})
-                            result.replace_range(start..end + 2, "");
-                            offset = start;
-                        } else {
-                            // Mandatory placeholder not found
-                            return Err(PopulateError::MissingMandatoryPlaceholder(
-                                path.to_string(),
-                            ));
+    /// Walks a parsed template node tree, resolving placeholders against `input_json` and the
+    /// `{{this}}`/`{{@index}}` scope introduced by enclosing `{{#each}}` blocks.
+    fn render_template_nodes(
+        &self,
+        nodes: &[TemplateNode],
+        input_json: &serde_json::Value,
+        scope_stack: &mut Vec<EachScope>,
+    ) -> Result<String, PopulateError> {
+        let mut result = String::new();
+
+        for node in nodes {
+            match node {
+                TemplateNode::Text(text) => result.push_str(text),
+                TemplateNode::Interpolation { path, optional } => {
+                    let value = self.resolve_template_path(path, input_json, scope_stack)?;
+                    match value {
+                        Some(v) => result.push_str(&value_to_string(&v)?),
+                        None if *optional => {}
+                        None => {
+                            return Err(PopulateError::MissingMandatoryPlaceholder(path.clone()));
                         }
                     }
                 }
-            } else {
-                break;
+                TemplateNode::If {
+                    path,
+                    body,
+                    else_body,
+                } => {
+                    let condition = self
+                        .resolve_template_path(path, input_json, scope_stack)?
+                        .unwrap_or(serde_json::Value::Null);
+                    let branch = if is_truthy(&condition) { body } else { else_body };
+                    result.push_str(&self.render_template_nodes(branch, input_json, scope_stack)?);
+                }
+                TemplateNode::Each { path, body } => {
+                    let target = self
+                        .resolve_template_path(path, input_json, scope_stack)?
+                        .unwrap_or(serde_json::Value::Null);
+                    let items = target
+                        .as_array()
+                        .ok_or_else(|| PopulateError::EachTargetNotArray(path.clone()))?;
+
+                    for (index, item) in items.iter().enumerate() {
+                        scope_stack.push(EachScope {
+                            item: item.clone(),
+                            index,
+                        });
+                        let rendered = self.render_template_nodes(body, input_json, scope_stack);
+                        scope_stack.pop();
+                        result.push_str(&rendered?);
+                    }
+                }
             }
         }
 
         Ok(result)
     }
 
-    /// Helper: Resolve a dotted path in a JSON value
-    ///
-    /// Supports paths like "input.prop1.prop2"
-    fn resolve_path<'a>(&self, value: &'a serde_json::Value, path: &str) -> Result<Option<&'a serde_json::Value>, PopulateError> {
-        let parts: Vec<&str> = path.split('.').collect();
+    /// Resolves a placeholder path against the `var.*` namespace, the innermost `{{#each}}`
+    /// scope (`this`, `this.*`, `@index`), or the serialized input (`input`, `input.*`).
+    fn resolve_template_path(
+        &self,
+        path: &str,
+        input_json: &serde_json::Value,
+        scope_stack: &[EachScope],
+    ) -> Result<Option<serde_json::Value>, PopulateError> {
+        if path.starts_with("var.") {
+            return Ok(self.resolve_variable(path)?.map(serde_json::Value::String));
+        }
 
-        // First part should be "input"
-        if parts.is_empty() || parts[0] != "input" {
-            return Err(PopulateError::InvalidPlaceholderPath(path.to_string()));
+        if path == "@index" {
+            let scope = scope_stack
+                .last()
+                .ok_or_else(|| PopulateError::InvalidPlaceholderPath(path.to_string()))?;
+            return Ok(Some(serde_json::Value::from(scope.index)));
         }
 
-        let mut current = value;
+        if path == "this" || path.starts_with("this.") {
+            let scope = scope_stack
+                .last()
+                .ok_or_else(|| PopulateError::InvalidPlaceholderPath(path.to_string()))?;
+            return Ok(match path.strip_prefix("this.") {
+                Some(rest) => navigate(&scope.item, rest),
+                None => Some(scope.item.clone()),
+            });
+        }
 
-        // Navigate through the path (skip "input" as we start from the input value)
-        for part in &parts[1..] {
-            match current.get(part) {
-                Some(v) => current = v,
-                None => return Ok(None),
-            }
+        if path == "input" {
+            return Ok(Some(input_json.clone()));
         }
 
-        Ok(Some(current))
+        if let Some(rest) = path.strip_prefix("input.") {
+            return Ok(navigate(input_json, rest));
+        }
+
+        Err(PopulateError::InvalidPlaceholderPath(path.to_string()))
+    }
+
+    /// Helper: Resolve a `var.name` placeholder path
+    ///
+    /// Checks `variable_values` first (values already answered for this invocation), then the
+    /// attached `VariableRegistry`'s declared default. A variable marked `required` with neither
+    /// a supplied value nor a default surfaces `PopulateError::MissingVariable` carrying the
+    /// prompt metadata a caller needs to ask the operator and re-run. A variable the registry
+    /// doesn't know about at all resolves to `None`, same as an absent optional input field.
+    fn resolve_variable(&self, path: &str) -> Result<Option<String>, PopulateError> {
+        let name = path
+            .strip_prefix("var.")
+            .ok_or_else(|| PopulateError::InvalidPlaceholderPath(path.to_string()))?;
+
+        if let Some(value) = self.variable_values.get(name) {
+            return Ok(Some(value.clone()));
+        }
+
+        let definitions = match self.variable_registry.as_ref() {
+            Some(registry) => registry.variables(&self.agent)?,
+            None => return Ok(None),
+        };
+
+        let definition = match definitions.iter().find(|d| d.name == name) {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+
+        if let Some(default) = &definition.default {
+            return Ok(Some(default.clone()));
+        }
+
+        if definition.required {
+            return Err(PopulateError::MissingVariable(MissingVariableInfo {
+                name: definition.name.clone(),
+                description: definition.description.clone(),
+                default: definition.default.clone(),
+                choices: definition.choices.clone(),
+            }));
+        }
+
+        Ok(None)
     }
 
     /// Public function: run
     ///
-    /// Activates the agent by orchestrating the complete execution lifecycle
-    /// with persistent caching.
+    /// Activates the agent by orchestrating the complete execution lifecycle with persistent
+    /// caching. `get_model` may return several candidates in preference order; if the current
+    /// candidate's execution fails with `ModelNotFound` or `ExecutionFailed`, the next candidate
+    /// is tried before giving up. The cache entry records whichever model actually produced the
+    /// result, not necessarily the first candidate.
     pub fn run(self) -> Result<ExecutionResult, AgentRunnerError> {
         // Step 1: Load agent template (instructions) before populating
         // This is needed to generate the cache folder hash
-        let agent_template = self.agent_registry.get_specification(&self.agent)
-            .map_err(|e| AgentRunnerError::Populate(e))?;
+        let agent_template = self.agent_registry.get_specification(&self.agent)?;
 
-        // Step 2: Resolve model
-        let model = self.agent_model_registry.get_model(&self.agent)?;
+        // Step 2: Resolve candidate models, most preferred first
+        let mut candidates = self.agent_model_registry.get_model(&self.agent)?.into_iter();
+        let mut model = candidates
+            .next()
+            .ok_or_else(|| ExecutionError::ModelNotFound(self.agent.clone()))?;
 
-        // Step 3: Generate cache key based on agent instructions + input
+        // Step 3: Generate cache key based on agent instructions + input (model-independent;
+        // the model only determines which cache subfolder it's looked up in)
         let cache_key = self.generate_cache_key(&agent_template);
+        let cache_key_prefix = &cache_key[..cache_key.len().min(8)];
+
+        let span = tracing::info_span!("agent_run", agent = %self.agent, cache_key_prefix = %cache_key_prefix);
+        let _enter = span.enter();
+
+        // Step 4: Populate the specification once (replace placeholders in template); it doesn't
+        // depend on which candidate model ends up executing it
+        let specification = {
+            let _populate_span = tracing::debug_span!("populate", agent = %self.agent).entered();
+            self.populate()?
+        };
+
+        loop {
+            // Get cache instance (folder based on hash(instructions + model)) and check for a
+            // cached result under this candidate before spending an execution attempt on it
+            let cache = self.get_cached_artefact(&agent_template, &model.name)?;
+            if let Some(cached_value) = cache.get(&cache_key) {
+                tracing::debug!("[AgentRunner] run, agent={}, model={}, cache_hit=true", self.agent, model.name);
+                return Ok(ExecutionResult::from_cache(cached_value));
+            }
+            tracing::debug!("[AgentRunner] run, agent={}, model={}, cache_hit=false", self.agent, model.name);
+
+            match self.execute(&specification, &model) {
+                Ok(result) => {
+                    // Store result in cache (background operation) so returning doesn't block
+                    let cache_value = result.output.clone();
+                    let agent_name = self.agent.clone();
+                    let model_name = model.name.clone();
+                    let input_shape = self.input_shape();
+                    std::thread::spawn(move || {
+                        let _span = tracing::debug_span!("cache_store", agent = %agent_name, model = %model_name).entered();
+                        tracing::debug!("[AgentRunner] cache_store, agent={}, model={}, storing result in background", agent_name, model_name);
+                        cache.set_with_metadata(&cache_key, &cache_value, &agent_name, &model_name, input_shape);
+                    });
+
+                    return Ok(result);
+                }
+                Err(ExecutionError::ModelNotFound(_)) | Err(ExecutionError::ExecutionFailed(_)) => {
+                    tracing::warn!("[AgentRunner] run, agent={}, model={} failed, falling back to next candidate", self.agent, model.name);
+                    model = candidates.next().ok_or_else(|| {
+                        ExecutionError::ExecutionFailed(format!(
+                            "all candidate models failed for agent '{}'",
+                            self.agent
+                        ))
+                    })?;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
 
-        // Step 4: Get cache instance (folder based on hash(instructions + model))
+    /// Like `run`, but streams the Python runner's response: `on_delta` is invoked with each
+    /// `{"type":"delta","text":"..."}` chunk as it arrives instead of waiting for the whole
+    /// output to buffer, while the full text is still accumulated and cached exactly as a
+    /// non-streaming `run` would. A cache hit is served directly with no deltas emitted, since
+    /// there is no streaming left to do.
+    pub fn run_streaming(self, mut on_delta: impl FnMut(&str)) -> Result<ExecutionResult, AgentRunnerError> {
+        let agent_template = self.agent_registry.get_specification(&self.agent)?;
+
+        let model = self
+            .agent_model_registry
+            .get_model(&self.agent)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| ExecutionError::ModelNotFound(self.agent.clone()))?;
+        let cache_key = self.generate_cache_key(&agent_template);
         let cache = self.get_cached_artefact(&agent_template, &model.name)?;
 
-        // Step 5: Check cache for existing result
         if let Some(cached_value) = cache.get(&cache_key) {
-            // Cache hit - return immediately
-            return Ok(ExecutionResult {
-                output: cached_value,
-            });
+            return Ok(ExecutionResult::from_cache(cached_value));
+        }
+
+        let specification = self.populate()?;
+        let request_json = self.build_request_json(&specification, &model)?;
+        let result = self.run_python_runner_streaming(&request_json, &mut on_delta)?;
+
+        let cache_value = result.output.clone();
+        let agent_name = self.agent.clone();
+        let model_name = model.name.clone();
+        let input_shape = self.input_shape();
+        std::thread::spawn(move || {
+            cache.set_with_metadata(&cache_key, &cache_value, &agent_name, &model_name, input_shape);
+        });
+
+        Ok(result)
+    }
+
+    /// Like `run`, but persists conversation history across invocations via a `Session` keyed by
+    /// `session_id`: prior turns are loaded and sent to the Python runner as a `messages` array
+    /// alongside this turn's populated specification, and the new user/assistant turn is appended
+    /// back to the session once execution completes. The cache key folds in the session id and
+    /// history length, so a resumed conversation's turns never collide with a fresh run or with
+    /// each other.
+    pub fn run_in_session(self, session_id: &str) -> Result<ExecutionResult, AgentRunnerError> {
+        let agent_template = self.agent_registry.get_specification(&self.agent)?;
+
+        let model = self
+            .agent_model_registry
+            .get_model(&self.agent)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| ExecutionError::ModelNotFound(self.agent.clone()))?;
+
+        let session = Session::new(session_id);
+        let history = session.load();
+
+        let cache_key = self.generate_session_cache_key(&agent_template, session_id, history.len());
+        let cache = self.get_cached_artefact(&agent_template, &model.name)?;
+
+        if let Some(cached_value) = cache.get(&cache_key) {
+            return Ok(ExecutionResult::from_cache(cached_value));
         }
 
-        // Cache miss - proceed with execution
-        // Step 6: Populate the specification (replace placeholders in template)
         let specification = self.populate()?;
+        let request_json = self.build_session_request_json(&specification, &model, &history)?;
+        let result = self.run_python_runner(&request_json)?;
 
-        // Step 7: Execute the agent
-        let result = self.execute(&specification, &model)?;
+        let mut updated_history = history;
+        updated_history.push(SessionMessage {
+            role: "user".to_string(),
+            content: specification.system_prompt.clone(),
+        });
+        updated_history.push(SessionMessage {
+            role: "assistant".to_string(),
+            content: result.output.clone(),
+        });
+        session.save(&updated_history);
 
-        // Step 8: Store result in cache (background operation)
-        // Note: In a real implementation, this would be done in a background thread
-        // to ensure it doesn't block returning the result
         let cache_value = result.output.clone();
+        let agent_name = self.agent.clone();
+        let model_name = model.name.clone();
+        let input_shape = self.input_shape();
         std::thread::spawn(move || {
-            cache.set(&cache_key, &cache_value);
+            cache.set_with_metadata(&cache_key, &cache_value, &agent_name, &model_name, input_shape);
         });
 
         Ok(result)
     }
+
+    /// Collects the top-level keys of the serialized input, recorded in the cache manifest so
+    /// stale entries from a since-renamed input field can be spotted without re-deriving them
+    /// from the original draft/context files.
+    fn input_shape(&self) -> Vec<String> {
+        match serde_json::to_value(&self.input) {
+            Ok(serde_json::Value::Object(map)) => {
+                let mut keys: Vec<String> = map.keys().cloned().collect();
+                keys.sort();
+                keys
+            }
+            _ => Vec::new(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use serde::Serialize;
+    use std::fs;
 
     #[derive(Serialize)]
     struct TestInput {
@@ -478,10 +1317,8 @@ mod tests {
     struct TestModelRegistry;
 
     impl AgentModelRegistry for TestModelRegistry {
-        fn get_model(&self, _agent_name: &str) -> Result<Model, ExecutionError> {
-            Ok(Model {
-                name: "test-model".to_string(),
-            })
+        fn get_model(&self, _agent_name: &str) -> Result<Vec<Model>, ExecutionError> {
+            Ok(vec![Model::new("test-model")])
         }
     }
 
@@ -524,6 +1361,33 @@ mod tests {
         assert_ne!(key1, key3);
     }
 
+    #[test]
+    fn test_run_reports_cache_hit_without_measuring_duration() {
+        let input = TestInput {
+            name: "cache_hit_test".to_string(),
+            value: 7,
+        };
+        let runner = AgentRunner::new(
+            "test_agent".to_string(),
+            input,
+            TestRegistry,
+            TestModelRegistry,
+        );
+
+        let agent_template = "Test specification for test_agent".to_string();
+        let instructions_model_hash = runner.generate_instructions_model_hash(&agent_template, "test-model");
+        let cache_key = runner.generate_cache_key(&agent_template);
+        let cache = runner.get_cached_artefact(&agent_template, "test-model").unwrap();
+        cache.set(&cache_key, "cached output");
+
+        let result = runner.run().unwrap();
+        assert_eq!(result.output, "cached output");
+        assert!(result.cache_hit);
+        assert!(result.duration.is_none());
+
+        let _ = fs::remove_dir_all(format!(".reen/{}", instructions_model_hash));
+    }
+
     #[test]
     fn test_agent_runner_execution() {
         let input = TestInput {
@@ -673,4 +1537,262 @@ mod tests {
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), "Frank lives in Paris, France");
     }
+
+    #[derive(Serialize)]
+    struct ListTestInput {
+        flag: bool,
+        items: Vec<String>,
+    }
+
+    #[test]
+    fn test_placeholder_if_else_and_truthiness() {
+        let input = ListTestInput {
+            flag: true,
+            items: Vec::new(),
+        };
+        let runner = AgentRunner::new(
+            "test_agent".to_string(),
+            input,
+            TestRegistry,
+            TestModelRegistry,
+        );
+
+        let template = "{{#if input.flag}}yes{{else}}no{{/if}} / {{#if input.items}}has items{{else}}empty{{/if}}";
+        let result = runner.replace_placeholders(template);
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), "yes / empty");
+    }
+
+    #[test]
+    fn test_placeholder_each_with_this_and_index() {
+        let input = ListTestInput {
+            flag: false,
+            items: vec!["a".to_string(), "b".to_string()],
+        };
+        let runner = AgentRunner::new(
+            "test_agent".to_string(),
+            input,
+            TestRegistry,
+            TestModelRegistry,
+        );
+
+        let template = "{{#each input.items}}{{@index}}:{{this}} {{/each}}";
+        let result = runner.replace_placeholders(template);
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), "0:a 1:b ");
+    }
+
+    #[test]
+    fn test_placeholder_each_over_non_array_errors() {
+        let input = ListTestInput {
+            flag: true,
+            items: Vec::new(),
+        };
+        let runner = AgentRunner::new(
+            "test_agent".to_string(),
+            input,
+            TestRegistry,
+            TestModelRegistry,
+        );
+
+        let template = "{{#each input.flag}}{{this}}{{/each}}";
+        let result = runner.replace_placeholders(template);
+
+        match result {
+            Err(PopulateError::EachTargetNotArray(path)) => assert_eq!(path, "input.flag"),
+            other => panic!("expected EachTargetNotArray, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_placeholder_array_indexing() {
+        let input = ListTestInput {
+            flag: false,
+            items: vec!["first".to_string(), "second".to_string()],
+        };
+        let runner = AgentRunner::new(
+            "test_agent".to_string(),
+            input,
+            TestRegistry,
+            TestModelRegistry,
+        );
+
+        let template = "{{input.items.1}}";
+        let result = runner.replace_placeholders(template);
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), "second");
+    }
+
+    struct TestVariableRegistry;
+
+    impl VariableRegistry for TestVariableRegistry {
+        fn variables(&self, _agent_name: &str) -> Result<Vec<VariableDefinition>, PopulateError> {
+            Ok(vec![
+                VariableDefinition {
+                    name: "tone".to_string(),
+                    description: "Desired writing tone".to_string(),
+                    default: Some("neutral".to_string()),
+                    required: false,
+                    choices: Some(vec!["neutral".to_string(), "formal".to_string()]),
+                },
+                VariableDefinition {
+                    name: "topic".to_string(),
+                    description: "Subject to write about".to_string(),
+                    default: None,
+                    required: true,
+                    choices: None,
+                },
+            ])
+        }
+    }
+
+    #[test]
+    fn test_variable_replacement_from_supplied_values() {
+        let input = TestInput {
+            name: "Grace".to_string(),
+            value: 1,
+        };
+        let mut values = HashMap::new();
+        values.insert("topic".to_string(), "space travel".to_string());
+
+        let runner = AgentRunner::new("test_agent".to_string(), input, TestRegistry, TestModelRegistry)
+            .with_variable_registry(Box::new(TestVariableRegistry))
+            .with_variable_values(values);
+
+        let template = "Write about {{var.topic}} in a {{var.tone}} tone";
+        let result = runner.replace_placeholders(template);
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), "Write about space travel in a neutral tone");
+    }
+
+    #[test]
+    fn test_variable_replacement_missing_required_surfaces_prompt_metadata() {
+        let input = TestInput {
+            name: "Heidi".to_string(),
+            value: 2,
+        };
+
+        let runner = AgentRunner::new("test_agent".to_string(), input, TestRegistry, TestModelRegistry)
+            .with_variable_registry(Box::new(TestVariableRegistry));
+
+        let template = "Write about {{var.topic}}";
+        let result = runner.replace_placeholders(template);
+
+        assert!(result.is_err());
+        match result {
+            Err(PopulateError::MissingVariable(info)) => {
+                assert_eq!(info.name, "topic");
+                assert_eq!(info.description, "Subject to write about");
+                assert!(info.default.is_none());
+            }
+            _ => panic!("Expected MissingVariable error"),
+        }
+    }
+
+    #[test]
+    fn test_variable_replacement_unknown_variable_is_optional_empty() {
+        let input = TestInput {
+            name: "Ivan".to_string(),
+            value: 3,
+        };
+
+        let runner = AgentRunner::new("test_agent".to_string(), input, TestRegistry, TestModelRegistry)
+            .with_variable_registry(Box::new(TestVariableRegistry));
+
+        let template = "Extra: {{var.unknown?}}";
+        let result = runner.replace_placeholders(template);
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), "Extra: ");
+    }
+
+    #[test]
+    fn test_cache_key_differs_by_variable_values() {
+        let input = TestInput {
+            name: "test".to_string(),
+            value: 42,
+        };
+        let runner = AgentRunner::new("test_agent".to_string(), input, TestRegistry, TestModelRegistry);
+
+        let key_no_vars = runner.generate_cache_key("model1");
+
+        let mut values = HashMap::new();
+        values.insert("tone".to_string(), "formal".to_string());
+        let input2 = TestInput {
+            name: "test".to_string(),
+            value: 42,
+        };
+        let runner_with_vars = AgentRunner::new("test_agent".to_string(), input2, TestRegistry, TestModelRegistry)
+            .with_variable_values(values);
+        let key_with_vars = runner_with_vars.generate_cache_key("model1");
+
+        assert_ne!(key_no_vars, key_with_vars);
+    }
+
+    struct TestFunctionRegistry;
+
+    impl FunctionRegistry for TestFunctionRegistry {
+        fn list_declarations(&self) -> Vec<FunctionDeclaration> {
+            vec![FunctionDeclaration {
+                name: "add".to_string(),
+                description: "Adds two integers".to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "a": {"type": "integer"},
+                        "b": {"type": "integer"},
+                    },
+                    "required": ["a", "b"],
+                }),
+            }]
+        }
+
+        fn call(&self, name: &str, args: serde_json::Value) -> Result<serde_json::Value, ExecutionError> {
+            match name {
+                "add" => {
+                    let a = args["a"].as_i64().unwrap_or(0);
+                    let b = args["b"].as_i64().unwrap_or(0);
+                    Ok(serde_json::json!({ "result": a + b }))
+                }
+                other => Err(ExecutionError::ExecutionFailed(format!("unknown function '{}'", other))),
+            }
+        }
+    }
+
+    #[test]
+    fn test_function_registry_declarations_and_dispatch() {
+        let registry = TestFunctionRegistry;
+
+        let declarations = registry.list_declarations();
+        assert_eq!(declarations.len(), 1);
+        assert_eq!(declarations[0].name, "add");
+
+        let result = registry.call("add", serde_json::json!({"a": 2, "b": 3})).unwrap();
+        assert_eq!(result["result"], 5);
+    }
+
+    #[test]
+    fn test_function_registry_unknown_call_is_execution_failed() {
+        let registry = TestFunctionRegistry;
+        let err = registry.call("subtract", serde_json::json!({})).unwrap_err();
+        assert!(matches!(err, ExecutionError::ExecutionFailed(_)));
+    }
+
+    #[test]
+    fn test_with_function_registry_and_max_tool_iterations_are_applied() {
+        let input = TestInput {
+            name: "test".to_string(),
+            value: 42,
+        };
+        let runner = AgentRunner::new("test_agent".to_string(), input, TestRegistry, TestModelRegistry)
+            .with_function_registry(Box::new(TestFunctionRegistry))
+            .with_max_tool_iterations(3);
+
+        assert_eq!(runner.max_tool_iterations, 3);
+        assert!(runner.function_registry.is_some());
+    }
 }
@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the environment variable that switches cassette mode on.
+pub const REEN_CASSETTE_ENV: &str = "REEN_CASSETTE";
+
+/// Errors that can occur while recording to or replaying from a cassette.
+#[derive(Debug)]
+pub enum CassetteError {
+    Miss(String),
+    Io(String),
+}
+
+impl fmt::Display for CassetteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CassetteError::Miss(key) => {
+                write!(f, "no recorded response for request {} (replay mode allows no network access)", key)
+            }
+            CassetteError::Io(details) => write!(f, "failed to access cassette file: {}", details),
+        }
+    }
+}
+
+impl std::error::Error for CassetteError {}
+
+/// Which side of record/replay is active for this run, read from `REEN_CASSETTE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Execute normally, then append the request/response pair to the cassette file.
+    Record,
+    /// Serve only from the cassette file; a cache miss is an error, never a live call.
+    Replay,
+}
+
+impl CassetteMode {
+    /// Reads the active mode from `REEN_CASSETTE` ("record" or "replay"). Any other value,
+    /// including unset, means cassettes are not in play and requests should go out live.
+    pub fn from_env() -> Option<Self> {
+        match std::env::var(REEN_CASSETTE_ENV).ok()?.as_str() {
+            "record" => Some(CassetteMode::Record),
+            "replay" => Some(CassetteMode::Replay),
+            _ => None,
+        }
+    }
+}
+
+/// One recorded request/response pair. `request` is kept alongside the response purely for
+/// human inspection of a committed cassette file; lookups are by `key` alone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CassetteEntry {
+    key: String,
+    request: String,
+    response: String,
+}
+
+/// A flat-file store of recorded prompt/response pairs for one agent, letting the e2e workflow
+/// run offline against committed fixtures instead of a live API.
+pub struct Cassette {
+    path: PathBuf,
+    entries: HashMap<String, (String, String)>,
+}
+
+impl Cassette {
+    /// Hashes `request` into the key a recorded entry is looked up by, so a byte-for-byte
+    /// identical request always lands on the same fixture regardless of the order it was
+    /// originally recorded in.
+    pub fn key_for(request: &str) -> String {
+        hex::encode(Sha256::digest(request.as_bytes()))
+    }
+
+    /// Loads the cassette file at `path`. A missing file behaves as an empty cassette, so the
+    /// first `record` run can create it from scratch.
+    pub fn load(path: PathBuf) -> Result<Self, CassetteError> {
+        let entries = match fs::read_to_string(&path) {
+            Ok(raw) => {
+                let recorded: Vec<CassetteEntry> = serde_json::from_str(&raw)
+                    .map_err(|e| CassetteError::Io(format!("{:?} is not a valid cassette: {}", path, e)))?;
+                recorded.into_iter().map(|e| (e.key, (e.request, e.response))).collect()
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(CassetteError::Io(format!("failed to read {:?}: {}", path, e))),
+        };
+
+        Ok(Self { path, entries })
+    }
+
+    /// Looks up the response recorded for `key`.
+    pub fn get(&self, key: &str) -> Result<&str, CassetteError> {
+        self.entries
+            .get(key)
+            .map(|(_, response)| response.as_str())
+            .ok_or_else(|| CassetteError::Miss(key.to_string()))
+    }
+
+    /// Records a request/response pair and persists the cassette immediately, so a crash partway
+    /// through a long recording session doesn't lose entries already captured.
+    pub fn record(&mut self, request: &str, response: &str) -> Result<(), CassetteError> {
+        let key = Self::key_for(request);
+        self.entries.insert(key, (request.to_string(), response.to_string()));
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), CassetteError> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| CassetteError::Io(format!("failed to create {:?}: {}", dir, e)))?;
+        }
+
+        let mut recorded: Vec<CassetteEntry> = self
+            .entries
+            .iter()
+            .map(|(key, (request, response))| CassetteEntry {
+                key: key.clone(),
+                request: request.clone(),
+                response: response.clone(),
+            })
+            .collect();
+        recorded.sort_by(|a, b| a.key.cmp(&b.key));
+
+        let json = serde_json::to_string_pretty(&recorded)
+            .map_err(|e| CassetteError::Io(format!("failed to serialize cassette: {}", e)))?;
+
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, json)
+            .map_err(|e| CassetteError::Io(format!("failed to write {:?}: {}", tmp_path, e)))?;
+        fs::rename(&tmp_path, &self.path)
+            .map_err(|e| CassetteError::Io(format!("failed to finalize {:?}: {}", self.path, e)))
+    }
+}
+
+/// Default location of an agent's cassette file, relative to the current directory: the e2e
+/// workflow runs `reen` with its cwd set to the target project, so committed fixtures live
+/// alongside it rather than inside the `reen` checkout itself.
+pub fn cassette_path(agent_name: &str) -> PathBuf {
+    Path::new("cassettes").join(format!("{}.json", agent_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cassette_mode_from_env() {
+        std::env::remove_var(REEN_CASSETTE_ENV);
+        assert_eq!(CassetteMode::from_env(), None);
+
+        std::env::set_var(REEN_CASSETTE_ENV, "record");
+        assert_eq!(CassetteMode::from_env(), Some(CassetteMode::Record));
+
+        std::env::set_var(REEN_CASSETTE_ENV, "replay");
+        assert_eq!(CassetteMode::from_env(), Some(CassetteMode::Replay));
+
+        std::env::set_var(REEN_CASSETTE_ENV, "bogus");
+        assert_eq!(CassetteMode::from_env(), None);
+
+        std::env::remove_var(REEN_CASSETTE_ENV);
+    }
+
+    #[test]
+    fn test_record_then_replay_roundtrips() {
+        let path = PathBuf::from(format!("/tmp/reen_test_cassette_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut cassette = Cassette::load(path.clone()).expect("load empty cassette");
+        cassette.record("request-a", "response-a").expect("record");
+
+        let reloaded = Cassette::load(path.clone()).expect("reload cassette");
+        let key = Cassette::key_for("request-a");
+        assert_eq!(reloaded.get(&key).unwrap(), "response-a");
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_replay_miss_is_an_error() {
+        let path = PathBuf::from(format!("/tmp/reen_test_cassette_miss_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let cassette = Cassette::load(path.clone()).expect("load empty cassette");
+        let key = Cassette::key_for("never-recorded");
+        assert!(cassette.get(&key).is_err());
+
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -1,6 +1,110 @@
 use crate::data::Cache;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Marker written at the start of every `.cache` file ahead of its content hash and length,
+/// so a file from before this header existed is treated as corrupt (safe default) rather than
+/// misread as valid content.
+const ENTRY_HEADER_MAGIC: &str = "reen-cache-v1";
+
+/// The result of looking up a cache entry, distinguishing a genuine miss (nothing stored for
+/// this key) from detected corruption (something was stored, but its header, length, or hash
+/// doesn't match its content) rather than collapsing both into `None` as plain reads would.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheLookup {
+    Hit(String),
+    Miss,
+    Corrupt,
+}
+
+/// Prefixes `value` with a `{MAGIC} {sha256} {byte_len}\n` header so [`decode_entry`] can
+/// verify an entry's integrity from the file alone, without consulting the manifest.
+fn encode_entry(value: &str) -> String {
+    let hash = hex::encode(Sha256::digest(value.as_bytes()));
+    format!("{} {} {}\n{}", ENTRY_HEADER_MAGIC, hash, value.len(), value)
+}
+
+/// Parses a header written by [`encode_entry`] and verifies its recorded hash and length
+/// against the body that follows, returning [`CacheLookup::Corrupt`] on any mismatch
+/// (missing header line, wrong magic, unparsable length, length mismatch, or hash mismatch).
+fn decode_entry(raw: &str) -> CacheLookup {
+    let Some((header, body)) = raw.split_once('\n') else {
+        return CacheLookup::Corrupt;
+    };
+    let mut fields = header.split(' ');
+    let (Some(magic), Some(hash), Some(len)) = (fields.next(), fields.next(), fields.next())
+    else {
+        return CacheLookup::Corrupt;
+    };
+    if magic != ENTRY_HEADER_MAGIC {
+        return CacheLookup::Corrupt;
+    }
+    let Ok(expected_len) = len.parse::<usize>() else {
+        return CacheLookup::Corrupt;
+    };
+    if body.len() != expected_len || hex::encode(Sha256::digest(body.as_bytes())) != hash {
+        return CacheLookup::Corrupt;
+    }
+    CacheLookup::Hit(body.to_string())
+}
+
+/// One manifest entry describing a single `.cache` file, so stale entries and corrupted
+/// content can be identified without re-deriving them from the original draft/context
+/// inputs that produced them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheManifestEntry {
+    pub agent_name: String,
+    pub model_name: String,
+    pub input_shape: Vec<String>,
+    pub content_sha256: String,
+    pub timestamp: String,
+}
+
+/// Per-directory index of cache files, persisted as `manifest.json` alongside the `.cache`
+/// files it describes. Keyed by cache key (the filename without its `.cache` extension).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CacheManifest {
+    #[serde(default)]
+    pub entries: HashMap<String, CacheManifestEntry>,
+}
+
+impl CacheManifest {
+    fn load(dir: &Path) -> Self {
+        fs::read_to_string(dir.join(MANIFEST_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, dir: &Path) -> std::io::Result<()> {
+        let content = serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string());
+        let tmp_path = dir.join(format!("{}.tmp", MANIFEST_FILE));
+        fs::write(&tmp_path, content)?;
+        fs::rename(&tmp_path, dir.join(MANIFEST_FILE))
+    }
+}
+
+/// Removes a single entry from `dir`'s manifest (if present) and rewrites it atomically.
+/// Called after a `.cache` file has been unlinked directly (e.g. by a targeted clear) so the
+/// manifest doesn't keep describing a file that no longer exists.
+pub fn remove_manifest_entry(dir: &Path, key: &str) {
+    let mut manifest = CacheManifest::load(dir);
+    if manifest.entries.remove(key).is_some() {
+        if let Err(e) = manifest.save(dir) {
+            eprintln!(
+                "Failed to update cache manifest {:?}: {}",
+                dir.join(MANIFEST_FILE),
+                e
+            );
+        }
+    }
+}
 
 /// FileCache is an implementation of the Cache trait that stores cache artifacts
 /// in a file structure. Keys are used to derive both the file name and folder structure.
@@ -45,6 +149,89 @@ impl FileCache {
         path.push(&self.instructions_model_hash);
         path
     }
+
+    /// Re-hashes `contents` against the manifest's recorded sha256 for `key`, catching
+    /// tampering the per-entry header alone wouldn't (a rewritten file with a matching,
+    /// recomputed header). Entries with no manifest record (written before the manifest
+    /// existed, or via the bare `set` that doesn't carry agent metadata) are treated as
+    /// trusted for this check, since the header already covers basic corruption for them.
+    fn verify_integrity(&self, key: &str, contents: &str) -> bool {
+        match CacheManifest::load(&self.get_cache_dir()).entries.get(key) {
+            Some(entry) => entry.content_sha256 == hex::encode(Sha256::digest(contents.as_bytes())),
+            None => true,
+        }
+    }
+
+    /// Looks up `key`, distinguishing a genuine miss from detected corruption. A corrupt
+    /// entry is logged and its file removed so the next run regenerates it cleanly instead of
+    /// being stuck returning `Corrupt` forever.
+    pub fn get_checked(&self, key: &str) -> CacheLookup {
+        let path = self.get_cache_path(key);
+
+        let raw = match fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(_) => return CacheLookup::Miss,
+        };
+
+        let body = match decode_entry(&raw) {
+            CacheLookup::Hit(body) => body,
+            CacheLookup::Corrupt => {
+                self.quarantine(&path, "entry header, length, or hash didn't match its content");
+                return CacheLookup::Corrupt;
+            }
+            CacheLookup::Miss => unreachable!("decode_entry never returns Miss"),
+        };
+
+        if !self.verify_integrity(key, &body) {
+            self.quarantine(&path, "content didn't match the manifest's recorded hash");
+            return CacheLookup::Corrupt;
+        }
+
+        CacheLookup::Hit(body)
+    }
+
+    /// Logs a corrupted cache file and removes it so a future `get` sees a clean miss instead
+    /// of repeatedly re-detecting the same corruption.
+    fn quarantine(&self, path: &Path, reason: &str) {
+        eprintln!("Cache entry {:?} is corrupted ({}); removing it", path, reason);
+        if let Err(e) = fs::remove_file(path) {
+            eprintln!("Failed to remove corrupted cache file {:?}: {}", path, e);
+        }
+    }
+
+    /// Stores a value in the cache alongside a manifest entry recording `agent_name`,
+    /// `model_name`, and the top-level `input_shape` keys, so stale or corrupted entries can
+    /// later be identified without re-deriving them from the original draft/context inputs.
+    pub fn set_with_metadata(
+        &self,
+        key: &str,
+        value: &str,
+        agent_name: &str,
+        model_name: &str,
+        input_shape: Vec<String>,
+    ) {
+        self.set(key, value);
+
+        let dir = self.get_cache_dir();
+        let mut manifest = CacheManifest::load(&dir);
+        manifest.entries.insert(
+            key.to_string(),
+            CacheManifestEntry {
+                agent_name: agent_name.to_string(),
+                model_name: model_name.to_string(),
+                input_shape,
+                content_sha256: hex::encode(Sha256::digest(value.as_bytes())),
+                timestamp: Utc::now().to_rfc3339(),
+            },
+        );
+        if let Err(e) = manifest.save(&dir) {
+            eprintln!(
+                "Failed to update cache manifest {:?}: {}",
+                dir.join(MANIFEST_FILE),
+                e
+            );
+        }
+    }
 }
 
 impl Cache for FileCache {
@@ -57,21 +244,17 @@ impl Cache for FileCache {
     /// * `Some(String)` - The cached value if found and readable
     /// * `None` - If the cache file doesn't exist or cannot be read
     fn get(&self, key: &str) -> Option<String> {
-        let path = self.get_cache_path(key);
-
-        match fs::read_to_string(&path) {
-            Ok(contents) => Some(contents),
-            Err(_) => {
-                // File not found or read error - treat as cache miss
-                None
-            }
+        match self.get_checked(key) {
+            CacheLookup::Hit(value) => Some(value),
+            CacheLookup::Miss | CacheLookup::Corrupt => None,
         }
     }
 
     /// Stores a value in the cache for the given key
     ///
     /// Creates necessary directories if they don't exist. Errors are handled
-    /// gracefully without panicking.
+    /// gracefully without panicking. Written atomically (temp file + rename) so a crash
+    /// mid-write never leaves a partially-written `.cache` file behind.
     ///
     /// # Arguments
     /// * `key` - The cache key to store under
@@ -86,9 +269,13 @@ impl Cache for FileCache {
             return;
         }
 
-        // Write the cache file
-        if let Err(e) = fs::write(&path, value) {
-            eprintln!("Failed to write cache file {:?}: {}", path, e);
+        let tmp_path = dir.join(format!("{}.cache.tmp", key));
+        if let Err(e) = fs::write(&tmp_path, encode_entry(value)) {
+            eprintln!("Failed to write cache file {:?}: {}", tmp_path, e);
+            return;
+        }
+        if let Err(e) = fs::rename(&tmp_path, &path) {
+            eprintln!("Failed to finalize cache file {:?}: {}", path, e);
         }
     }
 }
@@ -131,4 +318,96 @@ mod tests {
         let cache = FileCache::new(None, "test_hash".to_string());
         assert_eq!(cache.folder, ".reen");
     }
+
+    #[test]
+    fn test_set_with_metadata_roundtrips_and_records_manifest() {
+        let test_dir = format!("/tmp/reen_test_manifest_{}", std::process::id());
+        let cache = FileCache::new(Some(test_dir.clone()), "test_hash".to_string());
+
+        cache.set_with_metadata(
+            "test_key",
+            "test_value",
+            "create_implementation",
+            "test-model",
+            vec!["context_content".to_string()],
+        );
+
+        assert_eq!(cache.get("test_key"), Some("test_value".to_string()));
+
+        let manifest = CacheManifest::load(&cache.get_cache_dir());
+        let entry = manifest.entries.get("test_key").expect("manifest entry");
+        assert_eq!(entry.agent_name, "create_implementation");
+        assert_eq!(entry.model_name, "test-model");
+
+        let _ = fs::remove_dir_all(&test_dir);
+    }
+
+    #[test]
+    fn test_corrupted_cache_file_is_treated_as_miss() {
+        let test_dir = format!("/tmp/reen_test_corrupt_{}", std::process::id());
+        let cache = FileCache::new(Some(test_dir.clone()), "test_hash".to_string());
+
+        cache.set_with_metadata(
+            "test_key",
+            "original_value",
+            "create_test",
+            "test-model",
+            vec![],
+        );
+        fs::write(cache.get_cache_path("test_key"), "tampered_value").unwrap();
+
+        assert_eq!(cache.get("test_key"), None);
+
+        let _ = fs::remove_dir_all(&test_dir);
+    }
+
+    #[test]
+    fn test_get_checked_distinguishes_miss_from_corruption() {
+        let test_dir = format!("/tmp/reen_test_checked_{}", std::process::id());
+        let cache = FileCache::new(Some(test_dir.clone()), "test_hash".to_string());
+
+        assert_eq!(cache.get_checked("nonexistent"), CacheLookup::Miss);
+
+        cache.set("test_key", "test_value");
+        assert_eq!(
+            cache.get_checked("test_key"),
+            CacheLookup::Hit("test_value".to_string())
+        );
+
+        fs::write(cache.get_cache_path("test_key"), "not a valid cache entry").unwrap();
+        assert_eq!(cache.get_checked("test_key"), CacheLookup::Corrupt);
+
+        // Corruption is quarantined: a second lookup sees a clean miss, not repeat corruption.
+        assert_eq!(cache.get_checked("test_key"), CacheLookup::Miss);
+
+        let _ = fs::remove_dir_all(&test_dir);
+    }
+
+    #[test]
+    fn test_set_is_atomic_and_leaves_no_temp_file() {
+        let test_dir = format!("/tmp/reen_test_atomic_{}", std::process::id());
+        let cache = FileCache::new(Some(test_dir.clone()), "test_hash".to_string());
+
+        cache.set("test_key", "test_value");
+
+        let tmp_path = cache.get_cache_dir().join("test_key.cache.tmp");
+        assert!(!tmp_path.exists());
+        assert_eq!(cache.get("test_key"), Some("test_value".to_string()));
+
+        let _ = fs::remove_dir_all(&test_dir);
+    }
+
+    #[test]
+    fn test_remove_manifest_entry() {
+        let test_dir = format!("/tmp/reen_test_remove_{}", std::process::id());
+        let cache = FileCache::new(Some(test_dir.clone()), "test_hash".to_string());
+        cache.set_with_metadata("test_key", "value", "create_test", "test-model", vec![]);
+
+        remove_manifest_entry(&cache.get_cache_dir(), "test_key");
+
+        let manifest = CacheManifest::load(&cache.get_cache_dir());
+        assert!(manifest.entries.get("test_key").is_none());
+
+        let _ = fs::remove_dir_all(&test_dir);
+    }
 }
@@ -1,8 +1,13 @@
 mod agent_runner;
+mod cassette;
 mod file_cache;
+mod session;
 
 pub use agent_runner::{
     AgentModelRegistry, AgentRegistry, AgentRunner, AgentRunnerError, AgentSpecification,
-    ExecutionError, ExecutionResult, Model, PopulateError,
+    ExecutionError, ExecutionResult, FunctionDeclaration, FunctionRegistry, MissingVariableInfo,
+    Model, PopulateError, VariableDefinition, VariableRegistry,
 };
-pub use file_cache::FileCache;
+pub use cassette::{cassette_path, Cassette, CassetteError, CassetteMode, REEN_CASSETTE_ENV};
+pub use file_cache::{remove_manifest_entry, CacheManifest, CacheManifestEntry, FileCache};
+pub use session::{Session, SessionMessage};
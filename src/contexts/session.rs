@@ -0,0 +1,93 @@
+use crate::contexts::FileCache;
+use crate::data::Cache;
+use serde::{Deserialize, Serialize};
+
+/// Key a session's history is stored under within its own cache subfolder.
+const HISTORY_KEY: &str = "history";
+
+/// One turn of a session's conversation history, sent to the Python runner as part of the
+/// `messages` array in `AgentRunner::run_in_session` and persisted across invocations.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionMessage {
+    pub role: String,
+    pub content: String,
+}
+
+/// Persists a session's ordered conversation history through the same `Cache`/`FileCache`
+/// mechanism agent output is cached through: each session id gets its own subfolder (one file
+/// per session), holding its full message list as a single JSON-encoded cache entry.
+pub struct Session {
+    cache: FileCache,
+}
+
+impl Session {
+    /// Opens the session identified by `session_id`, rooted at the same `.reen` cache folder
+    /// agent output uses. The session need not already exist; `load` simply returns an empty
+    /// history until the first `save`.
+    pub fn new(session_id: &str) -> Self {
+        Self {
+            cache: FileCache::new(None, format!("sessions/{}", session_id)),
+        }
+    }
+
+    /// Loads this session's message history, or an empty history if nothing has been saved yet
+    /// (including when the saved entry is corrupt, handled the same as a miss per `Cache`'s
+    /// contract).
+    pub fn load(&self) -> Vec<SessionMessage> {
+        self.cache
+            .get(HISTORY_KEY)
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default()
+    }
+
+    /// Persists `messages` as this session's full history, overwriting whatever was saved
+    /// before.
+    pub fn save(&self, messages: &[SessionMessage]) {
+        if let Ok(raw) = serde_json::to_string(messages) {
+            self.cache.set(HISTORY_KEY, &raw);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn unique_session_id(suffix: &str) -> String {
+        format!("reen_test_session_{}_{}", std::process::id(), suffix)
+    }
+
+    #[test]
+    fn test_new_session_loads_empty_history() {
+        let id = unique_session_id("empty");
+        let session = Session::new(&id);
+        assert!(session.load().is_empty());
+        let _ = fs::remove_dir_all(format!(".reen/sessions/{}", id));
+    }
+
+    #[test]
+    fn test_save_then_load_roundtrips_history() {
+        let id = unique_session_id("roundtrip");
+        let session = Session::new(&id);
+        let messages = vec![
+            SessionMessage {
+                role: "user".to_string(),
+                content: "hello".to_string(),
+            },
+            SessionMessage {
+                role: "assistant".to_string(),
+                content: "hi there".to_string(),
+            },
+        ];
+
+        session.save(&messages);
+        let reloaded = session.load();
+
+        assert_eq!(reloaded.len(), 2);
+        assert_eq!(reloaded[0].role, "user");
+        assert_eq!(reloaded[1].content, "hi there");
+
+        let _ = fs::remove_dir_all(format!(".reen/sessions/{}", id));
+    }
+}
@@ -19,6 +19,51 @@ struct Cli {
         help = "Perform a dry run without executing actions"
     )]
     dry_run: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Use `cargo check` instead of `cargo build` for diagnostic-gathering iterations in the fix loop, only falling back to a full build to confirm codegen once checks pass"
+    )]
+    fast_check: bool,
+
+    #[arg(
+        short = 'p',
+        long,
+        global = true,
+        help = "Target a specific workspace member package for compile/run/test/fix (passed through as `cargo -p <name>`)"
+    )]
+    package: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = cli::DiagnosticFormat::Fancy,
+        help = "Diagnostic rendering for layout validation failures (fancy framed snippets, or plain file:line:col lines for CI)"
+    )]
+    format: cli::DiagnosticFormat,
+
+    #[arg(
+        long = "cfg",
+        global = true,
+        help = "Active cfg predicate (key=\"value\", e.g. feature=\"account\") for `# cfg(...)`-gated drafts/specifications; repeatable"
+    )]
+    cfg: Vec<String>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Force a full rebuild, ignoring the build tracker's content-hash cache"
+    )]
+    no_cache: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Print the reason each file was rebuilt instead of served from the build tracker's cache"
+    )]
+    explain_cache: bool,
 }
 
 #[derive(Subcommand)]
@@ -43,20 +88,83 @@ enum Commands {
         max_compile_fix_attempts: u32,
     },
 
+    #[command(
+        about = "Open a transactional checkpoint over the .reen cache and generated artifacts"
+    )]
+    Checkpoint,
+
+    #[command(about = "Commit the innermost open checkpoint, keeping changes made since it opened")]
+    Commit,
+
+    #[command(
+        about = "Roll back the innermost open checkpoint, restoring every file it touched"
+    )]
+    Rollback,
+
     #[command(about = "Compile the generated project using cargo build")]
-    Compile,
+    Compile {
+        #[arg(
+            long,
+            help = "Use `cargo check` instead of a full `cargo build` for a faster type-check-only pass"
+        )]
+        check: bool,
+
+        #[arg(long, help = "Build with the release profile instead of dev")]
+        release: bool,
+    },
 
     #[command(about = "Build and run the application using cargo run")]
     Run {
+        #[arg(long, help = "Run with the release profile instead of dev")]
+        release: bool,
+
         #[arg(help = "Arguments to pass to the application", trailing_var_arg = true)]
         args: Vec<String>,
     },
 
     #[command(about = "Test the project using cargo test")]
-    Test,
+    Test {
+        #[arg(long, help = "Run tests with the release profile instead of dev")]
+        release: bool,
+
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = cli::TestRunner::Auto,
+            help = "Test runner: auto-detect cargo-nextest, or force `cargo`/`nextest`"
+        )]
+        runner: cli::TestRunner,
+
+        #[arg(
+            long,
+            help = "nextest --status-level (e.g. pass, fail, all); ignored unless using nextest"
+        )]
+        status_level: Option<String>,
+
+        #[arg(
+            help = "nextest filter expression (e.g. 'test(my_test)'); ignored unless using nextest"
+        )]
+        filter: Option<String>,
+    },
+
+    #[command(
+        about = "Watch drafts/ and specifications/ and incrementally re-run the pipeline on change"
+    )]
+    Watch,
 
     #[command(subcommand, about = "Clear cache entries or generated artifacts")]
     Clear(ClearCommands),
+
+    #[command(subcommand, about = "Manage the agent response cache")]
+    Cache(CacheCommands),
+}
+
+#[derive(Subcommand)]
+enum CacheCommands {
+    #[command(
+        about = "Remove agent response cache entries no longer referenced by any current draft/specification"
+    )]
+    Gc,
 }
 
 #[derive(Subcommand)]
@@ -120,6 +228,18 @@ enum CreateCommands {
         alias = "specifications"
     )]
     Specification {
+        #[arg(
+            long,
+            help = "Print the execution plan as JSON and exit without running any agent"
+        )]
+        build_plan: bool,
+
+        #[arg(
+            long,
+            help = "Bound the dependency context to N hops upstream (1 = direct dependencies only; omit for the full transitive closure)"
+        )]
+        context_depth: Option<usize>,
+
         #[arg(help = "Optional list of draft names (without .md extension)")]
         names: Vec<String>,
     },
@@ -133,12 +253,49 @@ enum CreateCommands {
         )]
         max_compile_fix_attempts: u32,
 
+        #[arg(
+            long,
+            help = "Print the execution plan as JSON and exit without running any agent"
+        )]
+        build_plan: bool,
+
+        #[arg(
+            long,
+            help = "Bound the dependency context to N hops upstream (1 = direct dependencies only; omit for the full transitive closure)"
+        )]
+        context_depth: Option<usize>,
+
+        #[arg(
+            long,
+            help = "Inject missing dependencies detected in generated code into Cargo.toml instead of only reporting them"
+        )]
+        fix_deps: bool,
+
+        #[arg(
+            long,
+            help = "Generate a Cargo workspace with one member crate per top-level specification folder instead of a single crate"
+        )]
+        workspace: bool,
+
+        #[arg(
+            long,
+            help = "Run rustfmt and cargo check against the generated project, reporting issues against their originating specs"
+        )]
+        verify: bool,
+
         #[arg(help = "Optional list of context names (without .md extension)")]
         names: Vec<String>,
     },
 
     #[command(about = "Create tests from context files", alias = "test")]
     Tests {
+        #[arg(
+            long,
+            default_value_t = 3,
+            help = "Maximum automatic test-fix attempts after test generation"
+        )]
+        max_test_fix_attempts: u32,
+
         #[arg(help = "Optional list of context names (without .md extension)")]
         names: Vec<String>,
     },
@@ -152,6 +309,12 @@ struct CreateArgs {
     )]
     clear_cache: bool,
 
+    #[arg(
+        long,
+        help = "Overwrite existing specification/implementation files instead of aborting when they're already present"
+    )]
+    force: bool,
+
     #[command(subcommand)]
     command: CreateCommands,
 }
@@ -197,32 +360,71 @@ enum ReviewCommands {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let argv = cli::expand_argv_aliases(std::env::args().collect())?;
+    let cli = Cli::parse_from(argv);
+    cli::init_active_cfgs(&cli.cfg)?;
 
     let config = cli::Config {
         verbose: cli.verbose,
         dry_run: cli.dry_run,
+        fast_check: cli.fast_check,
+        package: cli.package,
+        diagnostic_format: cli.format,
+        no_cache: cli.no_cache,
+        explain_cache: cli.explain_cache,
     };
 
     match cli.command {
         Commands::Create(create_args) => match create_args.command {
-            CreateCommands::Specification { names } => {
-                cli::create_specification(names, create_args.clear_cache, &config).await?;
+            CreateCommands::Specification {
+                build_plan,
+                context_depth,
+                names,
+            } => {
+                cli::create_specification(
+                    names,
+                    create_args.clear_cache,
+                    create_args.force,
+                    build_plan,
+                    context_depth,
+                    &config,
+                )
+                .await?;
             }
             CreateCommands::Implementation {
                 max_compile_fix_attempts,
+                build_plan,
+                context_depth,
+                fix_deps,
+                workspace,
+                verify,
                 names,
             } => {
                 cli::create_implementation(
                     names,
                     max_compile_fix_attempts as usize,
                     create_args.clear_cache,
+                    create_args.force,
+                    build_plan,
+                    context_depth,
+                    fix_deps,
+                    workspace,
+                    verify,
                     &config,
                 )
                 .await?;
             }
-            CreateCommands::Tests { names } => {
-                cli::create_tests(names, create_args.clear_cache, &config).await?;
+            CreateCommands::Tests {
+                max_test_fix_attempts,
+                names,
+            } => {
+                cli::create_tests(
+                    names,
+                    max_test_fix_attempts as usize,
+                    create_args.clear_cache,
+                    &config,
+                )
+                .await?;
             }
         },
         Commands::Check(check_cmd) => match check_cmd {
@@ -243,15 +445,37 @@ async fn main() -> Result<()> {
         } => {
             cli::fix(max_compile_fix_attempts as usize, &config).await?;
         }
-        Commands::Compile => {
-            cli::compile(&config).await?;
+        Commands::Checkpoint => {
+            cli::checkpoint(&config).await?;
         }
-        Commands::Run { args } => {
-            cli::run(args, &config).await?;
+        Commands::Commit => {
+            cli::commit(&config).await?;
         }
-        Commands::Test => {
-            cli::test(&config).await?;
+        Commands::Rollback => {
+            cli::rollback(&config).await?;
         }
+        Commands::Compile { check, release } => {
+            cli::compile(check, release, &config).await?;
+        }
+        Commands::Run { release, args } => {
+            cli::run(args, release, &config).await?;
+        }
+        Commands::Test {
+            release,
+            runner,
+            status_level,
+            filter,
+        } => {
+            cli::test(release, runner, status_level, filter, &config).await?;
+        }
+        Commands::Watch => {
+            cli::watch(&config).await?;
+        }
+        Commands::Cache(cache_cmd) => match cache_cmd {
+            CacheCommands::Gc => {
+                cli::cache_gc(&config).await?;
+            }
+        },
         Commands::Clear(clear_cmd) => match clear_cmd {
             ClearCommands::Cache(target) => match target {
                 ClearCacheTargets::Specification { names } => {
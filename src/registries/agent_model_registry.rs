@@ -8,6 +8,28 @@ use std::path::PathBuf;
 pub struct AgentConfig {
     pub model: String,
     pub parallel: bool,
+    pub provider: Option<String>,
+    pub platform: Option<String>,
+    pub max_tokens: Option<u32>,
+    pub temperature: Option<f64>,
+    pub endpoint: Option<String>,
+    /// Additional models to fall back to, in order, if this one fails at execution time.
+    pub fallback: Vec<AgentConfig>,
+}
+
+impl AgentConfig {
+    fn with_defaults(model: String, default_parallel: bool) -> Self {
+        Self {
+            model,
+            parallel: default_parallel,
+            provider: None,
+            platform: None,
+            max_tokens: None,
+            temperature: None,
+            endpoint: None,
+            fallback: Vec::new(),
+        }
+    }
 }
 
 /// File-based implementation of AgentModelRegistry
@@ -65,20 +87,46 @@ impl FileAgentModelRegistry {
 }
 
 impl AgentModelRegistry for FileAgentModelRegistry {
-    fn get_model(&self, agent_name: &str) -> Result<Model, ExecutionError> {
+    fn get_model(&self, agent_name: &str) -> Result<Vec<Model>, ExecutionError> {
         let registry = self.load_registry()?;
 
-        let model_name = registry
-            .get(agent_name)
-            .map(|config| config.model.clone())
-            .unwrap_or_else(|| self.default_model.clone());
+        let candidates = match registry.get(agent_name) {
+            Some(config) => std::iter::once(config)
+                .chain(config.fallback.iter())
+                .map(config_to_model)
+                .collect(),
+            None => vec![Model::new(self.default_model.clone())],
+        };
 
-        Ok(Model { name: model_name })
+        Ok(candidates)
     }
 }
 
+/// Converts a parsed [`AgentConfig`] into the [`Model`] shape `AgentRunner` expects, carrying
+/// through whichever optional settings were present in the registry.
+fn config_to_model(config: &AgentConfig) -> Model {
+    let mut model = Model::new(config.model.clone());
+    if let Some(provider) = &config.provider {
+        model = model.with_provider(provider.clone());
+    }
+    if let Some(platform) = &config.platform {
+        model = model.with_platform(platform.clone());
+    }
+    if let Some(max_tokens) = config.max_tokens {
+        model = model.with_max_tokens(max_tokens);
+    }
+    if let Some(temperature) = config.temperature {
+        model = model.with_temperature(temperature);
+    }
+    if let Some(endpoint) = &config.endpoint {
+        model = model.with_endpoint(endpoint.clone());
+    }
+    model
+}
+
 /// Parses the YAML registry file into a HashMap
-/// Supports both old format (string) and new format (object with model and parallel)
+/// Supports both old format (string) and new format (object with model, parallel, per-model
+/// settings, and an optional `fallback` list of further candidates)
 fn parse_registry(
     yaml_content: &str,
     default_model: &str,
@@ -100,34 +148,7 @@ fn parse_registry(
     if let Some(hash) = doc.as_hash() {
         for (key, value) in hash {
             if let Some(k) = key.as_str() {
-                let config = if let Some(v_str) = value.as_str() {
-                    // Old format: simple string value (model name)
-                    AgentConfig {
-                        model: v_str.to_string(),
-                        parallel: default_parallel,
-                    }
-                } else if let Some(v_hash) = value.as_hash() {
-                    // New format: object with model and parallel
-                    let model = v_hash
-                        .get(&yaml_rust::Yaml::String("model".to_string()))
-                        .and_then(|v| v.as_str())
-                        .map(|s| s.to_string())
-                        .unwrap_or_else(|| default_model.to_string());
-
-                    let parallel = v_hash
-                        .get(&yaml_rust::Yaml::String("parallel".to_string()))
-                        .and_then(|v| v.as_bool())
-                        .unwrap_or(default_parallel);
-
-                    AgentConfig { model, parallel }
-                } else {
-                    // Fallback to defaults
-                    AgentConfig {
-                        model: default_model.to_string(),
-                        parallel: default_parallel,
-                    }
-                };
-
+                let config = parse_agent_config(value, default_model, default_parallel);
                 registry.insert(k.to_string(), config);
             }
         }
@@ -136,6 +157,81 @@ fn parse_registry(
     Ok(registry)
 }
 
+/// Parses a single registry entry: either the old format (a bare string naming the model) or the
+/// new format (an object with `model`, `parallel`, per-model settings, and an optional
+/// `fallback` list of further candidates in the same two shapes).
+fn parse_agent_config(
+    value: &yaml_rust::Yaml,
+    default_model: &str,
+    default_parallel: bool,
+) -> AgentConfig {
+    if let Some(v_str) = value.as_str() {
+        // Old format: simple string value (model name)
+        return AgentConfig::with_defaults(v_str.to_string(), default_parallel);
+    }
+
+    let Some(v_hash) = value.as_hash() else {
+        return AgentConfig::with_defaults(default_model.to_string(), default_parallel);
+    };
+
+    let model = v_hash
+        .get(&yaml_rust::Yaml::String("model".to_string()))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| default_model.to_string());
+
+    let parallel = v_hash
+        .get(&yaml_rust::Yaml::String("parallel".to_string()))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(default_parallel);
+
+    let provider = v_hash
+        .get(&yaml_rust::Yaml::String("provider".to_string()))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+
+    let platform = v_hash
+        .get(&yaml_rust::Yaml::String("platform".to_string()))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+
+    let max_tokens = v_hash
+        .get(&yaml_rust::Yaml::String("max_tokens".to_string()))
+        .and_then(|v| v.as_i64())
+        .and_then(|v| u32::try_from(v).ok());
+
+    let temperature = v_hash
+        .get(&yaml_rust::Yaml::String("temperature".to_string()))
+        .and_then(|v| v.as_f64());
+
+    let endpoint = v_hash
+        .get(&yaml_rust::Yaml::String("endpoint".to_string()))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+
+    let fallback = v_hash
+        .get(&yaml_rust::Yaml::String("fallback".to_string()))
+        .and_then(|v| v.as_vec())
+        .map(|entries| {
+            entries
+                .iter()
+                .map(|entry| parse_agent_config(entry, default_model, default_parallel))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    AgentConfig {
+        model,
+        parallel,
+        provider,
+        platform,
+        max_tokens,
+        temperature,
+        endpoint,
+        fallback,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -193,4 +289,63 @@ create_implementation:
         assert!(result.is_ok());
         assert!(result.unwrap().is_empty());
     }
+
+    #[test]
+    fn test_parse_registry_with_fallback_and_settings() {
+        let yaml = r#"
+create_specifications:
+  model: gpt-4
+  provider: openai
+  max_tokens: 4096
+  temperature: 0.2
+  fallback:
+    - model: gpt-3.5-turbo
+      provider: openai
+    - claude-3-haiku
+"#;
+
+        let result = parse_registry(yaml, "default", false);
+        assert!(result.is_ok());
+
+        let registry = result.unwrap();
+        let config = registry.get("create_specifications").unwrap();
+        assert_eq!(config.model, "gpt-4");
+        assert_eq!(config.provider.as_deref(), Some("openai"));
+        assert_eq!(config.max_tokens, Some(4096));
+        assert_eq!(config.temperature, Some(0.2));
+
+        assert_eq!(config.fallback.len(), 2);
+        assert_eq!(config.fallback[0].model, "gpt-3.5-turbo");
+        assert_eq!(config.fallback[0].provider.as_deref(), Some("openai"));
+        assert_eq!(config.fallback[1].model, "claude-3-haiku");
+        assert!(config.fallback[1].provider.is_none());
+    }
+
+    #[test]
+    fn test_get_model_returns_primary_then_fallback_candidates() {
+        use std::io::Write;
+
+        let dir = std::env::temp_dir().join(format!(
+            "reen_test_agent_model_registry_{}_{}",
+            std::process::id(),
+            "fallback"
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let registry_path = dir.join("agent_model_registry.yml");
+        let mut file = std::fs::File::create(&registry_path).unwrap();
+        write!(
+            file,
+            "create_specifications:\n  model: gpt-4\n  fallback:\n    - gpt-3.5-turbo\n"
+        )
+        .unwrap();
+
+        let registry = FileAgentModelRegistry::new(Some(registry_path), None, None);
+        let candidates = registry.get_model("create_specifications").unwrap();
+
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].name, "gpt-4");
+        assert_eq!(candidates[1].name, "gpt-3.5-turbo");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
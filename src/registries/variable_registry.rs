@@ -0,0 +1,150 @@
+use crate::contexts::{PopulateError, VariableDefinition, VariableRegistry};
+use std::fs;
+use std::path::PathBuf;
+
+/// File-based implementation of VariableRegistry
+/// Loads an agent's `{{var.*}}` definitions from the `variables:` section of its YAML file
+#[derive(Clone)]
+pub struct FileVariableRegistry {
+    agents_dir: PathBuf,
+}
+
+impl FileVariableRegistry {
+    /// Creates a new FileVariableRegistry
+    ///
+    /// # Arguments
+    /// * `agents_dir` - Optional path to agents directory (defaults to "agents")
+    pub fn new(agents_dir: Option<PathBuf>) -> Self {
+        Self {
+            agents_dir: agents_dir.unwrap_or_else(|| PathBuf::from("agents")),
+        }
+    }
+}
+
+impl VariableRegistry for FileVariableRegistry {
+    fn variables(&self, agent_name: &str) -> Result<Vec<VariableDefinition>, PopulateError> {
+        let agent_path = self.agents_dir.join(format!("{}.yml", agent_name));
+
+        if !agent_path.exists() {
+            return Err(PopulateError::AgentNotFound(agent_name.to_string()));
+        }
+
+        let content = fs::read_to_string(&agent_path).map_err(|e| {
+            PopulateError::InvalidSpecification(format!(
+                "Failed to read agent specification {}: {}",
+                agent_path.display(),
+                e
+            ))
+        })?;
+
+        extract_variables(&content)
+    }
+}
+
+/// Extracts the `variables:` field from a YAML agent specification. A specification with no
+/// `variables:` section simply declares no variables, not an error.
+fn extract_variables(yaml_content: &str) -> Result<Vec<VariableDefinition>, PopulateError> {
+    use yaml_rust::YamlLoader;
+
+    let docs = YamlLoader::load_from_str(yaml_content)
+        .map_err(|e| PopulateError::InvalidSpecification(format!("Invalid YAML: {}", e)))?;
+
+    if docs.is_empty() {
+        return Err(PopulateError::InvalidSpecification(
+            "Empty YAML document".to_string(),
+        ));
+    }
+
+    let doc = &docs[0];
+    let Some(entries) = doc["variables"].as_vec() else {
+        return Ok(Vec::new());
+    };
+
+    let mut variables = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let name = entry["name"].as_str().ok_or_else(|| {
+            PopulateError::InvalidSpecification("variable entry missing 'name' field".to_string())
+        })?;
+
+        let description = entry["description"].as_str().unwrap_or("").to_string();
+        let default = entry["default"].as_str().map(|s| s.to_string());
+        let required = entry["required"].as_bool().unwrap_or(false);
+        let choices = entry["choices"].as_vec().map(|values| {
+            values
+                .iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                .collect()
+        });
+
+        variables.push(VariableDefinition {
+            name: name.to_string(),
+            description,
+            default,
+            required,
+            choices,
+        });
+    }
+
+    Ok(variables)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_variables_with_defaults_and_choices() {
+        let yaml = r#"
+name: test_agent
+variables:
+  - name: tone
+    description: Desired writing tone
+    default: neutral
+    choices:
+      - neutral
+      - formal
+      - casual
+  - name: topic
+    description: Subject to write about
+    required: true
+"#;
+
+        let variables = extract_variables(yaml).unwrap();
+        assert_eq!(variables.len(), 2);
+
+        let tone = variables.iter().find(|v| v.name == "tone").unwrap();
+        assert_eq!(tone.default.as_deref(), Some("neutral"));
+        assert_eq!(
+            tone.choices,
+            Some(vec!["neutral".to_string(), "formal".to_string(), "casual".to_string()])
+        );
+        assert!(!tone.required);
+
+        let topic = variables.iter().find(|v| v.name == "topic").unwrap();
+        assert!(topic.required);
+        assert!(topic.default.is_none());
+    }
+
+    #[test]
+    fn test_extract_variables_missing_section() {
+        let yaml = r#"
+name: test_agent
+system_prompt: Hello
+"#;
+
+        let variables = extract_variables(yaml).unwrap();
+        assert!(variables.is_empty());
+    }
+
+    #[test]
+    fn test_extract_variables_missing_name() {
+        let yaml = r#"
+name: test_agent
+variables:
+  - description: Missing a name field
+"#;
+
+        let result = extract_variables(yaml);
+        assert!(result.is_err());
+    }
+}
@@ -1,15 +1,125 @@
+use std::collections::HashMap;
+use std::fmt;
+
 use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use tracing;
 
+use crate::data::account_type::AccountType;
 use crate::data::currency::Currency;
 use crate::data::ledgerentry::LedgerEntry;
 use crate::data::Ledger;
 
-/// An immutable account view over a ledger
+/// Identifies a disputable ledger entry for this account. Entries carry no separate transaction
+/// id of their own, so disputes are keyed on the entry's own chain hash.
+pub type TxId = String;
+
+/// One entry within a `Statement`: the ledger entry itself, the signed minor-unit amount it
+/// contributed to the account (positive as sink, negative as source), and the running balance
+/// immediately after applying it.
+#[derive(Debug, Clone)]
+pub struct StatementLine {
+    pub entry: LedgerEntry,
+    pub signed_amount: i128,
+    pub running_balance: i128,
+}
+
+/// A bank-style statement over a `[from, to]` window, produced by `Account::statement`: the
+/// balance carried in from everything strictly before the window, each entry inside it in
+/// chronological order with a running balance, and the balance carried out after the last one.
+#[derive(Debug, Clone)]
+pub struct Statement {
+    pub opening_balance: i128,
+    pub lines: Vec<StatementLine>,
+    pub closing_balance: i128,
+}
+
+/// A serializable point-in-time view of an `Account`, produced by `Account::snapshot` for
+/// broadcasting over an integration boundary (websocket, bincode channel, ...) whenever the
+/// underlying ledger changes. `entries_hash` is a stable digest over the entries and dispute
+/// states that produced this snapshot, letting `changed_since` detect a real delta cheaply.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountSnapshot {
+    pub account_id: i32,
+    pub currency: Currency,
+    pub balance: i128,
+    pub held: i128,
+    pub available: i128,
+    pub frozen: bool,
+    pub as_of: DateTime<Utc>,
+    pub entries_hash: String,
+}
+
+impl AccountSnapshot {
+    /// True if any field that matters to a downstream consumer differs from `other` — `as_of`
+    /// is excluded, since it advances on every snapshot whether or not the account actually
+    /// moved.
+    pub fn changed_since(&self, other: &AccountSnapshot) -> bool {
+        self.account_id != other.account_id
+            || self.currency != other.currency
+            || self.balance != other.balance
+            || self.held != other.held
+            || self.available != other.available
+            || self.frozen != other.frozen
+            || self.entries_hash != other.entries_hash
+    }
+}
+
+/// Where a disputed entry stands in the dispute lifecycle: `Undisputed` is implicit (no entry in
+/// `Account::disputes`); an entry moves to `Disputed` via `dispute`, then to either `Resolved`
+/// (via `resolve`) or `ChargedBack` (via `chargeback`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DisputeState {
+    Disputed,
+    Resolved,
+    ChargedBack,
+}
+
+/// Errors raised by the dispute lifecycle (`Account::dispute`/`resolve`/`chargeback`). Kept
+/// distinct from `anyhow::Error` so callers can programmatically tell these apart from ordinary
+/// construction failures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisputeError {
+    /// `tx_id` does not match any ledger entry for this account.
+    UnknownTransaction(TxId),
+    /// `tx_id` has already been disputed, whether that dispute is still open, resolved or
+    /// charged back.
+    AlreadyDisputed(TxId),
+    /// `tx_id` is not currently under dispute (resolve/chargeback of an undisputed entry).
+    NotDisputed(TxId),
+}
+
+impl fmt::Display for DisputeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DisputeError::UnknownTransaction(tx_id) => {
+                write!(f, "no ledger entry found for transaction id {}", tx_id)
+            }
+            DisputeError::AlreadyDisputed(tx_id) => {
+                write!(f, "transaction {} is already under dispute", tx_id)
+            }
+            DisputeError::NotDisputed(tx_id) => {
+                write!(f, "transaction {} is not currently under dispute", tx_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DisputeError {}
+
+/// An immutable account view over a ledger, plus whatever disputes have been opened against it.
+/// Disputes are tracked in a `HashMap<TxId, DisputeState>` keyed by entry hash, updated by each
+/// `dispute`/`resolve`/`chargeback` call; those calls follow the same functional-
+/// update style as `Ledger::add_entry`, returning a new `Account` rather than mutating in place.
 #[derive(Debug, Clone)]
 pub struct Account {
     account_id: i32,
     ledger: Ledger,
+    account_type: AccountType,
+    disputes: HashMap<TxId, DisputeState>,
 }
 
 impl Account {
@@ -19,8 +129,12 @@ impl Account {
     /// - account_id must be positive
     /// - At least one entry for the account must exist on the Ledger
     /// - All entries for the account must share the same currency
-    pub fn new(account_id: i32, ledger: Ledger) -> Result<Self> {
-        tracing::info!("[Account] new, account_id={}", account_id);
+    pub fn new(account_id: i32, ledger: Ledger, account_type: AccountType) -> Result<Self> {
+        tracing::info!(
+            "[Account] new, account_id={}, account_type={:?}",
+            account_id,
+            account_type
+        );
 
         if account_id <= 0 {
             tracing::error!("[Account] new, invalid account_id={}", account_id);
@@ -59,7 +173,7 @@ impl Account {
             }
         }
 
-        Ok(Self { account_id, ledger })
+        Ok(Self { account_id, ledger, account_type, disputes: HashMap::new() })
     }
 
     /// Returns the id of the account.
@@ -68,6 +182,11 @@ impl Account {
         self.account_id
     }
 
+    /// Returns the account's bookkeeping classification.
+    pub fn account_type(&self) -> AccountType {
+        self.account_type
+    }
+
     /// Returns all ledger entries related to the account (source or sink),
     /// sorted by transaction date descending.
     pub fn transactions(&self) -> Vec<LedgerEntry> {
@@ -82,6 +201,38 @@ impl Account {
         entries
     }
 
+    /// Builds a statement over the window `[from, to]`: an opening balance (net of everything
+    /// strictly before `from`), each entry in the window in ascending timestamp order alongside
+    /// its running balance, and a closing balance after the last entry. Unlike `transactions()`,
+    /// entries are replayed ascending so the running balance accumulates forward through the
+    /// window; the netting itself reuses the same `signed_amount` rule `balance()` applies.
+    pub fn statement(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Statement {
+        tracing::info!(
+            "[Account] statement, account_id={}, from={:?}, to={:?}",
+            self.account_id,
+            from,
+            to
+        );
+
+        let entries = self.ledger.get_entries_for(self.account_id);
+
+        let mut running: i128 = entries
+            .iter()
+            .filter(|e| *e.timestamp() < from)
+            .map(|e| self.signed_amount(e))
+            .sum();
+        let opening_balance = running;
+
+        let mut lines = Vec::new();
+        for e in entries.iter().filter(|e| *e.timestamp() >= from && *e.timestamp() <= to) {
+            let signed_amount = self.signed_amount(e);
+            running += signed_amount;
+            lines.push(StatementLine { entry: e.clone(), signed_amount, running_balance: running });
+        }
+
+        Statement { opening_balance, lines, closing_balance: running }
+    }
+
     /// The currency of the account. Either None (no entries) or Some(currency).
     /// Since construction requires at least one entry, this will typically be Some(...).
     pub fn currency(&self) -> Option<Currency> {
@@ -96,6 +247,11 @@ impl Account {
 
     /// The balance of the account computed as:
     /// sum(amounts where account is sink) - sum(amounts where account is source)
+    /// minus whatever has been permanently removed by a chargeback.
+    ///
+    /// Amounts are scaled by the account's own currency exponent (see `Currency::exponent`)
+    /// rather than assuming two decimal places, since construction already guarantees every
+    /// entry shares one currency.
     ///
     /// Returned as a signed integer count of minor units.
     pub fn balance(&self) -> i128 {
@@ -104,22 +260,241 @@ impl Account {
             self.account_id
         );
 
-        let mut sum: i128 = 0;
         let entries = self.ledger.get_entries_for(self.account_id);
+        let sum: i128 = entries.iter().map(|e| self.signed_amount(e)).sum();
 
-        for e in entries.iter() {
-            // Reconstruct total minor units from amount.major() and amount.minor()
-            let units: i128 = (e.amount.major() as i128) * 100 + (e.amount.minor() as i128);
+        sum - self.charged_back_total()
+    }
+
+    /// The signed minor-unit amount `entry` contributes to this account: positive if the
+    /// account is the sink, negative if it is the source. Shared by `balance`, `balance_at`,
+    /// and `statement` so the netting rule lives in exactly one place.
+    fn signed_amount(&self, entry: &LedgerEntry) -> i128 {
+        let units = entry.amount().total_minor_units() as i128;
+        let mut signed = 0i128;
+        if matches!(entry.sink(), Some(id) if id == self.account_id) {
+            signed += units;
+        }
+        if matches!(entry.source(), Some(id) if id == self.account_id) {
+            signed -= units;
+        }
+        signed
+    }
+
+    /// The entry for `tx_id` among this account's own ledger entries, if any.
+    fn entry_for(&self, tx_id: &TxId) -> Option<LedgerEntry> {
+        self.ledger
+            .get_entries_for(self.account_id)
+            .into_iter()
+            .find(|e| e.hash() == tx_id)
+    }
+
+    /// Opens a dispute against the ledger entry identified by `tx_id`, moving its amount into the
+    /// held bucket (see `held_balance`); `balance()` is unaffected by a dispute on its own. Fails
+    /// if `tx_id` doesn't match an entry for this account, or has been disputed before (open,
+    /// resolved or charged back).
+    pub fn dispute(&self, tx_id: &TxId) -> Result<Account, DisputeError> {
+        tracing::info!("[Account] dispute, account_id={}, tx_id={}", self.account_id, tx_id);
+
+        if self.entry_for(tx_id).is_none() {
+            tracing::error!("[Account] dispute, unknown tx_id={}", tx_id);
+            return Err(DisputeError::UnknownTransaction(tx_id.clone()));
+        }
+        if self.disputes.contains_key(tx_id) {
+            tracing::error!("[Account] dispute, already disputed tx_id={}", tx_id);
+            return Err(DisputeError::AlreadyDisputed(tx_id.clone()));
+        }
+
+        let mut disputes = self.disputes.clone();
+        disputes.insert(tx_id.clone(), DisputeState::Disputed);
+        Ok(Self { disputes, ..self.clone() })
+    }
 
-            // Add if this account is the sink, subtract if it is the source
-            if matches!(e.sink, Some(id) if id == self.account_id) {
-                sum += units;
+    /// Releases a currently-open dispute on `tx_id`, returning its amount from `held_balance` to
+    /// `available_balance`. Fails if `tx_id` is not currently under dispute.
+    pub fn resolve(&self, tx_id: &TxId) -> Result<Account, DisputeError> {
+        tracing::info!("[Account] resolve, account_id={}, tx_id={}", self.account_id, tx_id);
+
+        match self.disputes.get(tx_id) {
+            Some(DisputeState::Disputed) => {
+                let mut disputes = self.disputes.clone();
+                disputes.insert(tx_id.clone(), DisputeState::Resolved);
+                Ok(Self { disputes, ..self.clone() })
+            }
+            _ => {
+                tracing::error!("[Account] resolve, not disputed tx_id={}", tx_id);
+                Err(DisputeError::NotDisputed(tx_id.clone()))
+            }
+        }
+    }
+
+    /// Charges back a currently-open dispute on `tx_id`: the held amount is removed and
+    /// subtracted from `balance()`, and the account is frozen (see `is_frozen`). Fails if
+    /// `tx_id` is not currently under dispute.
+    pub fn chargeback(&self, tx_id: &TxId) -> Result<Account, DisputeError> {
+        tracing::info!("[Account] chargeback, account_id={}, tx_id={}", self.account_id, tx_id);
+
+        match self.disputes.get(tx_id) {
+            Some(DisputeState::Disputed) => {
+                let mut disputes = self.disputes.clone();
+                disputes.insert(tx_id.clone(), DisputeState::ChargedBack);
+                Ok(Self { disputes, ..self.clone() })
             }
-            if matches!(e.source, Some(id) if id == self.account_id) {
-                sum -= units;
+            _ => {
+                tracing::error!("[Account] chargeback, not disputed tx_id={}", tx_id);
+                Err(DisputeError::NotDisputed(tx_id.clone()))
             }
         }
+    }
+
+    /// Sum, in minor units, of every entry currently held by an open dispute.
+    pub fn held_balance(&self) -> i128 {
+        tracing::info!("[Account] held_balance, account_id={}", self.account_id);
+
+        self.disputes
+            .iter()
+            .filter(|(_, state)| **state == DisputeState::Disputed)
+            .filter_map(|(tx_id, _)| self.entry_for(tx_id))
+            .map(|e| e.amount().total_minor_units() as i128)
+            .sum()
+    }
+
+    /// `balance()` minus whatever is currently held by an open dispute.
+    pub fn available_balance(&self) -> i128 {
+        tracing::info!("[Account] available_balance, account_id={}", self.account_id);
+        self.balance() - self.held_balance()
+    }
+
+    /// True once a dispute against this account has been charged back. The frozen state is
+    /// reported here and in `snapshot().frozen` only: `Account::new` does not see it (disputes
+    /// are not stored on the ledger), and the balance queries and dispute calls still operate on
+    /// a frozen account as on any other.
+    pub fn is_frozen(&self) -> bool {
+        tracing::info!("[Account] is_frozen, account_id={}", self.account_id);
+        self.disputes.values().any(|state| *state == DisputeState::ChargedBack)
+    }
+
+    /// The balance of the account as of `date`: the same sink-minus-source net as `balance()`,
+    /// but only over entries whose transaction timestamp is on or before `date`. Lets a caller
+    /// reconstruct what the account read at a past reconciliation checkpoint.
+    pub fn balance_at(&self, date: DateTime<Utc>) -> i128 {
+        tracing::info!(
+            "[Account] balance_at, account_id={}, date={:?}",
+            self.account_id,
+            date
+        );
+
+        let entries = self.ledger.get_entries_for(self.account_id);
+        entries
+            .iter()
+            .filter(|e| *e.timestamp() <= date)
+            .map(|e| self.signed_amount(e))
+            .sum()
+    }
+
+    /// Verifies a reconciliation checkpoint: fails with a descriptive error naming the account
+    /// id plus the expected and actual minor-unit balances if `balance_at(date)` doesn't equal
+    /// `expected`.
+    pub fn assert_balance_at(&self, date: DateTime<Utc>, expected: i128) -> Result<()> {
+        tracing::info!(
+            "[Account] assert_balance_at, account_id={}, date={:?}, expected={}",
+            self.account_id,
+            date,
+            expected
+        );
+
+        let actual = self.balance_at(date);
+        if actual != expected {
+            tracing::error!(
+                "[Account] assert_balance_at, mismatch for account_id={}, expected={}, found={}",
+                self.account_id,
+                expected,
+                actual
+            );
+            return Err(anyhow!(
+                "balance assertion failed for account_id={}: expected {}, found {}",
+                self.account_id,
+                expected,
+                actual
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Sum, in minor units, of every entry permanently removed from the total by a chargeback.
+    fn charged_back_total(&self) -> i128 {
+        self.disputes
+            .iter()
+            .filter(|(_, state)| **state == DisputeState::ChargedBack)
+            .filter_map(|(tx_id, _)| self.entry_for(tx_id))
+            .map(|e| e.amount().total_minor_units() as i128)
+            .sum()
+    }
+
+    /// Produces a serializable point-in-time snapshot of this account, suitable for
+    /// broadcasting to downstream consumers whenever the underlying ledger changes. See
+    /// `AccountSnapshot`.
+    pub fn snapshot(&self) -> AccountSnapshot {
+        tracing::info!("[Account] snapshot, account_id={}", self.account_id);
+
+        AccountSnapshot {
+            account_id: self.account_id,
+            currency: self.currency().expect("account invariant: at least one entry exists"),
+            balance: self.balance(),
+            held: self.held_balance(),
+            available: self.available_balance(),
+            frozen: self.is_frozen(),
+            as_of: Utc::now(),
+            entries_hash: self.entries_hash(),
+        }
+    }
+
+    /// Stable SHA256 digest over this account's own entries (by chain hash, in the order
+    /// `get_entries_for` returns them) followed by its dispute states, so the digest changes
+    /// whenever the entries feeding a snapshot or any dispute transition does.
+    fn entries_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+
+        for e in self.ledger.get_entries_for(self.account_id) {
+            hasher.update(e.hash().as_bytes());
+            hasher.update(b"|");
+        }
+
+        let mut dispute_keys: Vec<&TxId> = self.disputes.keys().collect();
+        dispute_keys.sort();
+        for tx_id in dispute_keys {
+            hasher.update(tx_id.as_bytes());
+            hasher.update(format!("={:?}|", self.disputes[tx_id]).as_bytes());
+        }
+
+        STANDARD.encode(hasher.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::amount::Amount;
+
+    fn single_entry_account(currency: Currency, major: i64, minor: i64) -> Account {
+        let amount = Amount::from_parts(major, minor, currency).unwrap();
+        let entry = LedgerEntry::create(None, Some(1), amount, Utc::now(), None, None).unwrap();
+        let ledger = Ledger::new(entry);
+        Account::new(1, ledger, AccountType::Asset).unwrap()
+    }
+
+    #[test]
+    fn test_balance_scales_by_exponent_for_zero_decimal_currency() {
+        // JPY has no minor unit at all: major=1000, minor=0 should read as exactly 1000.
+        let account = single_entry_account(Currency::JPY, 1000, 0);
+        assert_eq!(account.balance(), 1000);
+    }
 
-        sum
+    #[test]
+    fn test_balance_scales_by_exponent_for_three_decimal_currency() {
+        // BHD has three fractional digits: major=5, minor=500 should read as 5*1000+500=5500.
+        let account = single_entry_account(Currency::BHD, 5, 500);
+        assert_eq!(account.balance(), 5500);
     }
 }
\ No newline at end of file
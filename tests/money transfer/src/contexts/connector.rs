@@ -0,0 +1,196 @@
+use anyhow::{anyhow, Result};
+use rust_decimal::prelude::*;
+use tracing;
+
+use crate::contexts::{ExchangeRateProvider, MoneyTransfer};
+use crate::types::{Amount, Ledger, LedgerEntry};
+
+/// One ledger-local leg of a multi-hop route: funds move from `source_account_id` to
+/// `sink_account_id` on `ledger`, converting via `rate_provider` first if the hop crosses
+/// currencies. Chaining hops end to end (one hop's sink is the next hop's source, possibly on a
+/// different ledger) is how a payment routes across ledgers that don't share an account.
+pub struct RouteHop {
+    pub ledger: Ledger,
+    pub source_account_id: i32,
+    pub sink_account_id: i32,
+    pub rate_provider: Option<Box<dyn ExchangeRateProvider>>,
+}
+
+/// A hop whose withdrawal leg has been reserved by [`Connector::prepare`]: the unsettled
+/// withdrawal entry is already committed to the hop's ledger, and `ledger_before_hash` records
+/// where that ledger's chain stood immediately before, so [`Connector::reject`] can undo exactly
+/// this reservation with [`Ledger::rollback_to`] if a later hop fails to prepare.
+pub struct PreparedHop {
+    sink_account_id: i32,
+    ledger_before_hash: String,
+    ledger_after: Ledger,
+    withdrawal: LedgerEntry,
+    amount_out: Amount,
+    rate: Option<f64>,
+}
+
+/// Routes a payment across a chain of [`RouteHop`]s using the prepare/fulfill/reject settlement
+/// model of inter-ledger payment networks: `prepare` reserves every hop's withdrawal leg before
+/// any of it is finalized, `fulfill` settles every hop once all reservations succeeded, and
+/// `reject` releases them. `execute_route` composes the two so a failure on any hop unwinds the
+/// reservations already made on earlier hops, giving the whole route all-or-nothing semantics.
+pub struct Connector {
+    hops: Vec<RouteHop>,
+}
+
+impl Connector {
+    /// Builds a connector over `hops`, ordered from the payment's origin to its destination.
+    pub fn new(hops: Vec<RouteHop>) -> Self {
+        tracing::info!("[Connector] new, hops={}", hops.len());
+        Self { hops }
+    }
+
+    /// Reserves the withdrawal leg of every hop in order, converting `amount` into each
+    /// subsequent hop's currency as it goes. Stops at the first hop that fails to prepare and
+    /// rejects everything reserved so far, so a partial failure never leaves some hops held and
+    /// others not.
+    pub fn prepare(&self, amount: Amount) -> Result<Vec<PreparedHop>> {
+        tracing::info!("[Connector] prepare, hops={}, amount={}", self.hops.len(), amount.to_str());
+
+        let mut prepared: Vec<PreparedHop> = Vec::new();
+        let mut current_amount = amount;
+
+        for hop in &self.hops {
+            match Self::prepare_hop(hop, current_amount) {
+                Ok(p) => {
+                    current_amount = p.amount_out.clone();
+                    prepared.push(p);
+                }
+                Err(e) => {
+                    tracing::error!(
+                        "[Connector] prepare, hop failed, source={}, sink={}, error={}",
+                        hop.source_account_id,
+                        hop.sink_account_id,
+                        e
+                    );
+                    Self::reject(prepared)?;
+                    return Err(e);
+                }
+            }
+        }
+
+        tracing::debug!("[Connector] prepare, reserved {} hop(s)", prepared.len());
+        Ok(prepared)
+    }
+
+    /// Settles every hop's reserved withdrawal into its sink account, applying the per-hop
+    /// exchange rate recorded at prepare time. Returns the resulting ledger of each hop, in the
+    /// same order as the connector's route.
+    pub fn fulfill(prepared: Vec<PreparedHop>) -> Result<Vec<Ledger>> {
+        tracing::info!("[Connector] fulfill, hops={}", prepared.len());
+
+        let mut settled_ledgers = Vec::with_capacity(prepared.len());
+        for hop in prepared {
+            let settled = match hop.rate {
+                Some(rate) => hop
+                    .ledger_after
+                    .settle_converted(&hop.withdrawal, hop.sink_account_id, hop.amount_out, rate),
+                None => hop.ledger_after.settle(&hop.withdrawal, hop.sink_account_id),
+            }
+            .map_err(|e| {
+                tracing::error!("[Connector] fulfill, failed to settle hop, error={}", e);
+                e
+            })?;
+
+            let ledger = hop.ledger_after.add_entry(settled).map_err(|e| {
+                tracing::error!("[Connector] fulfill, failed to commit settled entry, error={}", e);
+                e
+            })?;
+            settled_ledgers.push(ledger);
+        }
+
+        tracing::debug!("[Connector] fulfill, settled {} hop(s)", settled_ledgers.len());
+        Ok(settled_ledgers)
+    }
+
+    /// Releases every reserved hop by rolling its ledger back to the head it had immediately
+    /// before the withdrawal was committed, undoing the hold. Returns the restored ledger of each
+    /// hop, in the same order as the connector's route.
+    pub fn reject(prepared: Vec<PreparedHop>) -> Result<Vec<Ledger>> {
+        tracing::info!("[Connector] reject, hops={}", prepared.len());
+
+        let mut restored_ledgers = Vec::with_capacity(prepared.len());
+        for hop in prepared {
+            let (restored, _undone) = hop.ledger_after.rollback_to(&hop.ledger_before_hash).map_err(|e| {
+                tracing::error!("[Connector] reject, failed to roll back hop, error={}", e);
+                e
+            })?;
+            restored_ledgers.push(restored);
+        }
+
+        tracing::debug!("[Connector] reject, released {} hop(s)", restored_ledgers.len());
+        Ok(restored_ledgers)
+    }
+
+    /// Routes `amount` across every hop atomically: prepares all hops, then fulfills them if
+    /// every reservation succeeded. `prepare` already unwinds and surfaces the error if any hop
+    /// fails to reserve, so a caller only ever observes either a fully settled route or no change
+    /// to any ledger at all.
+    pub fn execute_route(&self, amount: Amount) -> Result<Vec<Ledger>> {
+        tracing::info!("[Connector] execute_route, hops={}, amount={}", self.hops.len(), amount.to_str());
+
+        let prepared = self.prepare(amount)?;
+        Self::fulfill(prepared)
+    }
+
+    fn prepare_hop(hop: &RouteHop, amount_in: Amount) -> Result<PreparedHop> {
+        tracing::debug!(
+            "[Connector] prepare_hop, source={}, sink={}, amount={}",
+            hop.source_account_id,
+            hop.sink_account_id,
+            amount_in.to_str()
+        );
+
+        let balance = hop
+            .ledger
+            .balance_for(hop.source_account_id)
+            .get(&amount_in.currency())
+            .copied()
+            .unwrap_or(0);
+        if balance - amount_in.total_minor_units() < 0 {
+            let msg = format!(
+                "insufficient funds on hop: requested {}, available {} minor units",
+                amount_in.to_str(),
+                balance
+            );
+            tracing::warn!("[Connector] prepare_hop, {}", msg);
+            return Err(anyhow!(msg));
+        }
+
+        let ledger_before_hash = hop.ledger.head_hash().clone();
+        let withdrawal = hop.ledger.create_entry(Some(hop.source_account_id), amount_in)?;
+        let ledger_after = hop.ledger.add_entry(withdrawal.clone())?;
+
+        let (amount_out, rate) = match hop.rate_provider.as_ref() {
+            Some(provider) => {
+                let sink_currency = hop
+                    .ledger
+                    .get_entries_for(hop.sink_account_id)
+                    .first()
+                    .map(|e| e.currency())
+                    .ok_or_else(|| anyhow!("sink account {} has undefined currency", hop.sink_account_id))?;
+
+                let (converted, rate) = MoneyTransfer::convert_amount(provider.as_ref(), withdrawal.amount(), sink_currency)?;
+                let rate_f64 = rate
+                    .to_f64()
+                    .ok_or_else(|| anyhow!("applied exchange rate could not be represented as f64"))?;
+                (converted, Some(rate_f64))
+            }
+            None => (withdrawal.amount().clone(), None),
+        };
+
+        Ok(PreparedHop {
+            sink_account_id: hop.sink_account_id,
+            ledger_before_hash,
+            ledger_after,
+            withdrawal,
+            amount_out,
+            rate,
+        })
+    }
+}
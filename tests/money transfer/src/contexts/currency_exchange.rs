@@ -0,0 +1,127 @@
+use anyhow::{anyhow, Result};
+use rust_decimal::prelude::*;
+use tracing;
+
+use crate::contexts::{Account, ExchangeRateProvider};
+use crate::types::Currency;
+
+/// A directional conversion rate: one unit of `from` is worth `rate` units of `to`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExchangeRate {
+    pub from: Currency,
+    pub to: Currency,
+    pub rate: f64,
+}
+
+/// A table of known `ExchangeRate`s, usable as a `MoneyTransfer` `ExchangeRateProvider`.
+/// `rate()` looks up a direct `from -> to` entry first; if none is present it falls back to the
+/// inverse of a `to -> from` entry, so a single quoted direction covers both ways.
+pub struct CurrencyExchange {
+    rates: Vec<ExchangeRate>,
+}
+
+impl CurrencyExchange {
+    /// Builds a table from a fixed list of quoted rates.
+    pub fn new(rates: Vec<ExchangeRate>) -> Self {
+        tracing::info!("[CurrencyExchange] new, rates={}", rates.len());
+        Self { rates }
+    }
+}
+
+impl ExchangeRateProvider for CurrencyExchange {
+    fn rate(&self, from: Currency, to: Currency) -> Result<Decimal> {
+        tracing::debug!("[CurrencyExchange] rate, from={:?}, to={:?}", from, to);
+
+        if let Some(r) = self.rates.iter().find(|r| r.from == from && r.to == to) {
+            return Decimal::from_f64(r.rate)
+                .ok_or_else(|| anyhow!("rate {} for {:?}->{:?} is not representable", r.rate, from, to));
+        }
+
+        if let Some(r) = self.rates.iter().find(|r| r.from == to && r.to == from) {
+            let direct = Decimal::from_f64(r.rate)
+                .ok_or_else(|| anyhow!("rate {} for {:?}->{:?} is not representable", r.rate, to, from))?;
+            if direct.is_zero() {
+                return Err(anyhow!("cannot invert a zero rate for {:?}->{:?}", to, from));
+            }
+            return Ok(Decimal::ONE / direct);
+        }
+
+        Err(anyhow!("no exchange rate available for {:?}->{:?} (direct or inverse)", from, to))
+    }
+}
+
+/// Converts each of `accounts`' own `balance()` (each single-currency, per `Account`'s
+/// invariant) into `base` and sums them, giving one consolidated net-worth figure across
+/// accounts held in different currencies. An account already denominated in `base` is summed
+/// as-is, with no lookup needed.
+///
+/// Looks up a direct `from -> base` rate in `rates` first; if none is quoted, falls back to
+/// composing through a single intermediate currency that has a leg both from `from` and on to
+/// `base`. Fails, naming the currency pair, if neither a direct nor a composed rate exists.
+pub fn consolidated_balance(accounts: &[Account], base: Currency, rates: &[ExchangeRate]) -> Result<i128> {
+    tracing::info!(
+        "[CurrencyExchange] consolidated_balance, accounts={}, base={:?}",
+        accounts.len(),
+        base
+    );
+
+    let mut total: i128 = 0;
+    for account in accounts {
+        let currency = account.currency().ok_or_else(|| {
+            anyhow!("account {} has no currency (no ledger entries)", account.account_id())
+        })?;
+
+        let minor_units = account.balance();
+        let converted = if currency == base {
+            minor_units
+        } else {
+            convert_minor_units(currency, minor_units, base, rates)?
+        };
+
+        total += converted;
+    }
+
+    tracing::debug!("[CurrencyExchange] consolidated_balance, total={}", total);
+    Ok(total)
+}
+
+/// Converts `minor_units` (denominated in `from`) into `to`'s minor units at whatever rate
+/// `resolve_rate` finds, rounding to `to`'s own minor-unit exponent so the result is a
+/// deterministic integer regardless of account ordering.
+fn convert_minor_units(from: Currency, minor_units: i128, to: Currency, rates: &[ExchangeRate]) -> Result<i128> {
+    let rate = resolve_rate(rates, from, to)?;
+
+    let minor_units: i64 = minor_units
+        .try_into()
+        .map_err(|_| anyhow!("balance in {:?} overflows i64 minor units", from))?;
+
+    let source_value = Decimal::new(minor_units, from.exponent());
+    let converted_value = (source_value * rate).round_dp(to.exponent());
+
+    let scale = Decimal::new(10i64.pow(to.exponent()), 0);
+    (converted_value * scale)
+        .to_i128()
+        .ok_or_else(|| anyhow!("converted amount overflowed i128"))
+}
+
+/// Looks up a conversion rate from `from` to `to` in `rates`: a direct quote first, then a rate
+/// composed through any single intermediate currency with a leg both from `from` and on to `to`.
+fn resolve_rate(rates: &[ExchangeRate], from: Currency, to: Currency) -> Result<Decimal> {
+    if let Some(r) = rates.iter().find(|r| r.from == from && r.to == to) {
+        return Decimal::from_f64(r.rate)
+            .ok_or_else(|| anyhow!("rate {} for {:?}->{:?} is not representable", r.rate, from, to));
+    }
+
+    for leg in rates.iter().filter(|r| r.from == from) {
+        if let Some(second) = rates.iter().find(|r| r.from == leg.to && r.to == to) {
+            let first_rate = Decimal::from_f64(leg.rate)
+                .ok_or_else(|| anyhow!("rate {} for {:?}->{:?} is not representable", leg.rate, leg.from, leg.to))?;
+            let second_rate = Decimal::from_f64(second.rate).ok_or_else(|| {
+                anyhow!("rate {} for {:?}->{:?} is not representable", second.rate, second.from, second.to)
+            })?;
+            return Ok(first_rate * second_rate);
+        }
+    }
+
+    Err(anyhow!("no exchange rate path from {:?} to {:?}", from, to))
+}
@@ -1,8 +1,16 @@
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
+use rust_decimal::prelude::*;
 use tracing;
 
 use crate::contexts::Account;
-use crate::types::{Amount, Ledger, LedgerEntry};
+use crate::types::{AccountType, Amount, Currency, Ledger, LedgerEntry};
+
+/// Supplies a conversion rate for cross-currency transfers. `rate(from, to)` must return how
+/// many units of `to` one unit of `from` is worth, so the caller converts via
+/// `converted = source_amount * rate`.
+pub trait ExchangeRateProvider {
+    fn rate(&self, from: Currency, to: Currency) -> Result<Decimal>;
+}
 
 #[cfg(feature = "money_transfer")]
 pub struct MoneyTransfer {
@@ -13,6 +21,8 @@ pub struct MoneyTransfer {
     // Props
     amount: Amount,
     ledger: Ledger,
+    rate_provider: Option<Box<dyn ExchangeRateProvider>>,
+    transfer_id: Option<String>,
 }
 
 #[cfg(feature = "money_transfer")]
@@ -28,24 +38,7 @@ impl MoneyTransfer {
             amount.to_str()
         );
 
-        // Construct accounts
-        let source = Account::new(source_account_id, ledger.clone()).map_err(|e| {
-            tracing::error!(
-                "[MoneyTransfer] new, failed to construct source account, source_id={}, error={}",
-                source_account_id,
-                e
-            );
-            e
-        })?;
-
-        let sink = Account::new(sink_account_id, ledger.clone()).map_err(|e| {
-            tracing::error!(
-                "[MoneyTransfer] new, failed to construct sink account, sink_id={}, error={}",
-                sink_account_id,
-                e
-            );
-            e
-        })?;
+        let (source, sink) = Self::construct_accounts(sink_account_id, source_account_id, &ledger)?;
 
         // Validate business rule: currency of amount must match currency of source
         let source_currency = source.currency().ok_or_else(|| {
@@ -79,11 +72,77 @@ impl MoneyTransfer {
             sink,
             amount,
             ledger,
+            rate_provider: None,
+            transfer_id: None,
         })
     }
 
-    /// Executes the transfer: withdraws from source, deposits to sink, and adds the entry to the ledger.
-    pub fn Transfer(&self) -> Result<Ledger> {
+    /// Constructs a MoneyTransfer that may move funds between accounts held in different
+    /// currencies: `rate_provider` is consulted for the source-to-sink conversion rate at
+    /// transfer time, so unlike `new`, no same-currency check is performed up front.
+    pub fn new_with_rate_provider(
+        sink_account_id: i32,
+        source_account_id: i32,
+        amount: Amount,
+        ledger: Ledger,
+        rate_provider: Box<dyn ExchangeRateProvider>,
+    ) -> Result<Self> {
+        tracing::info!(
+            "[MoneyTransfer] new_with_rate_provider, sink={}, source={}, amount={}",
+            sink_account_id,
+            source_account_id,
+            amount.to_str()
+        );
+
+        let (source, sink) = Self::construct_accounts(sink_account_id, source_account_id, &ledger)?;
+
+        Ok(Self {
+            source,
+            sink,
+            amount,
+            ledger,
+            rate_provider: Some(rate_provider),
+            transfer_id: None,
+        })
+    }
+
+    /// Attaches a unique `transfer_id` (nonce) to this transfer, making `execute` idempotent:
+    /// a retried call with an id the ledger has recently committed returns the ledger unchanged
+    /// instead of applying the transfer a second time. See `Ledger::has_transfer_id`.
+    pub fn with_transfer_id(mut self, transfer_id: impl Into<String>) -> Self {
+        self.transfer_id = Some(transfer_id.into());
+        self
+    }
+
+    // Money transfers move funds already held in an account, so both legs are Asset accounts by
+    // default; use Account::new directly if a transfer needs a different classification.
+    fn construct_accounts(sink_account_id: i32, source_account_id: i32, ledger: &Ledger) -> Result<(Account, Account)> {
+        let source = Account::new(source_account_id, ledger.clone(), AccountType::Asset).map_err(|e| {
+            tracing::error!(
+                "[MoneyTransfer] construct_accounts, failed to construct source account, source_id={}, error={}",
+                source_account_id,
+                e
+            );
+            e
+        })?;
+
+        let sink = Account::new(sink_account_id, ledger.clone(), AccountType::Asset).map_err(|e| {
+            tracing::error!(
+                "[MoneyTransfer] construct_accounts, failed to construct sink account, sink_id={}, error={}",
+                sink_account_id,
+                e
+            );
+            e
+        })?;
+
+        Ok((source, sink))
+    }
+
+    /// Executes the transfer: withdraws from source, deposits to sink, and adds the entry to the
+    /// ledger. If a `transfer_id` was attached via `with_transfer_id` and the ledger has already
+    /// committed it recently, the transfer is not re-applied; the current ledger is returned
+    /// unchanged instead.
+    pub fn execute(&self) -> Result<Ledger> {
         tracing::info!(
             "[MoneyTransfer] transfer, source={}, sink={}, amount={}",
             self.source.account_id(),
@@ -91,9 +150,23 @@ impl MoneyTransfer {
             self.amount.to_str()
         );
 
+        if let Some(transfer_id) = &self.transfer_id {
+            if self.ledger.has_transfer_id(transfer_id) {
+                tracing::info!(
+                    "[MoneyTransfer] transfer, transfer_id={} already applied, returning ledger unchanged",
+                    transfer_id
+                );
+                return Ok(self.ledger.clone());
+            }
+        }
+
         let entry = self.withdraw()?;
         let settled = self.deposit(entry)?;
-        let new_ledger = self.ledger.add_entry(settled).map_err(|e| {
+        let new_ledger = match &self.transfer_id {
+            Some(transfer_id) => self.ledger.add_entry_with_transfer_id(settled, transfer_id),
+            None => self.ledger.add_entry(settled),
+        }
+        .map_err(|e| {
             tracing::error!(
                 "[MoneyTransfer] transfer, failed to add entry to ledger, error={}",
                 e
@@ -160,7 +233,30 @@ impl MoneyTransfer {
             self.amount.to_str()
         );
 
-        let settled = self.ledger.settle(&entry, self.sink.account_id()).map_err(|e| {
+        let settled = match self.rate_provider.as_ref() {
+            Some(provider) => {
+                let sink_currency = self.sink.currency().ok_or_else(|| {
+                    let msg = "sink account has undefined currency";
+                    tracing::error!("[MoneyTransfer] sink deposit, {}", msg);
+                    anyhow!(msg)
+                })?;
+
+                let (converted, rate) = Self::convert_amount(provider.as_ref(), &self.amount, sink_currency)?;
+                tracing::info!(
+                    "[MoneyTransfer] sink deposit, cross-currency settlement, original={}, rate={}, converted={}",
+                    self.amount.to_str(),
+                    rate,
+                    converted.to_str()
+                );
+
+                let rate_f64 = rate
+                    .to_f64()
+                    .ok_or_else(|| anyhow!("applied exchange rate could not be represented as f64"))?;
+                self.ledger.settle_converted(&entry, self.sink.account_id(), converted, rate_f64)
+            }
+            None => self.ledger.settle(&entry, self.sink.account_id()),
+        }
+        .map_err(|e| {
             tracing::error!(
                 "[MoneyTransfer] sink deposit, failed to settle ledger entry, error={}",
                 e
@@ -177,6 +273,30 @@ impl MoneyTransfer {
         Ok(settled)
     }
 
+    /// Converts `amount` (in its own currency) into `sink_currency` using `provider`'s rate,
+    /// rounding to the sink currency's minor-unit exponent. Returns the converted amount
+    /// alongside the rate that was applied, for the caller to log/record.
+    pub(crate) fn convert_amount(
+        provider: &dyn ExchangeRateProvider,
+        amount: &Amount,
+        sink_currency: Currency,
+    ) -> Result<(Amount, Decimal)> {
+        let source_currency = amount.currency();
+        let rate = provider.rate(source_currency, sink_currency)?;
+
+        let source_minor_total = amount.major() * 10i64.pow(source_currency.exponent()) + amount.minor();
+        let source_value = Decimal::new(source_minor_total, source_currency.exponent());
+
+        let converted_value = (source_value * rate).round_dp(sink_currency.exponent());
+        let sink_scale = Decimal::new(10i64.pow(sink_currency.exponent()), 0);
+        let converted_minor_total = (converted_value * sink_scale)
+            .to_i64()
+            .ok_or_else(|| anyhow!("converted amount overflowed i64"))?;
+
+        let converted = Amount::new(converted_minor_total, sink_currency)?;
+        Ok((converted, rate))
+    }
+
     // Helpers (private, internal-only)
 
     fn amount_lte(a: &Amount, b: &Amount) -> bool {
@@ -1,3 +1,8 @@
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use anyhow::{anyhow, Result};
@@ -5,7 +10,19 @@ use chrono::{DateTime, Utc};
 use tracing;
 
 use crate::types::amount::Amount;
+use crate::types::currency::Currency;
+use crate::types::ledger_file::{AccountEntry, CurrencyEntry, LedgerFile, TransactionEntry};
 use crate::types::ledgerentry::LedgerEntry;
+use crate::types::transaction::Transaction;
+
+/// Name of the environment variable consulted by [`Ledger::resolve_ledger_path`] when the caller
+/// has no explicit `-l`/`--ledger` path of their own.
+pub const REEN_LEDGER_FILE_ENV: &str = "REEN_LEDGER_FILE";
+
+/// How many recently-applied transfer ids [`Ledger::add_entry_with_transfer_id`] remembers for
+/// replay protection before evicting the oldest. Bounded so a long-lived ledger's memory use
+/// doesn't grow without limit just from idempotency bookkeeping.
+const TRANSFER_ID_RING_CAPACITY: usize = 1024;
 
 /// The Ledger is an immutable chain of ledger entries.
 /// The `head` is the current entry; `tail` represents all previous entries.
@@ -13,13 +30,25 @@ use crate::types::ledgerentry::LedgerEntry;
 pub struct Ledger {
     head: LedgerEntry,
     tail: Option<Rc<Ledger>>,
+    recent_transfer_ids: Rc<VecDeque<String>>,
 }
 
 impl Ledger {
     /// Creates a new ledger with None as tail and the provided entry as head.
     pub fn new(entry: LedgerEntry) -> Self {
         tracing::info!("[Ledger] new");
-        Self { head: entry, tail: None }
+        Self {
+            head: entry,
+            tail: None,
+            recent_transfer_ids: Rc::new(VecDeque::new()),
+        }
+    }
+
+    /// The hash of the current head entry: the `prev_hash` required of anything appended next,
+    /// and the checkpoint a caller can later pass to [`Ledger::rollback_to`] to undo everything
+    /// added after this point.
+    pub fn head_hash(&self) -> &String {
+        self.head.hash()
     }
 
     /// Returns all ledger entries where the account is either sink or source,
@@ -74,19 +103,54 @@ impl Ledger {
             return Err(anyhow!(msg));
         }
 
+        let recent_transfer_ids = self.recent_transfer_ids.clone();
         let new_ledger = Ledger {
             head: entry,
             tail: Some(Rc::new(self.clone())),
+            recent_transfer_ids,
         };
 
         tracing::debug!("[Ledger] add_entry, committed");
         Ok(new_ledger)
     }
 
+    /// True if `transfer_id` appears in the bounded window of recently-applied transfer ids
+    /// (see [`Ledger::add_entry_with_transfer_id`]). A caller retrying an idempotent operation
+    /// should treat a hit here as "already done" rather than committing a duplicate entry.
+    pub fn has_transfer_id(&self, transfer_id: &str) -> bool {
+        self.recent_transfer_ids.iter().any(|id| id == transfer_id)
+    }
+
+    /// Commits `entry` exactly like `add_entry`, additionally recording `transfer_id` in the
+    /// replay-protection ring so a future `has_transfer_id` call recognizes a retried request.
+    /// The new entry and the id are recorded together, so a failure partway through can't leave
+    /// the id marked as seen without the entry actually present on the chain, or vice versa.
+    /// Once the ring reaches [`TRANSFER_ID_RING_CAPACITY`], the oldest id is evicted to make
+    /// room for the new one.
+    pub fn add_entry_with_transfer_id(&self, entry: LedgerEntry, transfer_id: &str) -> Result<Ledger> {
+        tracing::info!("[Ledger] add_entry_with_transfer_id, transfer_id={}", transfer_id);
+
+        let mut ledger = self.add_entry(entry)?;
+
+        let mut ring = (*self.recent_transfer_ids).clone();
+        if ring.len() >= TRANSFER_ID_RING_CAPACITY {
+            ring.pop_front();
+        }
+        ring.push_back(transfer_id.to_string());
+        ledger.recent_transfer_ids = Rc::new(ring);
+
+        tracing::debug!("[Ledger] add_entry_with_transfer_id, recorded, ring_len={}", ledger.recent_transfer_ids.len());
+        Ok(ledger)
+    }
+
     /// Creates a new entry based on the input entry, setting sink to the provided account id;
     /// the timestamp of the new entry is equal to the timestamp of the original entry.
     ///
     /// Valid only for an unsettled entry (i.e., one where sink is None)
+    ///
+    /// Also enforces that settlement keeps asset identity intact: the settled entry must carry
+    /// the same currency as the entry being settled, and the source account's balance in that
+    /// currency must not go negative as a result.
     pub fn settle(&self, entry: &LedgerEntry, sink_account_id: i32) -> Result<LedgerEntry> {
         tracing::info!(
             "[Ledger] settle, sink_account_id={}, input_prev_hash_present={}",
@@ -105,6 +169,20 @@ impl Ledger {
         let amount: Amount = entry.amount().clone();
         let timestamp: DateTime<Utc> = entry.timestamp().clone();
         let prev_hash = Some(self.head.hash().clone());
+        let currency = entry.currency();
+
+        if let Some(src) = source {
+            let balance = self
+                .balance_for(src)
+                .get(&currency)
+                .copied()
+                .unwrap_or(0);
+            if balance - amount.total_minor_units() < 0 {
+                let msg = "settle validation failed: source balance in this currency would go negative";
+                tracing::error!("[Ledger] settle, error=\"{}\"", msg);
+                return Err(anyhow!(msg));
+            }
+        }
 
         tracing::debug!(
             "[Ledger] settle, constructing entry, source={:?}, sink={:?}, timestamp={:?}",
@@ -113,11 +191,154 @@ impl Ledger {
             timestamp
         );
 
-        let settled = LedgerEntry::create(sink, source, amount, timestamp, prev_hash)?;
+        let settled = LedgerEntry::create(sink, source, amount, timestamp, prev_hash, None)?;
+
+        if settled.currency() != currency {
+            let msg = "settle validation failed: resulting entry would mix currencies with the entry being settled";
+            tracing::error!("[Ledger] settle, error=\"{}\"", msg);
+            return Err(anyhow!(msg));
+        }
+
         tracing::debug!("[Ledger] settle, created settled entry");
         Ok(settled)
     }
 
+    /// Variant of [`Ledger::settle`] for cross-currency transfers: the withdrawal entry was
+    /// debited in its own currency, but `converted_amount` (already run through an exchange
+    /// rate by the caller) is what gets credited to the sink. Skips the same-currency check
+    /// `settle` enforces, since a currency change is the whole point here. `rate` is the
+    /// exchange rate that was applied, recorded on the resulting entry for auditability.
+    pub fn settle_converted(
+        &self,
+        entry: &LedgerEntry,
+        sink_account_id: i32,
+        converted_amount: Amount,
+        rate: f64,
+    ) -> Result<LedgerEntry> {
+        tracing::info!(
+            "[Ledger] settle_converted, sink_account_id={}, converted_currency={:?}, rate={}",
+            sink_account_id,
+            converted_amount.currency(),
+            rate
+        );
+
+        if entry.sink().is_some() {
+            let msg = "settle_converted validation failed: entry is already settled (sink is Some)";
+            tracing::error!("[Ledger] settle_converted, error=\"{}\"", msg);
+            return Err(anyhow!(msg));
+        }
+
+        let sink = Some(sink_account_id);
+        let source = entry.source();
+        let amount: Amount = entry.amount().clone();
+        let timestamp: DateTime<Utc> = entry.timestamp().clone();
+        let prev_hash = Some(self.head.hash().clone());
+        let currency = entry.currency();
+
+        if let Some(src) = source {
+            let balance = self
+                .balance_for(src)
+                .get(&currency)
+                .copied()
+                .unwrap_or(0);
+            if balance - amount.total_minor_units() < 0 {
+                let msg = "settle_converted validation failed: source balance in this currency would go negative";
+                tracing::error!("[Ledger] settle_converted, error=\"{}\"", msg);
+                return Err(anyhow!(msg));
+            }
+        }
+
+        tracing::debug!(
+            "[Ledger] settle_converted, constructing entry, source={:?}, sink={:?}, timestamp={:?}",
+            source,
+            sink,
+            timestamp
+        );
+
+        let settled = LedgerEntry::create(sink, source, converted_amount, timestamp, prev_hash, Some(rate))?;
+
+        tracing::debug!("[Ledger] settle_converted, created settled entry");
+        Ok(settled)
+    }
+
+    /// Computes minor-unit balances for `account`, bucketed by currency, by walking the chain
+    /// once and adding `amount.total_minor_units()` when the account is the sink and
+    /// subtracting it when it is the source. Unsettled entries (sink is `None`) are skipped
+    /// since they have not yet moved funds into any account.
+    pub fn balance_for(&self, account: i32) -> HashMap<Currency, i64> {
+        tracing::info!("[Ledger] balance_for, account={}", account);
+
+        let mut balances: HashMap<Currency, i64> = HashMap::new();
+
+        let mut current: Option<&Ledger> = Some(self);
+        while let Some(ledger) = current {
+            let entry = &ledger.head;
+
+            if entry.sink().is_some() {
+                let currency = entry.currency();
+                let minor = entry.amount().total_minor_units();
+
+                if entry.sink() == Some(account) {
+                    *balances.entry(currency).or_insert(0) += minor;
+                }
+                if entry.source() == Some(account) {
+                    *balances.entry(currency).or_insert(0) -= minor;
+                }
+            }
+
+            current = ledger.tail.as_ref().map(|rc| rc.as_ref());
+        }
+
+        balances
+    }
+
+    /// Walks the whole chain and confirms the ledger-wide double-entry invariant: for every
+    /// currency it has ever touched, the signed total across all entries (sink-side positive,
+    /// source-side negative) is zero, so no value has been silently minted or destroyed.
+    pub fn is_balanced(&self) -> bool {
+        tracing::info!("[Ledger] is_balanced");
+
+        let mut totals: HashMap<Currency, i64> = HashMap::new();
+
+        let mut current: Option<&Ledger> = Some(self);
+        while let Some(ledger) = current {
+            let entry = &ledger.head;
+            let units = entry.amount().total_minor_units();
+
+            if entry.sink().is_some() {
+                *totals.entry(entry.currency()).or_insert(0) += units;
+            }
+            if entry.source().is_some() {
+                *totals.entry(entry.currency()).or_insert(0) -= units;
+            }
+
+            current = ledger.tail.as_ref().map(|rc| rc.as_ref());
+        }
+
+        totals.values().all(|&total| total == 0)
+    }
+
+    /// Commits every entry in `tx` to the chain in order, but only once the transaction balances
+    /// as a whole (see `Transaction::is_balanced`): a transaction whose debits and credits don't
+    /// net to zero per currency is rejected outright, before any of its entries touch the chain.
+    pub fn commit_transaction(&self, tx: Transaction) -> Result<Ledger> {
+        tracing::info!("[Ledger] commit_transaction, entries={}", tx.entries().len());
+
+        if !tx.is_balanced() {
+            let msg = "commit_transaction validation failed: transaction does not balance to zero per currency";
+            tracing::error!("[Ledger] commit_transaction, error=\"{}\"", msg);
+            return Err(anyhow!(msg));
+        }
+
+        let mut ledger = self.clone();
+        for entry in tx.entries().iter().cloned() {
+            ledger = ledger.add_entry(entry)?;
+        }
+
+        tracing::debug!("[Ledger] commit_transaction, committed");
+        Ok(ledger)
+    }
+
     /// Constructs a new ledger entry and returns it.
     ///
     /// Constraints:
@@ -163,7 +384,7 @@ impl Ledger {
                 timestamp
             );
 
-            let entry = LedgerEntry::create(sink, source, amount, timestamp, prev_hash)?;
+            let entry = LedgerEntry::create(sink, source, amount, timestamp, prev_hash, None)?;
             tracing::debug!("[Ledger] create_entry, entry created");
             Ok(entry)
         } else {
@@ -173,4 +394,290 @@ impl Ledger {
             Err(anyhow!(msg))
         }
     }
+
+    /// Returns the chain's entries ordered from the current head back to genesis.
+    fn entries_head_first(&self) -> Vec<LedgerEntry> {
+        let mut entries = Vec::new();
+
+        let mut current: Option<&Ledger> = Some(self);
+        while let Some(ledger) = current {
+            entries.push(ledger.head.clone());
+            current = ledger.tail.as_ref().map(|rc| rc.as_ref());
+        }
+
+        entries
+    }
+
+    /// Persists the chain to `path` as JSON, ordered genesis-first so `load` can replay it.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        tracing::info!("[Ledger] save, path={:?}", path);
+
+        let mut entries = self.entries_head_first();
+        entries.reverse();
+
+        let json = serde_json::to_string_pretty(&entries)
+            .map_err(|e| anyhow!("failed to serialize ledger chain: {}", e))?;
+
+        fs::write(path, json)
+            .map_err(|e| anyhow!("failed to write ledger file {:?}: {}", path, e))?;
+
+        tracing::debug!("[Ledger] save, wrote {} entries", entries.len());
+        Ok(())
+    }
+
+    /// Loads a chain previously written by `save`, rebuilding it by replaying `add_entry`
+    /// entry-by-entry from genesis so every `prev_hash` link is re-checked: the predecessor's
+    /// `hash()` must equal the loaded entry's `prev_hash()`.
+    ///
+    /// Rejects the whole file with a clear error at the first broken link.
+    pub fn load(path: &Path) -> Result<Ledger> {
+        tracing::info!("[Ledger] load, path={:?}", path);
+
+        let json = fs::read_to_string(path)
+            .map_err(|e| anyhow!("failed to read ledger file {:?}: {}", path, e))?;
+
+        let entries: Vec<LedgerEntry> = serde_json::from_str(&json)
+            .map_err(|e| anyhow!("failed to parse ledger file {:?}: {}", path, e))?;
+
+        let mut iter = entries.into_iter();
+        let genesis = iter
+            .next()
+            .ok_or_else(|| anyhow!("ledger file {:?} contains no entries", path))?;
+
+        let mut ledger = Ledger::new(genesis);
+
+        for (idx, entry) in iter.enumerate() {
+            ledger = ledger.add_entry(entry).map_err(|e| {
+                anyhow!(
+                    "ledger file {:?} is corrupt: chain link broken at entry {}: {}",
+                    path,
+                    idx + 1,
+                    e
+                )
+            })?;
+        }
+
+        tracing::debug!("[Ledger] load, rebuilt chain");
+        Ok(ledger)
+    }
+
+    /// Resolves the ledger file path a CLI should read/write: `explicit` (the `-l`/`--ledger`
+    /// argument, when the caller has one) always wins; otherwise falls back to the
+    /// `REEN_LEDGER_FILE` environment variable. Errors clearly if neither is set, since a
+    /// generated command should never silently guess a ledger location.
+    pub fn resolve_ledger_path(explicit: Option<PathBuf>) -> Result<PathBuf> {
+        tracing::info!("[Ledger] resolve_ledger_path, explicit={:?}", explicit);
+
+        if let Some(path) = explicit {
+            return Ok(path);
+        }
+
+        env::var(REEN_LEDGER_FILE_ENV)
+            .map(PathBuf::from)
+            .map_err(|_| {
+                anyhow!(
+                    "no ledger file given: pass -l/--ledger or set {}",
+                    REEN_LEDGER_FILE_ENV
+                )
+            })
+    }
+
+    /// Loads a chain previously written by `to_yaml_path`. The file's `currencies` and `accounts`
+    /// sections are descriptive metadata for humans and other tools; only `transactions` (the
+    /// chain itself) is replayed, via `add_entry`, the same way `load` replays a JSON file.
+    pub fn from_yaml_path(path: &Path) -> Result<Ledger> {
+        tracing::info!("[Ledger] from_yaml_path, path={:?}", path);
+
+        let yaml = fs::read_to_string(path)
+            .map_err(|e| anyhow!("failed to read ledger file {:?}: {}", path, e))?;
+
+        let file: LedgerFile = serde_yaml::from_str(&yaml)
+            .map_err(|e| anyhow!("failed to parse ledger file {:?}: {}", path, e))?;
+
+        let mut iter = file.transactions.into_iter().map(|t| t.entry);
+        let genesis = iter
+            .next()
+            .ok_or_else(|| anyhow!("ledger file {:?} contains no transactions", path))?;
+
+        let mut ledger = Ledger::new(genesis);
+
+        for (idx, entry) in iter.enumerate() {
+            ledger = ledger.add_entry(entry).map_err(|e| {
+                anyhow!(
+                    "ledger file {:?} is corrupt: chain link broken at entry {}: {}",
+                    path,
+                    idx + 1,
+                    e
+                )
+            })?;
+        }
+
+        tracing::debug!("[Ledger] from_yaml_path, rebuilt chain");
+        Ok(ledger)
+    }
+
+    /// Persists the chain to `path` as YAML, in the schema typical CLI ledger tools use: a named
+    /// `owner`, the `currencies` and `accounts` the caller declares for human readers, and the
+    /// chain's own entries genesis-first under `transactions`.
+    pub fn to_yaml_path(
+        &self,
+        path: &Path,
+        owner: impl Into<String>,
+        currencies: Vec<CurrencyEntry>,
+        accounts: Vec<AccountEntry>,
+    ) -> Result<()> {
+        tracing::info!("[Ledger] to_yaml_path, path={:?}", path);
+
+        let mut entries = self.entries_head_first();
+        entries.reverse();
+
+        let file = LedgerFile {
+            owner: owner.into(),
+            currencies,
+            accounts,
+            transactions: entries.into_iter().map(|entry| TransactionEntry { entry }).collect(),
+        };
+
+        let yaml = serde_yaml::to_string(&file)
+            .map_err(|e| anyhow!("failed to serialize ledger chain: {}", e))?;
+
+        fs::write(path, yaml)
+            .map_err(|e| anyhow!("failed to write ledger file {:?}: {}", path, e))?;
+
+        tracing::debug!("[Ledger] to_yaml_path, wrote {} entries", file.transactions.len());
+        Ok(())
+    }
+
+    /// Serializes the chain genesis-first as newline-delimited JSON, writing each record as it
+    /// is produced instead of buffering the whole chain into one in-memory string first (the
+    /// way `save` does for its whole-file JSON array).
+    pub fn export_streaming<W: Write>(&self, mut writer: W) -> Result<()> {
+        tracing::info!("[Ledger] export_streaming");
+
+        let mut entries = self.entries_head_first();
+        entries.reverse();
+
+        for entry in &entries {
+            serde_json::to_writer(&mut writer, entry)
+                .map_err(|e| anyhow!("failed to serialize ledger entry: {}", e))?;
+            writer
+                .write_all(b"\n")
+                .map_err(|e| anyhow!("failed to write ledger entry: {}", e))?;
+        }
+
+        tracing::debug!("[Ledger] export_streaming, wrote {} entries", entries.len());
+        Ok(())
+    }
+
+    /// Reads a chain previously written by `export_streaming` one record at a time, recomputing
+    /// each entry's own hash and checking its `prev_hash` linkage as it is read, rather than
+    /// deserializing the whole stream into memory first like `load` does. Fails at the first
+    /// record whose stored hash or linkage doesn't check out, naming its index, so a huge
+    /// ledger can be validated with bounded memory.
+    pub fn import_streaming<R: Read>(reader: R) -> Result<Ledger> {
+        tracing::info!("[Ledger] import_streaming");
+
+        let mut lines = BufReader::new(reader).lines();
+
+        let first_line = lines
+            .next()
+            .transpose()
+            .map_err(|e| anyhow!("failed to read ledger stream: {}", e))?
+            .ok_or_else(|| anyhow!("ledger stream contains no entries"))?;
+        let genesis: LedgerEntry = serde_json::from_str(&first_line)
+            .map_err(|e| anyhow!("failed to parse ledger entry 0: {}", e))?;
+
+        LedgerEntry::verify_chain(std::slice::from_ref(&genesis), genesis.prev_hash().map(|s| s.as_str()))
+            .map_err(|e| anyhow!("ledger entry 0 failed self-hash verification: {}", e))?;
+
+        let mut expected_prev_hash = genesis.hash().clone();
+        let mut ledger = Ledger::new(genesis);
+
+        for (idx, line) in lines.enumerate() {
+            let idx = idx + 1;
+            let line = line
+                .map_err(|e| anyhow!("failed to read ledger stream at record {}: {}", idx, e))?;
+            let entry: LedgerEntry = serde_json::from_str(&line)
+                .map_err(|e| anyhow!("failed to parse ledger entry {}: {}", idx, e))?;
+
+            LedgerEntry::verify_chain(std::slice::from_ref(&entry), entry.prev_hash().map(|s| s.as_str()))
+                .map_err(|e| anyhow!("ledger entry {} failed self-hash verification: {}", idx, e))?;
+
+            let linked = match entry.prev_hash() {
+                Some(prev) => *prev == expected_prev_hash,
+                None => false,
+            };
+            if !linked {
+                let msg = format!("ledger stream is corrupt: chain link broken at entry {}", idx);
+                tracing::error!("[Ledger] import_streaming, error=\"{}\"", msg);
+                return Err(anyhow!(msg));
+            }
+
+            expected_prev_hash = entry.hash().clone();
+            ledger = ledger
+                .add_entry(entry)
+                .map_err(|e| anyhow!("ledger stream is corrupt: entry {} rejected: {}", idx, e))?;
+        }
+
+        tracing::debug!("[Ledger] import_streaming, rebuilt chain");
+        Ok(ledger)
+    }
+
+    /// Walks the chain from `head` back to genesis, confirming at each step that the current
+    /// entry's `prev_hash` equals the hash of the entry immediately preceding it, so tampering
+    /// can be detected without reloading from disk.
+    pub fn verify(&self) -> Result<()> {
+        tracing::info!("[Ledger] verify");
+
+        let mut current: &Ledger = self;
+        while let Some(predecessor) = current.tail.as_ref().map(|rc| rc.as_ref()) {
+            let expected = predecessor.head.hash();
+            let matches = match current.head.prev_hash() {
+                Some(prev) => prev == expected,
+                None => false,
+            };
+
+            if !matches {
+                let msg = "verify failed: prev_hash does not match the predecessor's hash";
+                tracing::error!("[Ledger] verify, error=\"{}\"", msg);
+                return Err(anyhow!(msg));
+            }
+
+            current = predecessor;
+        }
+
+        tracing::debug!("[Ledger] verify, chain intact");
+        Ok(())
+    }
+
+    /// Walks the chain from `head` looking for the entry whose `hash()` equals `target_hash`,
+    /// returning a new `Ledger` rooted at that entry alongside the undone entries (tip-first).
+    ///
+    /// Lets callers discard a disputed tip and re-apply corrected entries afterwards: the
+    /// returned ledger's head hash becomes the required `prev_hash` for replacements, so
+    /// `add_entry` validates the reattachment the same way it validates any other append.
+    pub fn rollback_to(&self, target_hash: &str) -> Result<(Ledger, Vec<LedgerEntry>)> {
+        tracing::info!("[Ledger] rollback_to, target_hash={}", target_hash);
+
+        let mut undone: Vec<LedgerEntry> = Vec::new();
+        let mut current: &Ledger = self;
+
+        loop {
+            if current.head.hash() == target_hash {
+                tracing::debug!("[Ledger] rollback_to, undone {} entries", undone.len());
+                return Ok((current.clone(), undone));
+            }
+
+            undone.push(current.head.clone());
+
+            match current.tail.as_ref() {
+                Some(rc) => current = rc.as_ref(),
+                None => {
+                    let msg = "rollback_to failed: target_hash not found in chain";
+                    tracing::error!("[Ledger] rollback_to, error=\"{}\"", msg);
+                    return Err(anyhow!(msg));
+                }
+            }
+        }
+    }
 }
\ No newline at end of file
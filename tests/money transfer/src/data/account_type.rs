@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+/// Classifies an account for double-entry bookkeeping purposes, determining which side of a
+/// transaction (debit or credit) normally increases its balance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccountType {
+    Asset,
+    Liability,
+    Equity,
+    Income,
+    Expense,
+}
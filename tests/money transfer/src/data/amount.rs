@@ -0,0 +1,85 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use tracing;
+
+use crate::data::currency::Currency;
+
+/// A monetary amount, stored as major/minor units (dollars/cents) plus the currency those
+/// units are denominated in, so `major()`/`minor()`/`to_str()` never need to guess how many
+/// fractional digits a currency carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Amount {
+    major: i64,
+    minor: i64,
+    currency: Currency,
+}
+
+impl Amount {
+    /// Constructs an `Amount` from a total minor-unit count (e.g. 25_000 cents), splitting it
+    /// into major/minor parts according to `currency`'s ISO 4217 exponent.
+    pub fn new(minor_units: i64, currency: Currency) -> Result<Self> {
+        tracing::info!(
+            "[Amount] new, minor_units={}, currency={:?}",
+            minor_units,
+            currency
+        );
+
+        if minor_units < 0 {
+            return Err(anyhow!("amount must not be negative, got {}", minor_units));
+        }
+
+        let scale = 10i64.pow(currency.exponent());
+        Self::from_parts(minor_units / scale, minor_units % scale, currency)
+    }
+
+    /// Constructs an `Amount` directly from pre-split major/minor parts, enforcing that
+    /// `minor` doesn't carry more fractional digits than `currency`'s exponent allows (e.g. a
+    /// JPY amount, whose exponent is 0, can only ever have `minor == 0`).
+    pub fn from_parts(major: i64, minor: i64, currency: Currency) -> Result<Self> {
+        let limit = 10i64.pow(currency.exponent());
+        if minor < 0 || minor >= limit {
+            return Err(anyhow!(
+                "minor units {} are out of range for {:?} (must satisfy 0 <= minor < {})",
+                minor,
+                currency,
+                limit
+            ));
+        }
+
+        Ok(Self { major, minor, currency })
+    }
+
+    /// The whole-unit part of the amount (e.g. dollars).
+    pub fn major(&self) -> i64 {
+        self.major
+    }
+
+    /// The fractional-unit part of the amount (e.g. cents), always `< 10^currency.exponent()`.
+    pub fn minor(&self) -> i64 {
+        self.minor
+    }
+
+    /// The currency this amount is denominated in.
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    /// Alias for [`Amount::currency`], kept for call sites written before that name settled.
+    pub fn get_currency(&self) -> Currency {
+        self.currency()
+    }
+
+    /// The amount expressed as a single signed count of minor units (e.g. cents), combining
+    /// `major` and `minor` according to the currency's exponent. Balance/invariant math should
+    /// use this rather than `minor()` alone, which only ever holds the fractional remainder.
+    pub fn total_minor_units(&self) -> i64 {
+        self.major * 10i64.pow(self.currency.exponent()) + self.minor
+    }
+
+    /// Renders as `"{major}.{minor} CODE"`, with `minor` zero-padded to the currency's
+    /// exponent so e.g. a USD amount always shows two fractional digits.
+    pub fn to_str(&self) -> String {
+        let width = self.currency.exponent() as usize;
+        format!("{}.{:0width$} {}", self.major, self.minor, self.currency.to_str(), width = width)
+    }
+}
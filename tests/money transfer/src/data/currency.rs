@@ -1,8 +1,8 @@
 /// Currency enum representing a subset of ISO 4217 currency codes.
-/// 
+///
 /// Type Kind: Enum
 /// Mutability: Immutable
-/// 
+///
 /// Variants included based on the provided specification:
 /// - USD
 /// - EUR
@@ -11,11 +11,14 @@
 /// - CNY
 /// - AUD
 /// - CAD
-/// 
+/// - BHD
+///
 /// Notes on unspecified aspects:
 /// - The complete list of possible values is not defined beyond those above.
 /// - No validation or parsing functionalities are specified.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Currency {
     USD,
     EUR,
@@ -24,4 +27,33 @@ pub enum Currency {
     CNY,
     AUD,
     CAD,
+    /// Bahraini dinar: a three-decimal ISO 4217 currency, kept around as the fixed-point
+    /// counterpart to JPY's zero decimals for exercising `exponent()`'s edge cases.
+    BHD,
+}
+
+impl Currency {
+    /// Number of fractional digits this currency's minor unit represents, per ISO 4217 (e.g.
+    /// USD cents = 2, JPY has no subunit = 0, BHD fils = 3).
+    pub fn exponent(&self) -> u32 {
+        match self {
+            Currency::JPY => 0,
+            Currency::USD | Currency::EUR | Currency::GBP | Currency::CNY | Currency::AUD | Currency::CAD => 2,
+            Currency::BHD => 3,
+        }
+    }
+
+    /// The ISO 4217 alphabetic code, used by `Amount::to_str` and currency-match checks.
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Currency::USD => "USD",
+            Currency::EUR => "EUR",
+            Currency::JPY => "JPY",
+            Currency::GBP => "GBP",
+            Currency::CNY => "CNY",
+            Currency::AUD => "AUD",
+            Currency::CAD => "CAD",
+            Currency::BHD => "BHD",
+        }
+    }
 }
\ No newline at end of file
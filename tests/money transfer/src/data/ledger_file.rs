@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+use crate::data::account_type::AccountType;
+use crate::data::ledgerentry::LedgerEntry;
+
+/// One declared currency in a ledger file, as a typical CLI ledger tool would list it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurrencyEntry {
+    pub id: String,
+    pub name: String,
+    pub alias: String,
+    pub note: String,
+}
+
+/// One declared account in a ledger file, with its opening balance in minor units.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountEntry {
+    pub id: i32,
+    pub acct_name: String,
+    pub acct_type: AccountType,
+    pub debit_credit: i64,
+}
+
+/// One committed entry in a ledger file. The `LedgerEntry` is stored verbatim (hash, prev_hash,
+/// and all) rather than re-derived from friendlier fields, since only the original inputs to
+/// `LedgerEntry::create` can reproduce its hash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionEntry {
+    pub entry: LedgerEntry,
+}
+
+/// The on-disk shape of a ledger, read/written by `Ledger::from_yaml_path`/`to_yaml_path`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerFile {
+    pub owner: String,
+    pub currencies: Vec<CurrencyEntry>,
+    #[serde(default)]
+    pub accounts: Vec<AccountEntry>,
+    pub transactions: Vec<TransactionEntry>,
+}
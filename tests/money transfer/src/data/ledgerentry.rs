@@ -1,14 +1,96 @@
 use anyhow::{anyhow, Result};
 use base64::{engine::general_purpose::STANDARD, Engine as _};
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::fmt;
 use tracing;
 
 use crate::data::amount::Amount;
 use crate::data::currency::Currency;
 
+/// Corruption found while re-validating previously-created ledger data (loaded from disk, a
+/// stream, or otherwise handed back in from outside this process) rather than at `create` time.
+/// Carries the entry index and, where relevant, the two hashes that disagreed, so callers can
+/// report exactly what diverged instead of matching against a formatted string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CorruptionError {
+    /// The entry's `hash` field isn't canonical padded base64 (STANDARD alphabet, `=` padding).
+    NonCanonicalHash { index: usize, hash: String },
+    /// The entry's stored `hash` doesn't match the hash recomputed from its own fields.
+    SelfHashMismatch { index: usize, stored_hash: String, recomputed_hash: String },
+    /// The entry's `prev_hash` doesn't match the hash of the entry immediately before it.
+    BrokenPrevLink { index: usize, expected_hash: String, found_hash: String },
+    /// The entry's amount is zero, which `create` should never have let through.
+    ZeroAmount { index: usize },
+    /// The entry has neither a source nor a sink, which `create` should never have let through.
+    NoParty { index: usize },
+}
+
+impl fmt::Display for CorruptionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CorruptionError::NonCanonicalHash { index, hash } => write!(
+                f,
+                "entry {} has a non-canonical hash field: {:?}",
+                index, hash
+            ),
+            CorruptionError::SelfHashMismatch { index, stored_hash, recomputed_hash } => write!(
+                f,
+                "entry {} failed self-hash verification: stored={}, recomputed={}",
+                index, stored_hash, recomputed_hash
+            ),
+            CorruptionError::BrokenPrevLink { index, expected_hash, found_hash } => write!(
+                f,
+                "entry {} failed chain-linkage verification: expected prev_hash={}, found={}",
+                index, expected_hash, found_hash
+            ),
+            CorruptionError::ZeroAmount { index } => {
+                write!(f, "entry {} has a zero amount", index)
+            }
+            CorruptionError::NoParty { index } => {
+                write!(f, "entry {} has neither a source nor a sink", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CorruptionError {}
+
+/// Errors surfaced by ledger verification. Kept distinct from `anyhow::Error` (used for
+/// `create`-time business-rule violations) so callers can programmatically tell a storage-
+/// corruption fault apart from an ordinary validation failure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LedgerError {
+    Corruption(CorruptionError),
+}
+
+impl fmt::Display for LedgerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LedgerError::Corruption(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for LedgerError {}
+
+impl From<CorruptionError> for LedgerError {
+    fn from(e: CorruptionError) -> Self {
+        LedgerError::Corruption(e)
+    }
+}
+
+/// Hashing layout used by `create` when no version is requested explicitly. Bump this (and add
+/// a matching arm to `concat_for_hash`) behind a new cargo feature when the on-disk layout needs
+/// to change; existing entries keep verifying under whatever version they were stamped with.
+#[cfg(feature = "ledger_v1")]
+const DEFAULT_VERSION: u8 = 1;
+#[cfg(not(feature = "ledger_v1"))]
+const DEFAULT_VERSION: u8 = 0;
+
 /// A ledger entry records a single event in the main ledger.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LedgerEntry {
     sink: Option<i32>,
     source: Option<i32>,
@@ -16,6 +98,13 @@ pub struct LedgerEntry {
     timestamp: DateTime<Utc>,
     prev_hash: Option<String>,
     hash: String,
+    /// The `concat_for_hash` layout this entry was hashed with. Hashed in as well, so an
+    /// attacker can't downgrade a newer entry to an older, weaker layout without the
+    /// self-hash check catching it.
+    version: u8,
+    /// The exchange rate applied to produce this entry's amount, if it settled a cross-currency
+    /// transfer (see `Ledger::settle_converted`). `None` for same-currency entries.
+    applied_rate: Option<f64>,
 }
 
 impl LedgerEntry {
@@ -28,13 +117,15 @@ impl LedgerEntry {
         amount: Amount,
         timestamp: DateTime<Utc>,
         prev_hash: Option<String>,
+        applied_rate: Option<f64>,
     ) -> Result<LedgerEntry> {
         tracing::info!(
-            "[LedgerEntry] create, source={:?}, sink={:?}, timestamp={:?}, prev_hash_present={}",
+            "[LedgerEntry] create, source={:?}, sink={:?}, timestamp={:?}, prev_hash_present={}, applied_rate={:?}",
             source,
             sink,
             timestamp,
-            prev_hash.is_some()
+            prev_hash.is_some(),
+            applied_rate
         );
 
         // Business rule: At least one of sink and source must be not None.
@@ -68,7 +159,8 @@ impl LedgerEntry {
         }
 
         // Compute hash (SHA256 over concatenation of the values, excluding the hash field)
-        let concat = Self::concat_for_hash(&source, &sink, &amount, &timestamp, &prev_hash);
+        let version = DEFAULT_VERSION;
+        let concat = Self::concat_for_hash(version, &source, &sink, &amount, &timestamp, &prev_hash, &applied_rate);
         let digest = Sha256::digest(concat.as_bytes());
         let hash = STANDARD.encode(digest);
 
@@ -92,6 +184,8 @@ impl LedgerEntry {
             timestamp,
             prev_hash,
             hash,
+            version,
+            applied_rate,
         };
 
         tracing::info!("[LedgerEntry] create, success");
@@ -124,18 +218,80 @@ impl LedgerEntry {
         self.amount.get_currency()
     }
 
+    /// The account credited by this entry, if settled.
+    pub fn sink(&self) -> Option<i32> {
+        self.sink
+    }
+
+    /// The account debited by this entry, if any.
+    pub fn source(&self) -> Option<i32> {
+        self.source
+    }
+
+    /// The amount recorded by this entry.
+    pub fn amount(&self) -> &Amount {
+        &self.amount
+    }
+
+    /// The moment this entry was recorded.
+    pub fn timestamp(&self) -> &DateTime<Utc> {
+        &self.timestamp
+    }
+
+    /// The hash of the entry preceding this one in the chain, if any (genesis has none).
+    pub fn prev_hash(&self) -> Option<&String> {
+        self.prev_hash.as_ref()
+    }
+
+    /// The hash of this entry, used as the `prev_hash` of whatever entry follows it.
+    pub fn hash(&self) -> &String {
+        &self.hash
+    }
+
+    /// The hashing layout this entry was created under (see `concat_for_hash`).
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// The exchange rate applied to produce this entry, if any (see `applied_rate`).
+    pub fn applied_rate(&self) -> Option<f64> {
+        self.applied_rate
+    }
+
     // Helper: Concatenate field values (excluding hash) into a stable string for hashing.
+    // Dispatches on `version` so entries created under an older layout keep verifying after
+    // the layout changes; the version byte itself is always part of the hashed bytes.
     fn concat_for_hash(
+        version: u8,
+        source: &Option<i32>,
+        sink: &Option<i32>,
+        amount: &Amount,
+        timestamp: &DateTime<Utc>,
+        prev_hash: &Option<String>,
+        applied_rate: &Option<f64>,
+    ) -> String {
+        tracing::debug!("[LedgerEntry] concat_for_hash, version={}", version);
+        match version {
+            1 => Self::concat_for_hash_v1(source, sink, amount, timestamp, prev_hash, applied_rate),
+            _ => Self::concat_for_hash_v0(source, sink, amount, timestamp, prev_hash, applied_rate),
+        }
+    }
+
+    // Original layout (version 0): amount is hashed via its combined "{major}.{minor} CODE"
+    // rendering.
+    fn concat_for_hash_v0(
         source: &Option<i32>,
         sink: &Option<i32>,
         amount: &Amount,
         timestamp: &DateTime<Utc>,
         prev_hash: &Option<String>,
+        applied_rate: &Option<f64>,
     ) -> String {
-        tracing::debug!("[LedgerEntry] concat_for_hash");
         let mut s = String::new();
 
-        // Use a clear, deterministic representation
+        s.push_str("ver=0");
+        s.push('|');
+
         s.push_str("ts=");
         s.push_str(&timestamp.timestamp_nanos().to_string());
         s.push('|');
@@ -163,6 +319,75 @@ impl LedgerEntry {
             Some(h) => s.push_str(h),
             None => s.push_str("None"),
         }
+        s.push('|');
+
+        s.push_str("rate=");
+        match applied_rate {
+            Some(r) => s.push_str(&r.to_string()),
+            None => s.push_str("None"),
+        }
+
+        s
+    }
+
+    // Newer layout (version 1, behind the `ledger_v1` feature): hashes amount's major/minor/
+    // currency fields individually instead of through `Amount::to_str`, so formatting changes
+    // to `to_str` can no longer affect the hash.
+    fn concat_for_hash_v1(
+        source: &Option<i32>,
+        sink: &Option<i32>,
+        amount: &Amount,
+        timestamp: &DateTime<Utc>,
+        prev_hash: &Option<String>,
+        applied_rate: &Option<f64>,
+    ) -> String {
+        let mut s = String::new();
+
+        s.push_str("ver=1");
+        s.push('|');
+
+        s.push_str("ts=");
+        s.push_str(&timestamp.timestamp_nanos().to_string());
+        s.push('|');
+
+        s.push_str("src=");
+        match source {
+            Some(v) => s.push_str(&v.to_string()),
+            None => s.push_str("None"),
+        }
+        s.push('|');
+
+        s.push_str("snk=");
+        match sink {
+            Some(v) => s.push_str(&v.to_string()),
+            None => s.push_str("None"),
+        }
+        s.push('|');
+
+        s.push_str("amt_major=");
+        s.push_str(&amount.major().to_string());
+        s.push('|');
+
+        s.push_str("amt_minor=");
+        s.push_str(&amount.minor().to_string());
+        s.push('|');
+
+        s.push_str("amt_currency=");
+        s.push_str(amount.currency().to_str());
+        s.push('|');
+
+        s.push_str("prev=");
+        match prev_hash {
+            Some(h) => s.push_str(h),
+            None => s.push_str("None"),
+        }
+        s.push('|');
+
+        s.push_str("rate=");
+        match applied_rate {
+            Some(r) => s.push_str(&r.to_string()),
+            None => s.push_str("None"),
+        }
 
         s
     }
@@ -186,4 +411,82 @@ impl LedgerEntry {
 
         Ok(())
     }
+
+    /// Proves that `entries` (genesis-first, the same order `Ledger::load`/`add_entry` replays
+    /// them in) is an internally consistent hash chain: each entry's own fields, re-run through
+    /// `concat_for_hash` and SHA256, must reproduce its stored `hash`, and each entry's
+    /// `prev_hash` must equal the hash of the entry immediately before it. `genesis_prev_hash`
+    /// is what the first entry's `prev_hash` is required to equal (`None` for an ordinary
+    /// genesis entry, or a supplied value if the chain is a continuation of an earlier one).
+    ///
+    /// On the first inconsistency, fails with a `CorruptionError` carrying the offending entry's
+    /// index: an entry with no party or a zero amount, a non-canonical hash, an own hash that
+    /// didn't recompute, or a link to the previous entry that broke.
+    pub fn verify_chain(entries: &[LedgerEntry], genesis_prev_hash: Option<&str>) -> Result<(), LedgerError> {
+        tracing::info!("[LedgerEntry] verify_chain, entries={}", entries.len());
+
+        let mut expected_prev: Option<String> = genesis_prev_hash.map(|s| s.to_string());
+
+        for (idx, entry) in entries.iter().enumerate() {
+            if entry.sink.is_none() && entry.source.is_none() {
+                tracing::error!("[LedgerEntry] verify_chain, no party at index={}", idx);
+                return Err(CorruptionError::NoParty { index: idx }.into());
+            }
+
+            if entry.amount.major() == 0 && entry.amount.minor() == 0 {
+                tracing::error!("[LedgerEntry] verify_chain, zero amount at index={}", idx);
+                return Err(CorruptionError::ZeroAmount { index: idx }.into());
+            }
+
+            if Self::validate_base64_padded(&entry.hash).is_err() {
+                tracing::error!(
+                    "[LedgerEntry] verify_chain, non-canonical hash at index={}",
+                    idx
+                );
+                return Err(CorruptionError::NonCanonicalHash { index: idx, hash: entry.hash.clone() }.into());
+            }
+
+            let concat = Self::concat_for_hash(
+                entry.version,
+                &entry.source,
+                &entry.sink,
+                &entry.amount,
+                &entry.timestamp,
+                &entry.prev_hash,
+                &entry.applied_rate,
+            );
+            let digest = Sha256::digest(concat.as_bytes());
+            let recomputed = STANDARD.encode(digest);
+            if recomputed != entry.hash {
+                tracing::error!(
+                    "[LedgerEntry] verify_chain, self-hash mismatch at index={}",
+                    idx
+                );
+                return Err(CorruptionError::SelfHashMismatch {
+                    index: idx,
+                    stored_hash: entry.hash.clone(),
+                    recomputed_hash: recomputed,
+                }
+                .into());
+            }
+
+            if entry.prev_hash != expected_prev {
+                tracing::error!(
+                    "[LedgerEntry] verify_chain, broken linkage at index={}",
+                    idx
+                );
+                return Err(CorruptionError::BrokenPrevLink {
+                    index: idx,
+                    expected_hash: expected_prev.clone().unwrap_or_else(|| "None".to_string()),
+                    found_hash: entry.prev_hash.clone().unwrap_or_else(|| "None".to_string()),
+                }
+                .into());
+            }
+
+            expected_prev = Some(entry.hash.clone());
+        }
+
+        tracing::debug!("[LedgerEntry] verify_chain, chain intact");
+        Ok(())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+
+use crate::data::currency::Currency;
+use crate::data::ledgerentry::LedgerEntry;
+
+/// A group of ledger entries meant to be committed together as one unit. Double-entry
+/// bookkeeping requires that, per currency, the entries crediting a sink and the entries
+/// debiting a source net to zero; `is_balanced` checks exactly that before `Ledger` will ever
+/// commit the group (see `Ledger::commit_transaction`).
+pub struct Transaction {
+    entries: Vec<LedgerEntry>,
+}
+
+impl Transaction {
+    /// Groups `entries` into a transaction. Balance is not checked here; call `is_balanced` (or
+    /// go through `Ledger::commit_transaction`, which checks it for you) before committing.
+    pub fn new(entries: Vec<LedgerEntry>) -> Self {
+        Self { entries }
+    }
+
+    /// The entries that make up this transaction.
+    pub fn entries(&self) -> &[LedgerEntry] {
+        &self.entries
+    }
+
+    /// True if, for every currency touched by this transaction, the signed total of its entries
+    /// (sink-side positive, source-side negative) is zero.
+    pub fn is_balanced(&self) -> bool {
+        let mut totals: HashMap<Currency, i64> = HashMap::new();
+
+        for entry in &self.entries {
+            let units = entry.amount().total_minor_units();
+
+            if entry.sink().is_some() {
+                *totals.entry(entry.currency()).or_insert(0) += units;
+            }
+            if entry.source().is_some() {
+                *totals.entry(entry.currency()).or_insert(0) -= units;
+            }
+        }
+
+        totals.values().all(|&total| total == 0)
+    }
+}
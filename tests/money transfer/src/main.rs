@@ -6,7 +6,7 @@ use chrono::Utc;
 
 // Prefer crate-root re-exports
 use crate::contexts::{Account, MoneyTransfer};
-use crate::data::{Amount, Currency, Ledger, LedgerEntry};
+use crate::data::{AccountType, Amount, Currency, Ledger, LedgerEntry};
 
 pub struct ThePrimaryApplication;
 
@@ -25,7 +25,7 @@ impl ThePrimaryApplication {
         // 2) Execute transfer of 250.00 DKK from 123456 (source) to 654321 (sink)
         let transfer_amount = Amount::new(25_000, Currency::DKK)?;
         let mt = MoneyTransfer::new(654_321, 123_456, transfer_amount, ledger)?;
-        let resulting_ledger = mt.transfer()?;
+        let resulting_ledger = mt.execute()?;
 
         // 3) On success, print account transactions for each account
         self.print_account_transactions(&resulting_ledger, 123_456)?;
@@ -48,6 +48,7 @@ impl ThePrimaryApplication {
             initial_amount.clone(),
             Utc::now(),
             None,
+            None,
         )?;
         let ledger = Ledger::new(entry1);
 
@@ -66,7 +67,7 @@ impl ThePrimaryApplication {
             account_id
         );
 
-        let account = Account::new(account_id, ledger)?;
+        let account = Account::new(account_id, ledger, AccountType::Asset)?;
         let txs = account.transactions();
 
         for entry in txs {